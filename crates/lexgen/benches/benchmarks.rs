@@ -5,9 +5,9 @@ use criterion::{black_box, criterion_group, criterion_main, Criterion};
 
 #[inline(never)]
 fn lex_lua(s: &str) {
-    let mut lexer = Lexer::new(s);
-    while let Some(next) = lexer.next() {
-        next;
+    let lexer = Lexer::new(s);
+    for next in lexer {
+        let _ = next;
     }
 }
 
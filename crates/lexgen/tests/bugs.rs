@@ -1,7 +1,7 @@
 mod test_utils;
 
 use lexgen::lexer;
-use lexgen_util::{LexerError, LexerErrorKind};
+use lexgen_util::{InvalidTokenDetails, LexerError, LexerErrorKind};
 use test_utils::{loc, next};
 
 #[test]
@@ -22,7 +22,7 @@ fn failure_confusion_1() {
 
         '"' => |lexer| {
             println!("matched a double quote");
-            let str = std::mem::replace(&mut lexer.state().buf, String::new());
+            let str = std::mem::take(&mut lexer.state().buf);
             lexer.return_(str)
         },
 
@@ -75,7 +75,7 @@ fn failure_confusion_2() {
         rule Comment {
             "(*" => |lexer| {
                 let depth = &mut lexer.state().comment_depth;
-                *depth =  *depth + 1;
+                *depth += 1;
                 lexer.continue_()
             },
 
@@ -84,7 +84,7 @@ fn failure_confusion_2() {
                 if *depth == 1 {
                     lexer.switch(LexerRule::Init)
                 } else {
-                    *depth = *depth - 1;
+                    *depth -= 1;
                     lexer.continue_()
                 }
             },
@@ -345,6 +345,11 @@ fn empty_rule_simpification_issue_27() {
         Some(Err(LexerError {
             location: loc(0, 0, 0),
             kind: LexerErrorKind::InvalidToken,
+            details: Some(InvalidTokenDetails {
+                char: Some('f'),
+                expected: "",
+                rule_set: "HexInt",
+            }),
         }))
     );
 }
@@ -388,14 +393,24 @@ fn failure_should_reset_state_issue_48() {
         lexer.next(),
         Some(Err(LexerError {
             location: loc(0, 1, 1),
-            kind: LexerErrorKind::InvalidToken
+            kind: LexerErrorKind::InvalidToken,
+            details: Some(InvalidTokenDetails {
+                char: Some('x'),
+                expected: "'a'",
+                rule_set: "InString",
+            }),
         }))
     );
     assert_eq!(
         lexer.next(),
         Some(Err(LexerError {
             location: loc(0, 2, 2),
-            kind: LexerErrorKind::InvalidToken
+            kind: LexerErrorKind::InvalidToken,
+            details: Some(InvalidTokenDetails {
+                char: Some('a'),
+                expected: "'s'",
+                rule_set: "Init",
+            }),
         }))
     );
     assert_eq!(lexer.next(), Some(Ok((loc(0, 3, 3), "s", loc(0, 4, 4)))));
@@ -0,0 +1,190 @@
+mod test_utils;
+
+use lexgen::lexer;
+use lexgen_util::{InvalidTokenDetails, LexerError, LexerErrorKind};
+use test_utils::{loc, next};
+
+#[test]
+fn left_ctx_1() {
+    lexer! {
+        Lexer -> u32;
+
+        'a' < 'b' = 1,
+        'a' = 2,
+    }
+
+    let mut lexer = Lexer::new("ab");
+    assert_eq!(next(&mut lexer), Some(Ok(2)));
+    assert_eq!(next(&mut lexer), Some(Ok(1)));
+    assert_eq!(next(&mut lexer), None);
+
+    let mut lexer = Lexer::new("b");
+    assert_eq!(
+        next(&mut lexer),
+        Some(Err(LexerError {
+            location: loc(0, 0, 0),
+            kind: LexerErrorKind::InvalidToken,
+            details: Some(InvalidTokenDetails {
+                char: Some('b'),
+                expected: "'a'-'b'",
+                rule_set: "Init",
+            }),
+        }))
+    );
+
+    let mut lexer = Lexer::new("abb");
+    assert_eq!(next(&mut lexer), Some(Ok(2)));
+    assert_eq!(next(&mut lexer), Some(Ok(1)));
+    assert_eq!(
+        next(&mut lexer),
+        Some(Err(LexerError {
+            location: loc(0, 2, 2),
+            kind: LexerErrorKind::InvalidToken,
+            details: Some(InvalidTokenDetails {
+                char: Some('b'),
+                expected: "'a'-'b'",
+                rule_set: "Init",
+            }),
+        }))
+    );
+}
+
+#[test]
+fn left_ctx_2() {
+    // Left and right contexts together
+    lexer! {
+        Lexer -> u32;
+
+        'a' < 'b' > 'c' = 1,
+        'b' = 2,
+        'a' = 3,
+        'c' = 4,
+    }
+
+    let mut lexer = Lexer::new("abcab");
+    assert_eq!(next(&mut lexer), Some(Ok(3)));
+    assert_eq!(next(&mut lexer), Some(Ok(1)));
+    assert_eq!(next(&mut lexer), Some(Ok(4)));
+    assert_eq!(next(&mut lexer), Some(Ok(3)));
+    assert_eq!(next(&mut lexer), Some(Ok(2)));
+    assert_eq!(next(&mut lexer), None);
+}
+
+#[test]
+fn rust_lifetimes_and_chars() {
+    // A `'` is a char literal unless preceded by an identifier character
+    lexer! {
+        Lexer -> &'static str;
+
+        let id_char = $$ascii_alphanumeric | '_';
+
+        ' ',
+        $id_char+ = "id",
+        $id_char < '\'' = "tick",
+        '\'' _ '\'' = "char",
+    }
+
+    let input = "x'y 'a' z'";
+
+    let mut lexer = Lexer::new(input);
+    assert_eq!(next(&mut lexer), Some(Ok("id")));
+    assert_eq!(next(&mut lexer), Some(Ok("tick")));
+    assert_eq!(next(&mut lexer), Some(Ok("id")));
+    assert_eq!(next(&mut lexer), Some(Ok("char")));
+    assert_eq!(next(&mut lexer), Some(Ok("id")));
+    assert_eq!(next(&mut lexer), Some(Ok("tick")));
+    assert_eq!(next(&mut lexer), None);
+
+    // Left contexts only look at the previous character, so they work with iterator inputs
+    let mut lexer = Lexer::new_from_iter(input.chars());
+    assert_eq!(next(&mut lexer), Some(Ok("id")));
+    assert_eq!(next(&mut lexer), Some(Ok("tick")));
+    assert_eq!(next(&mut lexer), Some(Ok("id")));
+    assert_eq!(next(&mut lexer), Some(Ok("char")));
+    assert_eq!(next(&mut lexer), Some(Ok("id")));
+    assert_eq!(next(&mut lexer), Some(Ok("tick")));
+    assert_eq!(next(&mut lexer), None);
+
+    // No previous character at the beginning of the input
+    let mut lexer = Lexer::new("'");
+    assert!(matches!(next(&mut lexer), Some(Err(_))));
+}
+
+#[test]
+fn left_ctx_builtin() {
+    lexer! {
+        Lexer -> u32;
+
+        ($$ascii_digit | ')') < '-' = 1,
+        '-' = 2,
+        $$ascii_digit = 3,
+        ')' = 4,
+    }
+
+    let mut lexer = Lexer::new("-1-)-");
+    assert_eq!(next(&mut lexer), Some(Ok(2)));
+    assert_eq!(next(&mut lexer), Some(Ok(3)));
+    assert_eq!(next(&mut lexer), Some(Ok(1)));
+    assert_eq!(next(&mut lexer), Some(Ok(4)));
+    assert_eq!(next(&mut lexer), Some(Ok(1)));
+    assert_eq!(next(&mut lexer), None);
+}
+
+#[test]
+fn left_ctx_pred() {
+    // `/` starts a regex literal unless the previous token is an expression
+    #[derive(Debug, PartialEq, Eq)]
+    enum Token<'input> {
+        Id(&'input str),
+        Div,
+        Regex(&'input str),
+        LParen,
+    }
+
+    #[derive(Default)]
+    struct State {
+        after_expr: bool,
+    }
+
+    lexer! {
+        Lexer(State) -> Token<'input>;
+
+        [' ' '\n'],
+
+        ['a'-'z']+ => |lexer| {
+            lexer.state().after_expr = true;
+            let match_ = lexer.match_();
+            lexer.return_(Token::Id(match_))
+        },
+
+        '(' => |lexer| {
+            lexer.state().after_expr = false;
+            lexer.return_(Token::LParen)
+        },
+
+        { |lexer| !lexer.state().after_expr } < '/' (_ # ['/' '\n'])+ '/' => |lexer| {
+            lexer.state().after_expr = true;
+            let match_ = lexer.match_();
+            lexer.return_(Token::Regex(match_))
+        },
+
+        '/' => |lexer| {
+            lexer.state().after_expr = false;
+            lexer.return_(Token::Div)
+        },
+    }
+
+    let mut lexer = Lexer::new("a / b / c");
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Id("a"))));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Div)));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Id("b"))));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Div)));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Id("c"))));
+    assert_eq!(next(&mut lexer), None);
+
+    let mut lexer = Lexer::new("/ab/ (/b c/");
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Regex("/ab/"))));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::LParen)));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Regex("/b c/"))));
+    assert_eq!(next(&mut lexer), None);
+}
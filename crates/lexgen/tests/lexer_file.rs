@@ -0,0 +1,36 @@
+mod test_utils;
+
+use lexgen::lexer_file;
+use lexgen_util::{InvalidTokenDetails, LexerError, LexerErrorKind};
+use test_utils::{loc, next};
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    Plus,
+    Minus,
+    Int(u32),
+}
+
+lexer_file!("tests/lexers/arith.lex");
+
+#[test]
+fn lexer_file() {
+    let mut lexer = Lexer::new("1 + 23 # comment\n- 4 ?");
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Int(1))));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Plus)));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Int(23))));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Minus)));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Int(4))));
+    assert_eq!(
+        next(&mut lexer),
+        Some(Err(LexerError {
+            location: loc(1, 4, 21),
+            kind: LexerErrorKind::InvalidToken,
+            details: Some(InvalidTokenDetails {
+                char: Some('?'),
+                expected: "'\\t'-'\\n', ' ', '#', '+', '-', '0'-'9'",
+                rule_set: "Init",
+            }),
+        }))
+    );
+}
@@ -96,19 +96,14 @@ struct LexerState {
     in_comment: bool,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 enum Quote {
+    // arbitrary
+    #[default]
     Single,
     Double,
 }
 
-impl Default for Quote {
-    fn default() -> Self {
-        // arbitrary
-        Quote::Single
-    }
-}
-
 lexer! {
     Lexer(LexerState) -> Token<'input>;
 
@@ -368,6 +363,8 @@ lexer! {
     }
 }
 
+// Not used in benchmarks, which include this file
+#[allow(dead_code)]
 fn ignore_pos<A, E, L>(ret: Option<Result<(L, A, L), E>>) -> Option<Result<A, E>> {
     ret.map(|res| res.map(|(_, a, _)| a))
 }
@@ -543,9 +540,9 @@ fn lex_lua_windows_line_ending() {
 #[test]
 fn lex_lua_files() {
     let str = std::fs::read_to_string("tests/test_data").unwrap();
-    let mut lexer = Lexer::new(&str);
+    let lexer = Lexer::new(&str);
     let mut i = 0;
-    while let Some(tok) = lexer.next() {
+    for tok in lexer {
         assert!(tok.is_ok());
         i += 1;
     }
@@ -1,7 +1,7 @@
 mod test_utils;
 
 use lexgen::lexer;
-use lexgen_util::{LexerError, LexerErrorKind};
+use lexgen_util::{InvalidTokenDetails, LexerError, LexerErrorKind};
 use test_utils::{loc, next};
 
 #[test]
@@ -19,6 +19,11 @@ fn right_ctx_1() {
         Some(Err(LexerError {
             location: loc(0, 1, 1),
             kind: LexerErrorKind::InvalidToken,
+            details: Some(InvalidTokenDetails {
+                char: Some('a'),
+                expected: "'a'",
+                rule_set: "Init",
+            }),
         }))
     );
 
@@ -28,6 +33,11 @@ fn right_ctx_1() {
         Some(Err(LexerError {
             location: loc(0, 0, 0),
             kind: LexerErrorKind::InvalidToken,
+            details: Some(InvalidTokenDetails {
+                char: Some('a'),
+                expected: "'a'",
+                rule_set: "Init",
+            }),
         }))
     );
 }
@@ -47,6 +57,11 @@ fn right_ctx_2() {
         Some(Err(LexerError {
             location: loc(0, 1, 1),
             kind: LexerErrorKind::InvalidToken,
+            details: Some(InvalidTokenDetails {
+                char: Some('a'),
+                expected: "'a'",
+                rule_set: "Init",
+            }),
         }))
     );
 
@@ -57,6 +72,11 @@ fn right_ctx_2() {
         Some(Err(LexerError {
             location: loc(0, 1, 1),
             kind: LexerErrorKind::InvalidToken,
+            details: Some(InvalidTokenDetails {
+                char: Some('b'),
+                expected: "'a'",
+                rule_set: "Init",
+            }),
         }))
     );
 
@@ -66,6 +86,11 @@ fn right_ctx_2() {
         Some(Err(LexerError {
             location: loc(0, 0, 0),
             kind: LexerErrorKind::InvalidToken,
+            details: Some(InvalidTokenDetails {
+                char: Some('a'),
+                expected: "'a'",
+                rule_set: "Init",
+            }),
         }))
     );
 }
@@ -88,6 +113,11 @@ fn right_ctx_3() {
         Some(Err(LexerError {
             location: loc(0, 0, 0),
             kind: LexerErrorKind::InvalidToken,
+            details: Some(InvalidTokenDetails {
+                char: Some('a'),
+                expected: "'a'",
+                rule_set: "Init",
+            }),
         }))
     );
 }
@@ -224,3 +254,209 @@ fn ligature_shaping() {
     assert_eq!(next(&mut lexer), Some(Ok(Token::NotLig("----"))));
     assert_eq!(next(&mut lexer), None);
 }
+
+#[test]
+fn right_ctx_in_binding() {
+    #[derive(Debug, PartialEq, Eq)]
+    enum Token {
+        Kw,
+        Id,
+    }
+
+    lexer! {
+        Lexer -> Token;
+
+        let id_char = ['a'-'z' '0'-'9' '_'];
+        let kw = "if" > ((_ # $id_char) | $);
+
+        [' ' '\n'],
+
+        $kw = Token::Kw,
+        $id_char+ = Token::Id,
+    }
+
+    let mut lexer = Lexer::new("if iff if");
+    assert_eq!(
+        lexer.next(),
+        Some(Ok((loc(0, 0, 0), Token::Kw, loc(0, 2, 2))))
+    );
+    assert_eq!(
+        lexer.next(),
+        Some(Ok((loc(0, 3, 3), Token::Id, loc(0, 6, 6))))
+    );
+    assert_eq!(
+        lexer.next(),
+        Some(Ok((loc(0, 7, 7), Token::Kw, loc(0, 9, 9))))
+    );
+    assert_eq!(lexer.next(), None);
+}
+
+#[test]
+fn right_ctx_in_binding_alias() {
+    lexer! {
+        Lexer -> u32;
+
+        let a = 'a' > 'b';
+        let a_alias = $a;
+
+        $a_alias = 1,
+        'a' = 2,
+        'b' = 3,
+    }
+
+    let mut lexer = Lexer::new("aab");
+    assert_eq!(next(&mut lexer), Some(Ok(2)));
+    assert_eq!(next(&mut lexer), Some(Ok(1)));
+    assert_eq!(next(&mut lexer), Some(Ok(3)));
+    assert_eq!(next(&mut lexer), None);
+}
+
+#[test]
+fn right_ctx_in_binding_with_rule_ctx() {
+    lexer! {
+        Lexer -> u32;
+
+        let a = 'a' > 'b';
+
+        'x' < $a = 1,
+        ^$a = 2,
+        'a' = 3,
+        ['b' 'x'],
+    }
+
+    let mut lexer = Lexer::new("abxabab");
+    assert_eq!(next(&mut lexer), Some(Ok(2)));
+    assert_eq!(next(&mut lexer), Some(Ok(1)));
+    assert_eq!(next(&mut lexer), Some(Ok(3)));
+    assert_eq!(next(&mut lexer), None);
+}
+
+#[test]
+fn negated_right_ctx() {
+    #[derive(Debug, PartialEq, Eq)]
+    enum Token<'input> {
+        Int(&'input str),
+        Float(&'input str),
+        Range,
+    }
+
+    lexer! {
+        Lexer -> Token<'input>;
+
+        ['0'-'9']+ '.' >! '.' => |lexer| {
+            let match_ = lexer.match_();
+            lexer.return_(Token::Float(match_))
+        },
+
+        ['0'-'9']+ => |lexer| {
+            let match_ = lexer.match_();
+            lexer.return_(Token::Int(match_))
+        },
+
+        ".." = Token::Range,
+    }
+
+    // Negated right context holds at the end of input
+    let mut lexer = Lexer::new("1.");
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Float("1."))));
+    assert_eq!(next(&mut lexer), None);
+
+    let mut lexer = Lexer::new("1.2");
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Float("1."))));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Int("2"))));
+    assert_eq!(next(&mut lexer), None);
+
+    let mut lexer = Lexer::new("1..2");
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Int("1"))));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Range)));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Int("2"))));
+    assert_eq!(next(&mut lexer), None);
+}
+
+#[test]
+fn negated_right_ctx_multi_char() {
+    lexer! {
+        Lexer -> u32;
+
+        // `-` not followed by `->`
+        '-' >! "->" = 1,
+        '-' = 2,
+        '>' = 3,
+    }
+
+    let mut lexer = Lexer::new("---");
+    assert_eq!(next(&mut lexer), Some(Ok(1)));
+    assert_eq!(next(&mut lexer), Some(Ok(1)));
+    assert_eq!(next(&mut lexer), Some(Ok(1)));
+    assert_eq!(next(&mut lexer), None);
+
+    let mut lexer = Lexer::new("--->");
+    assert_eq!(next(&mut lexer), Some(Ok(1)));
+    assert_eq!(next(&mut lexer), Some(Ok(2)));
+    assert_eq!(next(&mut lexer), Some(Ok(1)));
+    assert_eq!(next(&mut lexer), Some(Ok(3)));
+    assert_eq!(next(&mut lexer), None);
+
+    // Partial match of the right context at the end of input
+    let mut lexer = Lexer::new("--");
+    assert_eq!(next(&mut lexer), Some(Ok(1)));
+    assert_eq!(next(&mut lexer), Some(Ok(1)));
+    assert_eq!(next(&mut lexer), None);
+}
+
+#[test]
+fn negated_right_ctx_in_binding() {
+    lexer! {
+        Lexer -> u32;
+
+        let a = 'a' >! 'b';
+
+        $a = 1,
+        'a' = 2,
+        'b' = 3,
+    }
+
+    let mut lexer = Lexer::new("aab");
+    assert_eq!(next(&mut lexer), Some(Ok(1)));
+    assert_eq!(next(&mut lexer), Some(Ok(2)));
+    assert_eq!(next(&mut lexer), Some(Ok(3)));
+    assert_eq!(next(&mut lexer), None);
+}
+
+#[test]
+fn shared_right_ctx() {
+    lexer! {
+        Lexer -> u32;
+
+        let ws = [' ' '\n'];
+
+        // Same right context in different rules
+        "a" > ($ws | $) = 1,
+        "b" > ($ws | $) = 2,
+
+        // Right context that matches the empty string always holds
+        "c" > 'x'* = 3,
+
+        $ws,
+    }
+
+    let mut lexer = Lexer::new("a b\nc");
+    assert_eq!(next(&mut lexer), Some(Ok(1)));
+    assert_eq!(next(&mut lexer), Some(Ok(2)));
+    assert_eq!(next(&mut lexer), Some(Ok(3)));
+    assert_eq!(next(&mut lexer), None);
+
+    let mut lexer = Lexer::new("ab");
+    assert_eq!(
+        next(&mut lexer),
+        Some(Err(LexerError {
+            location: loc(0, 0, 0),
+            kind: LexerErrorKind::InvalidToken,
+            details: Some(InvalidTokenDetails {
+                char: Some('a'),
+                expected: "'\\n', ' ', 'a'-'c'",
+                rule_set: "Init",
+            }),
+        }))
+    );
+}
@@ -0,0 +1,142 @@
+mod test_utils;
+
+use lexgen::lexer_file;
+use lexgen_core::runtime::{InvalidToken, RuntimeLexer, Token};
+use lexgen_util::{InvalidTokenDetails, LexerError, LexerErrorKind};
+use test_utils::{loc, next};
+
+use std::ops::Range;
+
+lexer_file!("tests/lexers/runtime.lex");
+
+/// Tokens of the generated lexer, until the end of input or the first error
+fn generated_tokens(input: &str, rule: LexerRule) -> Vec<Result<(usize, Range<usize>), usize>> {
+    let mut lexer = Lexer::new_at(input, loc(0, 0, 0), None, rule);
+    let mut tokens = vec![];
+    loop {
+        match lexer.next() {
+            None => break,
+            Some(Ok((start, token, end))) => tokens.push(Ok((token, start.byte_idx..end.byte_idx))),
+            Some(Err(LexerError { location, .. })) => {
+                tokens.push(Err(location.byte_idx));
+                break;
+            }
+        }
+    }
+    tokens
+}
+
+fn runtime_tokens(
+    lexer: &RuntimeLexer,
+    input: &str,
+    rule_set: &str,
+) -> Vec<Result<(usize, Range<usize>), usize>> {
+    lexer
+        .tokens_in(rule_set, input)
+        .unwrap()
+        .map(|token| {
+            token
+                .map(|Token { rule, span }| (rule, span))
+                .map_err(|InvalidToken { location }| location)
+        })
+        .collect()
+}
+
+#[test]
+fn runtime_lexer_same_as_generated() {
+    let runtime_lexer = RuntimeLexer::new(include_str!("lexers/runtime.lex")).unwrap();
+
+    let init_inputs = [
+        "",
+        "  ",
+        "abc if x1 _y",
+        "# comment\n  # not a comment",
+        "x:abc :def",
+        "12.34 12. 12 .5",
+        "1.x",
+        "a->b - -- --- -",
+        "a-b-c a-b-d",
+        "!",
+        "! !",
+        "x !",
+        "abc ?",
+        "ab\n#",
+        "ç",
+    ];
+
+    for input in init_inputs {
+        assert_eq!(
+            runtime_tokens(&runtime_lexer, input, "Init"),
+            generated_tokens(input, LexerRule::Init),
+            "input: {:?}",
+            input
+        );
+    }
+
+    let str_inputs = ["", "abc\"", "a\\\"b\"", "abc", "a\\", "\"\"x"];
+
+    for input in str_inputs {
+        assert_eq!(
+            runtime_tokens(&runtime_lexer, input, "Str"),
+            generated_tokens(input, LexerRule::Str),
+            "input: {:?}",
+            input
+        );
+    }
+}
+
+#[test]
+fn runtime_lexer_tokens() {
+    let runtime_lexer = RuntimeLexer::new(include_str!("lexers/runtime.lex")).unwrap();
+
+    let mut tokens = runtime_lexer.tokens("if 12.");
+    assert_eq!(
+        tokens.next(),
+        Some(Ok(Token {
+            rule: 3,
+            span: 0..2
+        }))
+    );
+    assert_eq!(
+        tokens.next(),
+        Some(Ok(Token {
+            rule: 5,
+            span: 3..5
+        }))
+    );
+    assert_eq!(
+        tokens.next(),
+        Some(Ok(Token {
+            rule: 8,
+            span: 5..6
+        }))
+    );
+    assert_eq!(tokens.next(), None);
+
+    // Errors are the same as `InvalidToken` errors of the generated lexer
+    let mut lexer = Lexer::new("ab ?");
+    assert_eq!(next(&mut lexer), Some(Ok(3)));
+    assert_eq!(
+        next(&mut lexer),
+        Some(Err(LexerError {
+            location: loc(0, 3, 3),
+            kind: LexerErrorKind::InvalidToken,
+            details: Some(InvalidTokenDetails {
+                char: Some('?'),
+                expected: "'\\n', ' '-'!', '#', '-'-'.', '0'-':', '_', 'a'-'z'",
+                rule_set: "Init",
+            }),
+        }))
+    );
+
+    let mut tokens = runtime_lexer.tokens("ab ?");
+    assert_eq!(
+        tokens.next(),
+        Some(Ok(Token {
+            rule: 3,
+            span: 0..2
+        }))
+    );
+    assert_eq!(tokens.next(), Some(Err(InvalidToken { location: 3 })));
+    assert_eq!(tokens.next(), None);
+}
@@ -1,7 +1,7 @@
 mod test_utils;
 
 use lexgen::lexer;
-use lexgen_util::{LexerError, LexerErrorKind, Loc};
+use lexgen_util::{InvalidTokenDetails, LexerError, LexerErrorKind, Loc, SourceLoc, Span, Spanned};
 use test_utils::{loc, next};
 
 use std::convert::TryFrom;
@@ -232,7 +232,7 @@ fn switch_user_state() {
         rule Comment {
             "/*" => |lexer| {
                 let state = lexer.state();
-                *state = *state + 1;
+                *state += 1;
                 lexer.continue_()
             },
 
@@ -241,7 +241,7 @@ fn switch_user_state() {
                 if *state == 1 {
                     lexer.switch_and_return(LexerRule::Init, Token::Comment)
                 } else {
-                    *state = *state - 1;
+                    *state -= 1;
                     lexer.continue_()
                 }
             },
@@ -702,6 +702,166 @@ fn regex_syntax_precedence() {
     assert_eq!(next(&mut lexer), None);
 }
 
+#[test]
+fn regex_macro() {
+    lexer! {
+        Lexer -> (usize, &'input str);
+
+        let ws = regex!(r"[ \t\n]");
+
+        $ws+,
+
+        regex!(r"[a-zA-Z_]\w*") => |lexer| {
+            let match_ = lexer.match_();
+            lexer.return_((1, match_))
+        },
+
+        regex!(r"-?\d+(\.\d+)?([eE][+-]?\d{1,3})?") => |lexer| {
+            let match_ = lexer.match_();
+            lexer.return_((2, match_))
+        },
+
+        // Strings, including raw strings, are still literals
+        r"\d" => |lexer| {
+            let match_ = lexer.match_();
+            lexer.return_((3, match_))
+        },
+
+        regex!(r#""([^"\\]|\\.)*""#) > regex!(r"\s") => |lexer| {
+            let match_ = lexer.match_();
+            lexer.return_((4, match_))
+        },
+    }
+
+    let mut lexer = Lexer::new(r#"x_1 -12.5e+10 \d "a\"b" 7"#);
+    assert_eq!(next(&mut lexer), Some(Ok((1, "x_1"))));
+    assert_eq!(next(&mut lexer), Some(Ok((2, "-12.5e+10"))));
+    assert_eq!(next(&mut lexer), Some(Ok((3, "\\d"))));
+    assert_eq!(next(&mut lexer), Some(Ok((4, r#""a\"b""#))));
+    assert_eq!(next(&mut lexer), Some(Ok((2, "7"))));
+    assert_eq!(next(&mut lexer), None);
+
+    let mut lexer = Lexer::new("1e1234");
+    assert_eq!(next(&mut lexer), Some(Ok((2, "1e123"))));
+    assert_eq!(next(&mut lexer), Some(Ok((2, "4"))));
+    assert_eq!(next(&mut lexer), None);
+}
+
+#[test]
+fn regex_macro_first_rule_and_concatenation() {
+    lexer! {
+        Lexer -> (usize, &'input str);
+
+        regex!(r"\d+") => |lexer| {
+            let match_ = lexer.match_();
+            lexer.return_((1, match_))
+        },
+
+        '#' regex!(r"[0-9a-f]{2}") ':' => |lexer| {
+            let match_ = lexer.match_();
+            lexer.return_((2, match_))
+        },
+
+        ' ',
+    }
+
+    let mut lexer = Lexer::new("12 #ff: 3");
+    assert_eq!(next(&mut lexer), Some(Ok((1, "12"))));
+    assert_eq!(next(&mut lexer), Some(Ok((2, "#ff:"))));
+    assert_eq!(next(&mut lexer), Some(Ok((1, "3"))));
+    assert_eq!(next(&mut lexer), None);
+}
+
+#[test]
+fn char_set_code_points_and_references() {
+    lexer! {
+        Lexer -> (usize, &'input str);
+
+        let sign = ['+' '-'];
+
+        ' ',
+
+        // Letters with combining diacritical marks
+        [$$ascii_alphabetic 0x300-0x36F '_']+ => |lexer| {
+            let match_ = lexer.match_();
+            lexer.return_((1, match_))
+        },
+
+        [$sign $$ascii_digit]+ => |lexer| {
+            let match_ = lexer.match_();
+            lexer.return_((2, match_))
+        },
+    }
+
+    let mut lexer = Lexer::new("a\u{301}_b +12-3");
+    assert_eq!(next(&mut lexer), Some(Ok((1, "a\u{301}_b"))));
+    assert_eq!(next(&mut lexer), Some(Ok((2, "+12-3"))));
+    assert_eq!(next(&mut lexer), None);
+
+    let mut lexer = Lexer::new("a\u{370}");
+    assert_eq!(next(&mut lexer), Some(Ok((1, "a"))));
+    assert_eq!(
+        next(&mut lexer),
+        Some(Err(LexerError {
+            location: loc(0, 1, 1),
+            kind: LexerErrorKind::InvalidToken,
+            details: Some(InvalidTokenDetails {
+                char: Some('\u{370}'),
+                expected: "' ', '+', '-', '0'-'9', 'A'-'Z', '_', 'a'-'z', '\\u{300}'-'\\u{36f}'",
+                rule_set: "Init",
+            }),
+        }))
+    );
+}
+
+#[test]
+fn parameterised_bindings() {
+    lexer! {
+        Lexer -> (usize, &'input str);
+
+        let delimited(q) = $q (_ # [$q '\\'] | '\\' _)* $q;
+        let list(item, sep) = $item ($sep $item)*;
+
+        ' ',
+
+        $delimited!('"') => |lexer| {
+            let match_ = lexer.match_();
+            lexer.return_((1, match_))
+        },
+
+        $delimited!('\'') | $delimited!('`') => |lexer| {
+            let match_ = lexer.match_();
+            lexer.return_((2, match_))
+        },
+
+        '[' $list!($$ascii_digit+, ',' ' '?) ']' => |lexer| {
+            let match_ = lexer.match_();
+            lexer.return_((3, match_))
+        },
+    }
+
+    let mut lexer = Lexer::new(r#""a\"b" 'c' `\`` [1, 23,4]"#);
+    assert_eq!(next(&mut lexer), Some(Ok((1, r#""a\"b""#))));
+    assert_eq!(next(&mut lexer), Some(Ok((2, "'c'"))));
+    assert_eq!(next(&mut lexer), Some(Ok((2, "`\\``"))));
+    assert_eq!(next(&mut lexer), Some(Ok((3, "[1, 23,4]"))));
+    assert_eq!(next(&mut lexer), None);
+
+    let mut lexer = Lexer::new("'a\"");
+    assert_eq!(
+        next(&mut lexer),
+        Some(Err(LexerError {
+            location: loc(0, 0, 0),
+            kind: LexerErrorKind::InvalidToken,
+            details: Some(InvalidTokenDetails {
+                char: None,
+                expected: "any character",
+                rule_set: "Init",
+            }),
+        }))
+    );
+}
+
 #[test]
 fn any_transitions() {
     lexer! {
@@ -992,7 +1152,7 @@ fn diff_1() {
     assert!(matches!(next(&mut lexer), Some(Err(_))));
     assert!(matches!(next(&mut lexer), Some(Err(_))));
     assert!(matches!(next(&mut lexer), Some(Err(_))));
-    assert!(matches!(next(&mut lexer), None));
+    assert!(next(&mut lexer).is_none());
 }
 
 #[test]
@@ -1008,11 +1168,11 @@ fn diff_2() {
 
     let mut lexer = Lexer::new("b");
     assert_eq!(next(&mut lexer), Some(Ok("b")));
-    assert!(matches!(next(&mut lexer), None));
+    assert!(next(&mut lexer).is_none());
 
     let mut lexer = Lexer::new("a");
     assert!(matches!(next(&mut lexer), Some(Err(_))));
-    assert!(matches!(next(&mut lexer), None));
+    assert!(next(&mut lexer).is_none());
 }
 
 #[test]
@@ -1062,6 +1222,11 @@ fn diff_4() {
         Some(Err(LexerError {
             location: loc(0, 7, 7),
             kind: LexerErrorKind::InvalidToken,
+            details: Some(InvalidTokenDetails {
+                char: Some('\n'),
+                expected: "'/'",
+                rule_set: "Init",
+            }),
         }))
     );
 }
@@ -1261,3 +1426,757 @@ fn static_and_input() {
     assert_eq!(lexer.state().words, vec!["world"]);
     assert_eq!(*lexer.state().counter, 1);
 }
+
+#[test]
+fn multi_char_lookahead() {
+    lexer! {
+        Lexer -> &'static str;
+
+        ' ',
+
+        ".." => |lexer| {
+            if lexer.starts_with("=") {
+                lexer.return_("range_inclusive")
+            } else {
+                lexer.return_("range")
+            }
+        },
+
+        '=' = "eq",
+
+        '[' => |lexer| {
+            if lexer.peek_nth(1) == Some(']') {
+                lexer.return_("nested")
+            } else if lexer.peek_nth(0).is_none() {
+                lexer.return_("last")
+            } else if lexer.peek_str(10) == "] [" {
+                lexer.return_("lbracket")
+            } else {
+                lexer.return_("other")
+            }
+        },
+
+        ']' = "rbracket",
+    }
+
+    let input = "..= .. [[] [";
+
+    let mut lexer = Lexer::new(input);
+    assert_eq!(next(&mut lexer), Some(Ok("range_inclusive")));
+    assert_eq!(next(&mut lexer), Some(Ok("eq")));
+    assert_eq!(next(&mut lexer), Some(Ok("range")));
+    assert_eq!(next(&mut lexer), Some(Ok("nested")));
+    assert_eq!(next(&mut lexer), Some(Ok("lbracket")));
+    assert_eq!(next(&mut lexer), Some(Ok("rbracket")));
+    assert_eq!(next(&mut lexer), Some(Ok("last")));
+    assert_eq!(next(&mut lexer), None);
+
+    let mut lexer = Lexer::new_from_iter(input.chars());
+    assert_eq!(next(&mut lexer), Some(Ok("range_inclusive")));
+    assert_eq!(next(&mut lexer), Some(Ok("eq")));
+    assert_eq!(next(&mut lexer), Some(Ok("range")));
+    assert_eq!(next(&mut lexer), Some(Ok("nested")));
+    assert_eq!(next(&mut lexer), Some(Ok("lbracket")));
+    assert_eq!(next(&mut lexer), Some(Ok("rbracket")));
+    assert_eq!(next(&mut lexer), Some(Ok("last")));
+    assert_eq!(next(&mut lexer), None);
+}
+
+#[test]
+fn peek_str_borrows_input() {
+    lexer! {
+        Lexer -> (&'static str, String);
+
+        '#' => |lexer| {
+            let kind = match lexer.peek_str(3) {
+                std::borrow::Cow::Borrowed(_) => "borrowed",
+                std::borrow::Cow::Owned(_) => "owned",
+            };
+            let rest = lexer.peek_str(3).into_owned();
+            lexer.return_((kind, rest))
+        },
+
+        ['a'-'z'],
+    }
+
+    let mut lexer = Lexer::new("#ab");
+    assert_eq!(next(&mut lexer), Some(Ok(("borrowed", "ab".to_owned()))));
+
+    let mut lexer = Lexer::new_from_iter("#ab".chars());
+    assert_eq!(next(&mut lexer), Some(Ok(("owned", "ab".to_owned()))));
+
+    // Input starting in the middle of a document
+    let mut lexer = Lexer::new_at("#abcd", loc(2, 3, 10), Some(' '), LexerRule::Init);
+    assert_eq!(next(&mut lexer), Some(Ok(("borrowed", "abc".to_owned()))));
+}
+
+#[test]
+fn consume_input_in_semantic_actions() {
+    lexer! {
+        Lexer -> &'input str;
+
+        ' ',
+
+        // Rust raw strings
+        'r' '#'* '"' => |lexer| {
+            let n_hashes = lexer.match_().len() - 2;
+            let mut terminator = String::from("\"");
+            terminator.push_str(&"#".repeat(n_hashes));
+            if lexer.skip_until(&terminator) {
+                let match_ = lexer.match_();
+                lexer.return_(match_)
+            } else {
+                lexer.return_("unterminated")
+            }
+        },
+
+        // Line comments
+        "--" => |lexer| {
+            lexer.skip_while(|char| char != '\n');
+            let match_ = lexer.match_();
+            lexer.return_(match_)
+        },
+
+        '\n',
+
+        // Escape: consumes the next character, whatever it is
+        '\\' => |lexer| {
+            match lexer.next_char() {
+                Some(_) => {
+                    let match_ = lexer.match_();
+                    lexer.return_(match_)
+                }
+                None => lexer.return_("eof"),
+            }
+        },
+    }
+
+    let mut lexer = Lexer::new(r###"r"a" r#"b"c"# r##"d"#"## \"-- x"#"###);
+    assert_eq!(next(&mut lexer), Some(Ok(r#"r"a""#)));
+    assert_eq!(next(&mut lexer), Some(Ok(r##"r#"b"c"#"##)));
+    assert_eq!(
+        lexer.next(),
+        Some(Ok((loc(0, 14, 14), r###"r##"d"#"##"###, loc(0, 24, 24))))
+    );
+    assert_eq!(next(&mut lexer), Some(Ok("\\\"")));
+    assert_eq!(next(&mut lexer), Some(Ok("-- x\"#")));
+    assert_eq!(next(&mut lexer), None);
+
+    let mut lexer = Lexer::new("r#\"abc\" -- comment\n\\");
+    assert_eq!(next(&mut lexer), Some(Ok("unterminated")));
+    assert_eq!(next(&mut lexer), None);
+
+    let mut lexer = Lexer::new("-- comment\n\\");
+    assert_eq!(next(&mut lexer), Some(Ok("-- comment")));
+    assert_eq!(next(&mut lexer), Some(Ok("eof")));
+    assert_eq!(next(&mut lexer), None);
+}
+
+#[test]
+fn anchors() {
+    lexer! {
+        Lexer -> &'input str;
+
+        ' ',
+        '\n',
+
+        ^^ "#!" (_ # '\n')* => |lexer| {
+            let match_ = lexer.match_();
+            lexer.return_(match_)
+        },
+
+        ^ '#' $$ascii_alphabetic+ = "directive",
+
+        '#' = "hash",
+
+        $$ascii_alphabetic+ = "word",
+    }
+
+    let mut lexer = Lexer::new("#!/bin/sh\n#define x #y\n #z\n#!");
+    assert_eq!(
+        lexer.next(),
+        Some(Ok((loc(0, 0, 0), "#!/bin/sh", loc(0, 9, 9))))
+    );
+    assert_eq!(
+        lexer.next(),
+        Some(Ok((loc(1, 0, 10), "directive", loc(1, 7, 17))))
+    );
+    assert_eq!(next(&mut lexer), Some(Ok("word")));
+    assert_eq!(next(&mut lexer), Some(Ok("hash")));
+    assert_eq!(next(&mut lexer), Some(Ok("word")));
+    assert_eq!(next(&mut lexer), Some(Ok("hash")));
+    assert_eq!(next(&mut lexer), Some(Ok("word")));
+    assert_eq!(next(&mut lexer), Some(Ok("hash")));
+    assert!(matches!(next(&mut lexer), Some(Err(_))));
+}
+
+#[test]
+fn anchors_rule_sets() {
+    // Anchors are checked against the location where the current rule set started matching, not
+    // the start of the current match
+    lexer! {
+        Lexer -> &'input str;
+
+        rule Init {
+            ' ',
+            '\n',
+            "/*" => |lexer| lexer.switch(LexerRule::Comment),
+        }
+
+        rule Comment {
+            "*/" => |lexer| {
+                let match_ = lexer.match_();
+                lexer.switch_and_return(LexerRule::Init, match_)
+            },
+
+            ^ '*' => |lexer| {
+                let match_ = lexer.match_();
+                lexer.return_(match_)
+            },
+
+            _,
+        }
+    }
+
+    let mut lexer = Lexer::new("/* a\n* b */");
+    assert_eq!(
+        lexer.next(),
+        Some(Ok((loc(0, 0, 0), "/* a\n*", loc(1, 1, 6))))
+    );
+    assert_eq!(
+        lexer.next(),
+        Some(Ok((loc(1, 1, 6), " b */", loc(1, 6, 11))))
+    );
+    assert_eq!(lexer.next(), None);
+}
+
+#[test]
+fn rule_priority() {
+    #[derive(Debug, PartialEq, Eq)]
+    enum Token<'input> {
+        Id(&'input str),
+        If,
+        Else,
+    }
+
+    lexer! {
+        Lexer -> Token<'input>;
+
+        ' ',
+
+        ['a'-'z']+ => |lexer| {
+            let match_ = lexer.match_();
+            lexer.return_(Token::Id(match_))
+        },
+
+        // Without the priorities the identifier rule above would take priority
+        #[priority = 1]
+        "if" = Token::If,
+
+        #[priority = 2]
+        "else" = Token::Else,
+    }
+
+    let mut lexer = Lexer::new("if iff else elsee");
+    assert_eq!(next(&mut lexer), Some(Ok(Token::If)));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Id("iff"))));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Else)));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Id("elsee"))));
+    assert_eq!(next(&mut lexer), None);
+
+    lexer! {
+        Lexer2 -> u32;
+
+        // Negative priority makes the rule lower priority than the rules without priority
+        #[priority = -1]
+        'a' = 1,
+        'a' = 2,
+    }
+
+    let mut lexer = Lexer2::new("a");
+    assert_eq!(next(&mut lexer), Some(Ok(2)));
+    assert_eq!(next(&mut lexer), None);
+}
+
+#[test]
+fn shortest_match() {
+    #[derive(Debug, PartialEq, Eq)]
+    enum Token<'input> {
+        Comment(&'input str),
+        Other(&'input str),
+    }
+
+    lexer! {
+        Lexer -> Token<'input>;
+
+        // Without `shortest` this would match until the last `*/`
+        #[shortest]
+        "/*" _* "*/" => |lexer| {
+            let match_ = lexer.match_();
+            lexer.return_(Token::Comment(match_))
+        },
+
+        (_ # '/')+ => |lexer| {
+            let match_ = lexer.match_();
+            lexer.return_(Token::Other(match_))
+        },
+    }
+
+    let mut lexer = Lexer::new("/* a */ b /* c */");
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Comment("/* a */"))));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Other(" b "))));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Comment("/* c */"))));
+    assert_eq!(next(&mut lexer), None);
+
+    lexer! {
+        Lexer2 -> u32;
+
+        #[shortest]
+        'a'+ = 1,
+
+        // Shortest match rules don't affect other rules
+        "aaa" = 2,
+    }
+
+    let mut lexer = Lexer2::new("aaaaa");
+    assert_eq!(next(&mut lexer), Some(Ok(2)));
+    assert_eq!(next(&mut lexer), Some(Ok(1)));
+    assert_eq!(next(&mut lexer), Some(Ok(1)));
+    assert_eq!(next(&mut lexer), None);
+}
+
+#[test]
+fn keyword_table() {
+    #[derive(Debug, PartialEq, Eq)]
+    enum Token<'input> {
+        Id(&'input str),
+        If,
+        In,
+        Int,
+        While,
+    }
+
+    lexer! {
+        Lexer -> Token<'input>;
+
+        ' ',
+
+        ['a'-'z']+ keywords {
+            "while" = Token::While,
+            "if" = Token::If,
+            "in" = Token::In,
+            "int" = Token::Int,
+        } => |lexer| {
+            let match_ = lexer.match_();
+            lexer.return_(Token::Id(match_))
+        },
+    }
+
+    let mut lexer = Lexer::new("if i in int inte whil while whiles");
+    assert_eq!(next(&mut lexer), Some(Ok(Token::If)));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Id("i"))));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::In)));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Int)));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Id("inte"))));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Id("whil"))));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::While)));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Id("whiles"))));
+    assert_eq!(next(&mut lexer), None);
+}
+
+#[test]
+fn keyword_table_iter_input() {
+    #[derive(Debug, PartialEq, Eq)]
+    enum Token {
+        Id(usize),
+        If,
+        Int,
+        Lambda,
+    }
+
+    lexer! {
+        Lexer -> Token;
+
+        ' ',
+
+        ['a'-'z' 'α'-'ω' 'Λ']+ keywords {
+            "if" = Token::If,
+            "int" = Token::Int,
+            "Λ" = Token::Lambda,
+        } => |lexer| {
+            let (start, end) = lexer.match_loc();
+            lexer.return_(Token::Id(end.byte_idx - start.byte_idx))
+        },
+    }
+
+    let mut lexer = Lexer::new_from_iter("if λ Λ inte int".chars());
+    assert_eq!(next(&mut lexer), Some(Ok(Token::If)));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Id(2))));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Lambda)));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Id(4))));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Int)));
+    assert_eq!(next(&mut lexer), None);
+}
+
+#[test]
+fn keyword_table_rule_kinds() {
+    #[derive(Debug, PartialEq, Eq)]
+    enum Token {
+        Id,
+        Return,
+    }
+
+    lexer! {
+        Lexer -> Token;
+
+        type Error = String;
+
+        ' ',
+
+        // Keywords in a rule without semantic action: other matches are skipped
+        ['A'-'Z']+ keywords { "RETURN" = Token::Return },
+
+        // Keywords in a fallible rule
+        ['a'-'z']+ keywords { "return" = Token::Return, } =? |lexer| {
+            if lexer.match_() == "error" {
+                lexer.return_(Err("error".to_owned()))
+            } else {
+                lexer.return_(Ok(Token::Id))
+            }
+        },
+    }
+
+    let mut lexer = Lexer::new("ABC RETURN return abc error");
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Return)));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Return)));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Id)));
+    assert_eq!(
+        next(&mut lexer).map(|res| res.map_err(|err| err.kind)),
+        Some(Err(LexerErrorKind::Custom("error".to_owned())))
+    );
+    assert_eq!(next(&mut lexer), None);
+}
+
+#[test]
+fn invalid_token_details() {
+    lexer! {
+        Lexer -> usize;
+
+        rule Init {
+            ' ',
+
+            ['0'-'9']+ "." ['0'-'9']+ => |lexer| {
+                let len = lexer.match_().len();
+                lexer.return_(len)
+            },
+
+            "/*" => |lexer| lexer.switch(LexerRule::Comment),
+        }
+
+        rule Comment {
+            "*/" => |lexer| lexer.switch(LexerRule::Init),
+            _,
+        }
+    }
+
+    let mut lexer = Lexer::new("1.5 1.x");
+    assert_eq!(next(&mut lexer), Some(Ok(3)));
+    assert_eq!(
+        next(&mut lexer),
+        Some(Err(LexerError {
+            location: loc(0, 4, 4),
+            kind: LexerErrorKind::InvalidToken,
+            details: Some(InvalidTokenDetails {
+                char: Some('x'),
+                expected: "'0'-'9'",
+                rule_set: "Init",
+            }),
+        }))
+    );
+
+    let mut lexer = Lexer::new("1");
+    assert_eq!(
+        next(&mut lexer).unwrap().unwrap_err().details,
+        Some(InvalidTokenDetails {
+            char: None,
+            expected: "'.', '0'-'9'",
+            rule_set: "Init",
+        })
+    );
+
+    let mut lexer = Lexer::new("/* *");
+    assert_eq!(
+        next(&mut lexer).unwrap().unwrap_err().details,
+        Some(InvalidTokenDetails {
+            char: None,
+            expected: "any character",
+            rule_set: "Comment",
+        })
+    );
+
+    // Details are kept when the lexer is wrapped
+    let mut lexer = Lexer::new("1.x").with_source("main.x").spanned();
+    let err = lexer.next().unwrap().unwrap_err();
+    assert_eq!(
+        err.details.as_ref().map(|details| details.char),
+        Some(Some('x'))
+    );
+    assert_eq!(
+        err.to_string(),
+        "main.x:1:1: invalid token: unexpected 'x', expected '0'-'9'"
+    );
+}
+
+#[test]
+fn error_display() {
+    #[derive(Debug, PartialEq, Eq)]
+    struct UserError;
+
+    impl std::fmt::Display for UserError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("user error")
+        }
+    }
+
+    impl std::error::Error for UserError {}
+
+    lexer! {
+        Lexer -> ();
+
+        type Error = UserError;
+
+        ' ' | '\n',
+
+        "ok" = (),
+
+        "err" =? |lexer| lexer.return_(Err(UserError)),
+    }
+
+    assert_eq!(loc(0, 0, 0).to_string(), "1:1");
+
+    let mut lexer = Lexer::new("ok\n err x");
+    assert_eq!(next(&mut lexer), Some(Ok(())));
+
+    let err = next(&mut lexer).unwrap().unwrap_err();
+    assert_eq!(err.to_string(), "2:5: user error");
+    assert_eq!(
+        std::error::Error::source(&err).map(|err| err.to_string()),
+        Some("user error".to_owned())
+    );
+
+    let err = next(&mut lexer).unwrap().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "2:6: invalid token: unexpected 'x', expected '\\n', ' ', 'e', 'o'"
+    );
+    assert!(std::error::Error::source(&err).is_none());
+
+    // Errors can be converted to `Box<dyn Error>`
+    let err: Box<dyn std::error::Error> = Box::new(err);
+    assert_eq!(
+        err.to_string(),
+        "2:6: invalid token: unexpected 'x', expected '\\n', ' ', 'e', 'o'"
+    );
+}
+
+#[test]
+fn spanned_tokens() {
+    lexer! {
+        Lexer -> &'input str;
+
+        [' ' '\n'],
+
+        ['a'-'z']+ => |lexer| {
+            let match_ = lexer.match_();
+            lexer.return_(match_)
+        },
+    }
+
+    let tokens: Vec<Spanned<&str>> = Lexer::new("ab\n cde")
+        .spanned()
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    let ab = Span::new(loc(0, 0, 0), loc(0, 2, 2));
+    let cde = Span::from((loc(1, 1, 4), loc(1, 4, 7)));
+
+    assert_eq!(
+        tokens,
+        vec![
+            Spanned {
+                value: "ab",
+                span: ab,
+            },
+            Spanned {
+                value: "cde",
+                span: cde,
+            },
+        ]
+    );
+
+    assert_eq!(ab.byte_range(), 0..2);
+    assert_eq!(std::ops::Range::from(cde), 4..7);
+    assert_eq!(cde.len(), 3);
+    assert_eq!(cde.to_string(), "2:2-2:5");
+
+    let merged = cde.merge(ab);
+    assert_eq!(merged, Span::new(loc(0, 0, 0), loc(1, 4, 7)));
+    assert!(merged.contains(&ab));
+    assert!(merged.contains(&cde));
+    assert!(!ab.contains(&merged));
+    assert!(cde.contains_byte(4));
+    assert!(!cde.contains_byte(7));
+
+    let reversed = Span::new(loc(0, 2, 2), loc(0, 0, 0));
+    assert_eq!(reversed.len(), 0);
+    assert!(reversed.is_empty());
+}
+
+#[test]
+fn source_ids() {
+    lexer! {
+        Lexer -> char;
+
+        ' ',
+
+        ['a'-'z'] => |lexer| {
+            let char = lexer.match_().chars().next().unwrap();
+            lexer.return_(char)
+        },
+    }
+
+    let mut lexer = Lexer::new("a b!").with_source("main.x");
+    assert_eq!(lexer.source(), &"main.x");
+    assert_eq!(
+        lexer.next(),
+        Some(Ok((
+            SourceLoc {
+                source: "main.x",
+                loc: loc(0, 0, 0),
+            },
+            'a',
+            SourceLoc {
+                source: "main.x",
+                loc: loc(0, 1, 1),
+            },
+        )))
+    );
+
+    let mut lexer = lexer.spanned();
+    assert_eq!(
+        lexer.next(),
+        Some(Ok(Spanned {
+            value: 'b',
+            span: Span::with_source(loc(0, 2, 2), loc(0, 3, 3), "main.x"),
+        }))
+    );
+    assert_eq!(
+        Span::with_source(loc(0, 2, 2), loc(0, 3, 3), "main.x").to_string(),
+        "1:3-1:4"
+    );
+
+    let err = lexer.next().unwrap().unwrap_err();
+    assert_eq!(
+        err,
+        LexerError {
+            location: SourceLoc {
+                source: "main.x",
+                loc: loc(0, 3, 3),
+            },
+            kind: LexerErrorKind::InvalidToken,
+            details: Some(InvalidTokenDetails {
+                char: Some('!'),
+                expected: "' ', 'a'-'z'",
+                rule_set: "Init",
+            }),
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "main.x:1:4: invalid token: unexpected '!', expected ' ', 'a'-'z'"
+    );
+}
+
+#[test]
+fn start_at_location() {
+    lexer! {
+        Lexer -> &'input str;
+
+        rule Init {
+            [' ' '\n'],
+
+            ['a'-'z']+ => |lexer| {
+                let match_ = lexer.match_();
+                lexer.return_(match_)
+            },
+
+            '"' => |lexer| lexer.switch(LexerRule::String),
+        }
+
+        rule String {
+            '"' => |lexer| lexer.switch(LexerRule::Init),
+
+            (_ # '"')+ => |lexer| {
+                let match_ = lexer.match_();
+                lexer.return_(match_)
+            },
+        }
+    }
+
+    let input = "x = f(ab\n cd)";
+    let mut lexer = Lexer::new_at(&input[6..12], loc(0, 6, 6), Some('('), LexerRule::Init);
+    assert_eq!(lexer.next(), Some(Ok((loc(0, 6, 6), "ab", loc(0, 8, 8)))));
+    assert_eq!(lexer.next(), Some(Ok((loc(1, 1, 10), "cd", loc(1, 3, 12)))));
+    assert_eq!(next(&mut lexer), None);
+
+    // Start in a string
+    let input = r#"s = "ab cd" x"#;
+    let mut lexer = Lexer::new_at(&input[5..], loc(0, 5, 5), Some('"'), LexerRule::String);
+    assert_eq!(
+        lexer.next(),
+        Some(Ok((loc(0, 5, 5), "ab cd", loc(0, 10, 10))))
+    );
+    assert_eq!(
+        lexer.next(),
+        Some(Ok((loc(0, 12, 12), "x", loc(0, 13, 13))))
+    );
+    assert_eq!(lexer.next(), None);
+}
+
+#[test]
+fn start_at_prev_char() {
+    lexer! {
+        Lexer -> &'static str;
+
+        ' ',
+
+        ^ '#' = "directive",
+
+        '#' = "hash",
+
+        ['a'-'z'] < '\'' = "suffix",
+
+        '\'' = "quote",
+    }
+
+    let input = "a'\n#x#";
+
+    // Left context: the input starts after 'a'
+    let mut lexer = Lexer::new_at(&input[1..2], loc(0, 1, 1), Some('a'), LexerRule::Init);
+    assert_eq!(next(&mut lexer), Some(Ok("suffix")));
+    assert_eq!(next(&mut lexer), None);
+
+    let mut lexer = Lexer::new_at(&input[1..2], loc(0, 1, 1), Some(' '), LexerRule::Init);
+    assert_eq!(next(&mut lexer), Some(Ok("quote")));
+    assert_eq!(next(&mut lexer), None);
+
+    // Anchor: the input starts at the beginning of the second line
+    let mut lexer = Lexer::new_at(&input[3..4], loc(1, 0, 3), Some('\n'), LexerRule::Init);
+    assert_eq!(next(&mut lexer), Some(Ok("directive")));
+    assert_eq!(next(&mut lexer), None);
+
+    // Anchor: the input starts in the middle of the second line
+    let mut lexer = Lexer::new_at(&input[5..], loc(1, 2, 5), Some('x'), LexerRule::Init);
+    assert_eq!(next(&mut lexer), Some(Ok("hash")));
+    assert_eq!(next(&mut lexer), None);
+}
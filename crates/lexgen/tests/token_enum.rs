@@ -0,0 +1,108 @@
+mod test_utils;
+
+use lexgen::lexer;
+use lexgen_util::{InvalidTokenDetails, LexerError, LexerErrorKind};
+use test_utils::{loc, next};
+
+#[test]
+fn token_enum() {
+    lexer! {
+        Lexer -> #[derive(Debug, PartialEq, Eq)] enum Token<'input> {
+            Id(&'input str),
+            Int { value: i64 },
+        };
+
+        let id = ['a'-'z']+;
+
+        [' ' '\n'],
+
+        "+" = Token::Plus,
+        '-' = Token::Minus,
+        "->" = Token::Arrow,
+
+        // Variant already returned by a rule with a literal: description uses the first literal
+        "!=" = Token::NotEq,
+        "<>" = Token::NotEq,
+
+        $id keywords {
+            "let" = Token::Let,
+        } => |lexer| {
+            let match_ = lexer.match_();
+            lexer.return_(Token::Id(match_))
+        },
+
+        ['0'-'9']+ => |lexer| {
+            let value = lexer.match_().parse().unwrap();
+            lexer.return_(Token::Int { value })
+        },
+
+        // Not a literal
+        ['*' '/'] = Token::MulOp,
+    }
+
+    let mut lexer = Lexer::new("let x + 12 -> - <> * y");
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Let)));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Id("x"))));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Plus)));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Int { value: 12 })));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Arrow)));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Minus)));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::NotEq)));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::MulOp)));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::Id("y"))));
+    assert_eq!(next(&mut lexer), None);
+
+    assert_eq!(Token::Id("x").kind(), TokenKind::Id);
+    assert_eq!(Token::Int { value: 1 }.kind(), TokenKind::Int);
+    assert_eq!(Token::Plus.kind(), TokenKind::Plus);
+
+    assert_eq!(TokenKind::Plus.describe(), "`+`");
+    assert_eq!(TokenKind::Minus.describe(), "`-`");
+    assert_eq!(TokenKind::Arrow.describe(), "`->`");
+    assert_eq!(TokenKind::NotEq.describe(), "`!=`");
+    assert_eq!(TokenKind::Let.describe(), "`let`");
+    assert_eq!(TokenKind::Id.describe(), "Id");
+    assert_eq!(TokenKind::MulOp.describe(), "MulOp");
+    assert_eq!(Token::Id("x").describe(), "Id");
+
+    assert_eq!(format!("expected {}", TokenKind::Plus), "expected `+`");
+}
+
+mod public_token_enum {
+    use lexgen::lexer;
+
+    lexer! {
+        pub Lexer -> #[derive(Debug, Clone, Copy, PartialEq, Eq)] pub enum Token {};
+
+        '(' = Token::LParen,
+        ')' = Token::RParen,
+    }
+}
+
+#[test]
+fn public_token_enum() {
+    use public_token_enum::{Lexer, Token, TokenKind};
+
+    let mut lexer = Lexer::new("()");
+    assert_eq!(next(&mut lexer), Some(Ok(Token::LParen)));
+    assert_eq!(next(&mut lexer), Some(Ok(Token::RParen)));
+    assert_eq!(next(&mut lexer), None);
+
+    let mut lexer = Lexer::new("(x");
+    assert_eq!(next(&mut lexer), Some(Ok(Token::LParen)));
+    assert_eq!(
+        next(&mut lexer),
+        Some(Err(LexerError {
+            location: loc(0, 1, 1),
+            kind: LexerErrorKind::InvalidToken,
+            details: Some(InvalidTokenDetails {
+                char: Some('x'),
+                expected: "'('-')'",
+                rule_set: "Init",
+            }),
+        }))
+    );
+
+    assert_eq!(Token::LParen.kind(), TokenKind::LParen);
+    assert_eq!(TokenKind::RParen.to_string(), "`)`");
+}
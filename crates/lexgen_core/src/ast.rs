@@ -0,0 +1,997 @@
+//! Proc macro AST definition and parser implementations
+
+use crate::semantic_action_table::{SemanticActionIdx, SemanticActionTable};
+use crate::token_enum::TokenEnum;
+
+use proc_macro2::Span;
+use quote::ToTokens;
+use syn::parse::{Parse, ParseStream};
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Var(pub String);
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Builtin(pub String);
+
+pub struct Lexer {
+    pub public: bool,
+    pub type_name: syn::Ident,
+    pub user_state_type: Option<syn::Type>,
+    pub token_type: syn::Type,
+
+    /// The token enum, when the token type is declared with `Lexer -> enum Token { ... };` syntax
+    pub token_enum: Option<syn::ItemEnum>,
+
+    pub rules: Vec<Rule>,
+}
+
+pub enum Rule {
+    /// `let <ident> = <regex>;`, or `let <ident>(<ident>, ...) = <regex>;` for a parameterised
+    /// binding
+    Binding {
+        var: Var,
+        params: Vec<Var>,
+        re: RegexCtx,
+
+        /// Span of the variable, for errors in the binding. `None` for bindings added in code.
+        span: Option<Span>,
+    },
+
+    /// `type Error = UserError;`
+    ErrorType {
+        /// Type on the RHS, e.g. `UserError<'input>`
+        ty: syn::Type,
+    },
+
+    /// A list of named rules at the top level: `rule <Ident> { <rules> },`
+    RuleSet {
+        name: syn::Ident,
+        rules: Vec<SingleRule>,
+
+        /// Span of the name, for errors in the rule set. `None` for rule sets added in code.
+        span: Option<Span>,
+    },
+
+    /// Set of rules without a name
+    UnnamedRules { rules: Vec<SingleRule> },
+}
+
+/// A regex bound to a variable, with the parameters of the binding. Parameters are used in the
+/// regex as variables, and are substituted with the arguments at use sites: `$<ident>!(<regex>,
+/// ...)`.
+#[derive(Debug, Clone)]
+pub struct Binding {
+    pub params: Vec<Var>,
+    pub re: Regex,
+}
+
+pub struct SingleRule {
+    pub options: RuleOptions,
+    pub lhs: RegexCtx,
+    pub rhs: SemanticActionIdx,
+
+    /// Span of the first token of the rule, for errors in the rule. `None` for rules added in
+    /// code.
+    pub span: Option<Span>,
+}
+
+/// Options of a rule, set with attributes before the rule: `#[priority = <int>]`, `#[shortest]`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RuleOptions {
+    /// When multiple rules match the same string, rules with higher priority are tried first.
+    /// Rules with the same priority are tried in the order they're defined. Default is 0.
+    pub priority: i32,
+
+    /// Stop matching the rule after its first (shortest) match, instead of the longest match
+    pub shortest: bool,
+}
+
+/// Regular expression with optional anchor, left context (lookbehind), and right context
+/// (lookahead)
+#[derive(Debug, Clone)]
+pub struct RegexCtx {
+    pub anchor: Option<Anchor>,
+    pub left_ctx: Option<LeftCtx>,
+    pub re: Regex,
+    pub right_ctx: Option<RightCtx>,
+}
+
+/// Left context of a rule
+#[derive(Debug, Clone)]
+pub enum LeftCtx {
+    /// `re < ...`: `re` is a character set, checked against the character before the location
+    /// where the current rule set started matching
+    Char(Regex),
+
+    /// `{ <expr> } < ...`: `<expr>` is a `fn(&mut Lexer) -> bool` checked against the lexer
+    /// handle, e.g. to check the previous token recorded in the user state
+    Pred(syn::Expr),
+}
+
+/// Right context of a rule: `> re`, or `>! re` for negated right context
+#[derive(Debug, Clone)]
+pub struct RightCtx {
+    pub re: Regex,
+
+    /// A negated right context holds when `re` does not match the rest of the input
+    pub negated: bool,
+}
+
+/// An anchor at the beginning of a rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Anchor {
+    /// `^`: matches at the beginning of a line, i.e. at the beginning of the input or after a
+    /// `'\n'`
+    LineStart,
+
+    /// `^^`: matches at the beginning of the input
+    InputStart,
+}
+
+#[derive(Debug, Clone)]
+pub enum RuleRhs {
+    None,
+    Rhs {
+        expr: syn::Expr,
+        kind: RuleKind,
+    },
+
+    /// A rule with a keyword table: `<regex> keywords { "<str>" = <token>, ... } <rhs>`. When the
+    /// matched string is one of the keywords the keyword's token is returned, otherwise `rhs` is
+    /// run.
+    Keywords {
+        keywords: Vec<(String, syn::Expr)>,
+        rhs: Box<RuleRhs>,
+    },
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum RuleKind {
+    /// Defined with `=`. RHS is not passed a `LexerHandle`, returns `Token`.
+    Simple,
+
+    /// Defined with `=?`. RHS is passed a `LexerHandle`, returns `LexerAction<Result<Token,
+    /// Error>>`.
+    Fallible,
+
+    /// Defined with `=>`. RHS is passed a `LexerHandle`, returns `LexerAction<Token>`
+    Infallible,
+}
+
+impl fmt::Debug for Lexer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Lexer")
+            .field("public", &self.public)
+            .field("type_name", &self.type_name.to_string())
+            .field("token_type", &"...")
+            .field("token_enum", &self.token_enum.as_ref().map(|_| "..."))
+            .field("rules", &self.rules)
+            .finish()
+    }
+}
+
+impl fmt::Debug for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rule::Binding {
+                var, params, re, ..
+            } => f
+                .debug_struct("Rule::Binding")
+                .field("var", var)
+                .field("params", params)
+                .field("re", re)
+                .finish(),
+            Rule::RuleSet { name, rules, .. } => f
+                .debug_struct("Rule::RuleSet")
+                .field("name", &name.to_string())
+                .field("rules", rules)
+                .finish(),
+            Rule::UnnamedRules { rules } => f
+                .debug_struct("Rule::UnnamedRules")
+                .field("rules", rules)
+                .finish(),
+            Rule::ErrorType { ty } => f.debug_struct("Rule::ErrorType").field("ty", ty).finish(),
+        }
+    }
+}
+
+impl fmt::Debug for SingleRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SingleRule")
+            .field("options", &self.options)
+            .field("lhs", &self.lhs)
+            .field("rhs", &"...")
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Regex {
+    Builtin(Builtin),
+    Var(Var),
+    Char(char),
+    String(String),
+    CharSet(CharSet),
+    ZeroOrMore(Box<Regex>),
+    OneOrMore(Box<Regex>),
+    ZeroOrOne(Box<Regex>),
+    Concat(Box<Regex>, Box<Regex>),
+    Or(Box<Regex>, Box<Regex>),
+    Any, // any character
+    EndOfInput,
+
+    /// Difference, or exclusion: characters in the first regex, excluding characters in the second
+    /// regex.
+    Diff(Box<Regex>, Box<Regex>),
+
+    /// Use of a parameterised binding: `$<ident>!(<regex>, ...)`
+    Call(Var, Vec<Regex>),
+}
+
+impl Regex {
+    /// Calls `f` on the variables used in the regex. Does not follow the variables.
+    pub fn for_each_var<F: FnMut(&Var)>(&self, f: &mut F) {
+        match self {
+            Regex::Var(var) => f(var),
+
+            Regex::Call(var, args) => {
+                f(var);
+                for arg in args {
+                    arg.for_each_var(f);
+                }
+            }
+
+            Regex::CharSet(CharSet(chars)) => {
+                for char in chars {
+                    if let CharOrRange::Var(var) = char {
+                        f(var);
+                    }
+                }
+            }
+
+            Regex::Builtin(_)
+            | Regex::Char(_)
+            | Regex::String(_)
+            | Regex::Any
+            | Regex::EndOfInput => {}
+
+            Regex::ZeroOrMore(re) | Regex::OneOrMore(re) | Regex::ZeroOrOne(re) => {
+                re.for_each_var(f)
+            }
+
+            Regex::Concat(re1, re2) | Regex::Or(re1, re2) | Regex::Diff(re1, re2) => {
+                re1.for_each_var(f);
+                re2.for_each_var(f);
+            }
+        }
+    }
+
+    /// Calls `f` on the built-in regexes used in the regex. Does not follow the variables.
+    pub fn for_each_builtin<F: FnMut(&Builtin)>(&self, f: &mut F) {
+        match self {
+            Regex::Builtin(builtin) => f(builtin),
+
+            Regex::Call(_, args) => {
+                for arg in args {
+                    arg.for_each_builtin(f);
+                }
+            }
+
+            Regex::CharSet(CharSet(chars)) => {
+                for char in chars {
+                    if let CharOrRange::Builtin(builtin) = char {
+                        f(builtin);
+                    }
+                }
+            }
+
+            Regex::Var(_) | Regex::Char(_) | Regex::String(_) | Regex::Any | Regex::EndOfInput => {}
+
+            Regex::ZeroOrMore(re) | Regex::OneOrMore(re) | Regex::ZeroOrOne(re) => {
+                re.for_each_builtin(f)
+            }
+
+            Regex::Concat(re1, re2) | Regex::Or(re1, re2) | Regex::Diff(re1, re2) => {
+                re1.for_each_builtin(f);
+                re2.for_each_builtin(f);
+            }
+        }
+    }
+}
+
+/// Shows the regex in the lexer definition syntax
+impl fmt::Display for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_prec(f, 0)
+    }
+}
+
+impl Regex {
+    /// Binding power of the regex's outermost operator, see `parse_regex`
+    fn prec(&self) -> u8 {
+        match self {
+            Regex::Or(_, _) => 0,
+            Regex::Concat(_, _) => 1,
+            Regex::ZeroOrMore(_) | Regex::OneOrMore(_) | Regex::ZeroOrOne(_) => 2,
+            Regex::Diff(_, _) => 3,
+            Regex::Builtin(_)
+            | Regex::Var(_)
+            | Regex::Char(_)
+            | Regex::String(_)
+            | Regex::CharSet(_)
+            | Regex::Any
+            | Regex::EndOfInput
+            | Regex::Call(_, _) => 4,
+        }
+    }
+
+    /// Show the regex, in parens if its binding power is lower than `prec`
+    fn fmt_prec(&self, f: &mut fmt::Formatter<'_>, prec: u8) -> fmt::Result {
+        if self.prec() < prec {
+            write!(f, "(")?;
+            self.fmt_prec(f, 0)?;
+            return write!(f, ")");
+        }
+
+        match self {
+            Regex::Builtin(Builtin(name)) => write!(f, "$${}", name),
+            Regex::Var(Var(name)) => write!(f, "${}", name),
+            Regex::Char(char) => write!(f, "{:?}", char),
+            Regex::String(str) => write!(f, "{:?}", str),
+            Regex::CharSet(CharSet(chars)) => {
+                write!(f, "[")?;
+                for (i, char_or_range) in chars.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, " ")?;
+                    }
+                    match char_or_range {
+                        CharOrRange::Char(char) => write!(f, "{:?}", char)?,
+                        CharOrRange::Range(start, end) => write!(f, "{:?}-{:?}", start, end)?,
+                        CharOrRange::Builtin(Builtin(name)) => write!(f, "$${}", name)?,
+                        CharOrRange::Var(Var(name)) => write!(f, "${}", name)?,
+                    }
+                }
+                write!(f, "]")
+            }
+            Regex::ZeroOrMore(re) => {
+                re.fmt_prec(f, 2)?;
+                write!(f, "*")
+            }
+            Regex::OneOrMore(re) => {
+                re.fmt_prec(f, 2)?;
+                write!(f, "+")
+            }
+            Regex::ZeroOrOne(re) => {
+                re.fmt_prec(f, 2)?;
+                write!(f, "?")
+            }
+            Regex::Concat(re1, re2) => {
+                re1.fmt_prec(f, 1)?;
+                write!(f, " ")?;
+                re2.fmt_prec(f, 2)
+            }
+            Regex::Or(re1, re2) => {
+                re1.fmt_prec(f, 0)?;
+                write!(f, " | ")?;
+                re2.fmt_prec(f, 1)
+            }
+            Regex::Diff(re1, re2) => {
+                re1.fmt_prec(f, 3)?;
+                write!(f, " # ")?;
+                re2.fmt_prec(f, 4)
+            }
+            Regex::Any => write!(f, "_"),
+            Regex::EndOfInput => write!(f, "$"),
+            Regex::Call(Var(name), args) => {
+                write!(f, "${}!(", name)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    arg.fmt_prec(f, 0)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+/// Shows the regex with its anchor and contexts in the lexer definition syntax
+impl fmt::Display for RegexCtx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.anchor {
+            Some(Anchor::LineStart) => write!(f, "^")?,
+            Some(Anchor::InputStart) => write!(f, "^^")?,
+            None => {}
+        }
+
+        match &self.left_ctx {
+            Some(LeftCtx::Char(left_ctx)) => write!(f, "{} < ", left_ctx)?,
+            Some(LeftCtx::Pred(pred)) => write!(f, "{{ {} }} < ", pred.to_token_stream())?,
+            None => {}
+        }
+
+        write!(f, "{}", self.re)?;
+
+        if let Some(RightCtx { re, negated }) = &self.right_ctx {
+            write!(f, " {} {}", if *negated { ">!" } else { ">" }, re)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CharSet(pub Vec<CharOrRange>);
+
+/// An element of a character set
+#[derive(Debug, Clone)]
+pub enum CharOrRange {
+    Char(char),
+    Range(char, char),
+
+    /// Characters of a built-in regex: `$$name`
+    Builtin(Builtin),
+
+    /// Characters of a variable bound to a character set: `$name`
+    Var(Var),
+}
+
+impl CharSet {
+    /// Whether the set only has characters and ranges, i.e. no built-in regexes or variables
+    pub fn is_simple(&self) -> bool {
+        self.0
+            .iter()
+            .all(|char| matches!(char, CharOrRange::Char(_) | CharOrRange::Range(_, _)))
+    }
+}
+
+/// Parses a regex with optional anchor, left context, and right context:
+/// `re_ctx -> [^ | ^^ | re < | { expr } <] re [> re | >! re]`
+pub fn parse_regex_ctx(input: ParseStream) -> syn::Result<RegexCtx> {
+    let anchor = parse_anchor(input)?;
+
+    let parse_lt = |input: ParseStream| -> syn::Result<()> {
+        let lt = input.parse::<syn::token::Lt>()?;
+        if anchor.is_some() {
+            return Err(syn::Error::new(
+                lt.span,
+                "A rule cannot have both an anchor and a left context",
+            ));
+        }
+        Ok(())
+    };
+
+    let (left_ctx, re) = if input.peek(syn::token::Brace) {
+        let braced;
+        syn::braced!(braced in input);
+        let pred = braced.parse::<syn::Expr>()?;
+        parse_lt(input)?;
+        (Some(LeftCtx::Pred(pred)), parse_regex(input)?)
+    } else {
+        let re = parse_regex(input)?;
+        if input.peek(syn::token::Lt) {
+            parse_lt(input)?;
+            (Some(LeftCtx::Char(re)), parse_regex(input)?)
+        } else {
+            (None, re)
+        }
+    };
+
+    let right_ctx = if input.peek(syn::token::Gt) {
+        input.parse::<syn::token::Gt>()?;
+        let negated = input.parse::<Option<syn::token::Bang>>()?.is_some();
+        Some(RightCtx {
+            re: parse_regex(input)?,
+            negated,
+        })
+    } else {
+        None
+    };
+
+    Ok(RegexCtx {
+        anchor,
+        left_ctx,
+        re,
+        right_ctx,
+    })
+}
+
+// anchor -> ^ | ^^
+fn parse_anchor(input: ParseStream) -> syn::Result<Option<Anchor>> {
+    if input.peek(syn::token::Caret) {
+        input.parse::<syn::token::Caret>()?;
+        if input.peek(syn::token::Caret) {
+            input.parse::<syn::token::Caret>()?;
+            Ok(Some(Anchor::InputStart))
+        } else {
+            Ok(Some(Anchor::LineStart))
+        }
+    } else {
+        Ok(None)
+    }
+}
+
+/// Parses a regex
+fn parse_regex(input: ParseStream) -> syn::Result<Regex> {
+    parse_regex_0(input)
+}
+
+// re_0 -> re_1 | re_0 `|` re_1 (alternation)
+fn parse_regex_0(input: ParseStream) -> syn::Result<Regex> {
+    let mut re = parse_regex_1(input)?;
+
+    while input.peek(syn::token::Or) {
+        let _ = input.parse::<syn::token::Or>()?;
+        let re2 = parse_regex_1(input)?;
+        re = Regex::Or(Box::new(re), Box::new(re2)); // left associative
+    }
+
+    Ok(re)
+}
+
+// re_1 -> re_2 | re_1 re_2 (concatenation)
+fn parse_regex_1(input: ParseStream) -> syn::Result<Regex> {
+    let mut re = parse_regex_2(input)?;
+
+    // Parse concatenations
+    while input.peek(syn::token::Paren)
+        || input.peek(syn::token::Dollar)
+        || input.peek(syn::LitChar)
+        || input.peek(syn::LitStr)
+        || input.peek(syn::token::Bracket)
+        || input.peek(syn::token::Underscore)
+        || (input.peek(syn::Ident) && input.peek2(syn::token::Bang))
+    {
+        let re2 = parse_regex_2(input)?;
+        re = Regex::Concat(Box::new(re), Box::new(re2)); // left associative
+    }
+
+    Ok(re)
+}
+
+// re_2 -> re_3 | re_3* | re_3? | re_3+
+fn parse_regex_2(input: ParseStream) -> syn::Result<Regex> {
+    let mut re = parse_regex_3(input)?;
+
+    loop {
+        if input.peek(syn::token::Star) {
+            let _ = input.parse::<syn::token::Star>()?;
+            re = Regex::ZeroOrMore(Box::new(re));
+        } else if input.peek(syn::token::Question) {
+            let _ = input.parse::<syn::token::Question>()?;
+            re = Regex::ZeroOrOne(Box::new(re));
+        } else if input.peek(syn::token::Add) {
+            let _ = input.parse::<syn::token::Add>()?;
+            re = Regex::OneOrMore(Box::new(re));
+        } else {
+            break;
+        }
+    }
+
+    Ok(re)
+}
+
+// re_3 -> re_4 | re_4 # re_4 (left associative)
+fn parse_regex_3(input: ParseStream) -> syn::Result<Regex> {
+    let mut re = parse_regex_4(input)?;
+
+    while input.peek(syn::token::Pound) {
+        let _ = input.parse::<syn::token::Pound>()?;
+        let re_2 = parse_regex_4(input)?;
+        re = Regex::Diff(Box::new(re), Box::new(re_2));
+    }
+
+    Ok(re)
+}
+
+// re_4 -> ( re_0 ) | $ | $x | $x!(re_0, ...) | $$x | _ | 'x' | "..." | regex!("...") | [...]
+fn parse_regex_4(input: ParseStream) -> syn::Result<Regex> {
+    if input.peek(syn::token::Paren) {
+        let parenthesized;
+        syn::parenthesized!(parenthesized in input);
+        parse_regex(&parenthesized) // no right ctx
+    } else if input.peek(syn::token::Dollar) {
+        let _ = input.parse::<syn::token::Dollar>()?;
+        if input.parse::<syn::token::Dollar>().is_ok() {
+            let ident = input.parse::<syn::Ident>()?;
+            Ok(Regex::Builtin(Builtin(ident.to_string())))
+        } else {
+            match input.parse::<syn::Ident>() {
+                Ok(ident) if input.peek(syn::token::Bang) => {
+                    let _ = input.parse::<syn::token::Bang>()?;
+                    let parenthesized;
+                    syn::parenthesized!(parenthesized in input);
+                    let args =
+                        parenthesized.parse_terminated::<_, syn::token::Comma>(parse_regex)?;
+                    if args.is_empty() {
+                        return Err(syn::Error::new(
+                            ident.span(),
+                            "Expected at least one argument",
+                        ));
+                    }
+                    Ok(Regex::Call(
+                        Var(ident.to_string()),
+                        args.into_iter().collect(),
+                    ))
+                }
+                Ok(ident) => Ok(Regex::Var(Var(ident.to_string()))),
+                Err(_) => Ok(Regex::EndOfInput),
+            }
+        }
+    } else if input.peek(syn::LitChar) {
+        let char = input.parse::<syn::LitChar>()?;
+        Ok(Regex::Char(char.value()))
+    } else if input.peek(syn::LitStr) {
+        let str = input.parse::<syn::LitStr>()?;
+        Ok(Regex::String(str.value()))
+    } else if input.peek(syn::Ident) && input.peek2(syn::token::Bang) {
+        // `regex!("...")`: a regex in the conventional syntax
+        let ident = input.parse::<syn::Ident>()?;
+        if ident != "regex" {
+            return Err(syn::Error::new(
+                ident.span(),
+                format!("Unknown macro `{}!`, expected `regex!`", ident),
+            ));
+        }
+        let _ = input.parse::<syn::token::Bang>()?;
+        let parenthesized;
+        syn::parenthesized!(parenthesized in input);
+        let str = parenthesized.parse::<syn::LitStr>()?;
+        if !parenthesized.is_empty() {
+            return Err(parenthesized.error("Expected one string literal in `regex!(...)`"));
+        }
+        crate::raw_regex::parse(&str.value()).map_err(|error| syn::Error::new(str.span(), error))
+    } else if input.peek(syn::token::Bracket) {
+        let bracketed;
+        syn::bracketed!(bracketed in input);
+        let char_set = parse_charset(&bracketed)?;
+        Ok(Regex::CharSet(char_set))
+    } else if input.parse::<syn::token::Underscore>().is_ok() {
+        Ok(Regex::Any)
+    } else if input.peek(syn::token::Caret) {
+        Err(input.error("Anchors (`^` and `^^`) can only be used at the beginning of a rule"))
+    } else {
+        Err(syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "Unable to parse regex",
+        ))
+    }
+}
+
+fn parse_charset(input: ParseStream) -> syn::Result<CharSet> {
+    let mut chars = vec![];
+    while !input.is_empty() {
+        chars.push(parse_char_or_range(input)?);
+    }
+    Ok(CharSet(chars))
+}
+
+// char_or_range -> char | char - char | $x | $$x
+fn parse_char_or_range(input: ParseStream) -> syn::Result<CharOrRange> {
+    if input.peek(syn::token::Dollar) {
+        let _ = input.parse::<syn::token::Dollar>()?;
+        return if input.parse::<syn::token::Dollar>().is_ok() {
+            let ident = input.parse::<syn::Ident>()?;
+            Ok(CharOrRange::Builtin(Builtin(ident.to_string())))
+        } else {
+            let ident = input.parse::<syn::Ident>()?;
+            Ok(CharOrRange::Var(Var(ident.to_string())))
+        };
+    }
+
+    let start_span = input.span();
+    let char = parse_set_char(input)?;
+    if input.peek(syn::token::Sub) {
+        let _ = input.parse::<syn::token::Sub>()?;
+        let char2 = parse_set_char(input)?;
+        if char2 < char {
+            return Err(syn::Error::new(
+                start_span,
+                format!(
+                    "Invalid range {:?}-{:?}: end of the range is smaller than the start",
+                    char, char2
+                ),
+            ));
+        }
+        Ok(CharOrRange::Range(char, char2))
+    } else {
+        Ok(CharOrRange::Char(char))
+    }
+}
+
+// char -> 'x' | <code point>, e.g. 0x300
+fn parse_set_char(input: ParseStream) -> syn::Result<char> {
+    if input.peek(syn::LitInt) {
+        let lit = input.parse::<syn::LitInt>()?;
+        if !lit.suffix().is_empty() {
+            return Err(syn::Error::new(
+                lit.span(),
+                "Code points in character sets cannot have a suffix",
+            ));
+        }
+        lit.base10_parse::<u32>()
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| syn::Error::new(lit.span(), "Invalid code point"))
+    } else if input.peek(syn::LitChar) {
+        Ok(input.parse::<syn::LitChar>()?.value())
+    } else {
+        Err(input.error(
+            "Expected a character, a code point (e.g. `0x300`), `$var`, or `$$builtin` in \
+             character set",
+        ))
+    }
+}
+
+// rule_options -> (#[priority = <int>] | #[shortest])*
+fn parse_rule_options(input: ParseStream) -> syn::Result<RuleOptions> {
+    let mut options = RuleOptions::default();
+
+    while input.peek(syn::token::Pound) {
+        input.parse::<syn::token::Pound>()?;
+        let attr;
+        syn::bracketed!(attr in input);
+
+        let ident = attr.parse::<syn::Ident>()?;
+        if ident == "priority" {
+            attr.parse::<syn::token::Eq>()?;
+            let negative = attr.parse::<Option<syn::token::Sub>>()?.is_some();
+            let lit = attr.parse::<syn::LitInt>()?;
+            let priority = lit.base10_parse::<i64>()?;
+            let priority = if negative { -priority } else { priority };
+            options.priority = i32::try_from(priority)
+                .map_err(|_| syn::Error::new(lit.span(), "Rule priority should fit in an `i32`"))?;
+        } else if ident == "shortest" {
+            options.shortest = true;
+        } else {
+            return Err(syn::Error::new(
+                ident.span(),
+                "Unknown rule attribute, expected \"priority\" or \"shortest\"",
+            ));
+        }
+
+        if !attr.is_empty() {
+            return Err(attr.error("Unexpected tokens in rule attribute"));
+        }
+    }
+
+    Ok(options)
+}
+
+// keywords -> [keywords { ("<str>" = <expr>),* }]
+fn parse_keywords(input: ParseStream) -> syn::Result<Option<Vec<(String, syn::Expr)>>> {
+    if !input.peek(syn::Ident) {
+        return Ok(None);
+    }
+
+    let ident = input.parse::<syn::Ident>()?;
+    if ident != "keywords" {
+        return Err(syn::Error::new(
+            ident.span(),
+            "Expected one of `keywords`, `,`, `=>`, `=?`, or `=` after a regex",
+        ));
+    }
+
+    let braced;
+    syn::braced!(braced in input);
+
+    let mut keywords: Vec<(String, syn::Expr)> = vec![];
+
+    while !braced.is_empty() {
+        let keyword = braced.parse::<syn::LitStr>()?;
+        let keyword_str = keyword.value();
+
+        if keyword_str.is_empty() {
+            return Err(syn::Error::new(keyword.span(), "Keywords cannot be empty"));
+        }
+
+        if keywords.iter().any(|(other, _)| *other == keyword_str) {
+            return Err(syn::Error::new(
+                keyword.span(),
+                format!("Keyword {:?} is defined multiple times", keyword_str),
+            ));
+        }
+
+        braced.parse::<syn::token::Eq>()?;
+        let expr = braced.parse::<syn::Expr>()?;
+
+        keywords.push((keyword_str, expr));
+
+        if !braced.is_empty() {
+            braced.parse::<syn::token::Comma>()?;
+        }
+    }
+
+    Ok(Some(keywords))
+}
+
+fn parse_single_rule(
+    input: ParseStream,
+    semantic_action_table: &mut SemanticActionTable,
+) -> syn::Result<SingleRule> {
+    let span = input.span();
+    let options = parse_rule_options(input)?;
+    let lhs = parse_regex_ctx(input)?;
+    let rhs = parse_rule_rhs(input)?;
+    let rhs = semantic_action_table.add(rhs);
+
+    Ok(SingleRule {
+        options,
+        lhs,
+        rhs,
+        span: Some(span),
+    })
+}
+
+/// Parses the part of a rule after the regex:
+/// `[keywords { ... }] (, | => <expr>, | = <expr>, | =? <expr>,)`
+pub fn parse_rule_rhs(input: ParseStream) -> syn::Result<RuleRhs> {
+    let keywords = parse_keywords(input)?;
+
+    let rhs = if input.parse::<syn::token::Comma>().is_ok() {
+        RuleRhs::None
+    } else if input.parse::<syn::token::FatArrow>().is_ok() {
+        let expr = input.parse::<syn::Expr>()?;
+        input.parse::<syn::token::Comma>()?;
+        RuleRhs::Rhs {
+            expr,
+            kind: RuleKind::Infallible,
+        }
+    } else if input.parse::<syn::token::Eq>().is_ok() {
+        let kind = if input.peek(syn::token::Question) {
+            let _ = input.parse::<syn::token::Question>();
+            RuleKind::Fallible
+        } else {
+            RuleKind::Simple
+        };
+        let expr = input.parse::<syn::Expr>()?;
+        input.parse::<syn::token::Comma>()?;
+        RuleRhs::Rhs { expr, kind }
+    } else {
+        return Err(input.error("Expected one of `,`, `=>`, `=?`, or `=` after a regex"));
+    };
+
+    Ok(match keywords {
+        Some(keywords) => RuleRhs::Keywords {
+            keywords,
+            rhs: Box::new(rhs),
+        },
+        None => rhs,
+    })
+}
+
+fn parse_rule(
+    input: ParseStream,
+    semantic_action_table: &mut SemanticActionTable,
+) -> syn::Result<Rule> {
+    if input.peek(syn::token::Let) {
+        // Let binding
+        input.parse::<syn::token::Let>()?;
+        let var = input.parse::<syn::Ident>()?;
+        let mut params: Vec<Var> = vec![];
+        if input.peek(syn::token::Paren) {
+            let parenthesized;
+            syn::parenthesized!(parenthesized in input);
+            let idents =
+                parenthesized.parse_terminated::<_, syn::token::Comma>(syn::Ident::parse)?;
+            if idents.is_empty() {
+                return Err(syn::Error::new(
+                    var.span(),
+                    "Parameterised bindings should have at least one parameter",
+                ));
+            }
+            for ident in idents {
+                let param = Var(ident.to_string());
+                if params.contains(&param) {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        format!("Parameter {:?} is defined multiple times", param.0),
+                    ));
+                }
+                params.push(param);
+            }
+        }
+        input.parse::<syn::token::Eq>()?;
+        let re = parse_regex_ctx(input)?;
+        input.parse::<syn::token::Semi>()?;
+        Ok(Rule::Binding {
+            var: Var(var.to_string()),
+            params,
+            re,
+            span: Some(var.span()),
+        })
+    } else if input.peek(syn::Ident) && !input.peek2(syn::token::Bang) {
+        // Name rules
+        let ident = input.parse::<syn::Ident>()?;
+        if ident != "rule" {
+            return Err(syn::Error::new(
+                ident.span(),
+                "Unknown identifier, expected \"rule\", \"let\", or a regex",
+            ));
+        }
+        let rule_name = input.parse::<syn::Ident>()?;
+        let braced;
+        syn::braced!(braced in input);
+        let mut single_rules = vec![];
+        while !braced.is_empty() {
+            single_rules.push(parse_single_rule(&braced, semantic_action_table)?);
+        }
+        // Consume optional trailing comma
+        let _ = input.parse::<syn::token::Comma>();
+        let span = rule_name.span();
+        Ok(Rule::RuleSet {
+            name: rule_name,
+            rules: single_rules,
+            span: Some(span),
+        })
+    } else if input.parse::<syn::token::Type>().is_ok() {
+        let ident = input.parse::<syn::Ident>()?;
+        if ident != "Error" {
+            return Err(syn::Error::new(
+                ident.span(),
+                "Error type syntax is: `type Error = ...;`",
+            ));
+        }
+        input.parse::<syn::token::Eq>()?;
+        let ty = input.parse::<syn::Type>()?;
+        input.parse::<syn::token::Semi>()?;
+        Ok(Rule::ErrorType { ty })
+    } else {
+        let mut single_rules = vec![];
+        while !input.is_empty() {
+            single_rules.push(parse_single_rule(input, semantic_action_table)?);
+        }
+        Ok(Rule::UnnamedRules {
+            rules: single_rules,
+        })
+    }
+}
+
+pub fn make_lexer_parser(
+    semantic_action_table: &mut SemanticActionTable,
+) -> impl FnOnce(ParseStream) -> Result<Lexer, syn::Error> + '_ {
+    |input: ParseStream| {
+        let public = input.parse::<syn::token::Pub>().is_ok();
+        let type_name = input.parse::<syn::Ident>()?;
+
+        let user_state_type = if input.peek(syn::token::Paren) {
+            let parenthesized;
+            syn::parenthesized!(parenthesized in input);
+            Some(parenthesized.parse::<syn::Type>()?)
+        } else {
+            None
+        };
+
+        input.parse::<syn::token::RArrow>()?;
+
+        // Token type, or token enum to generate
+        let (token_type, token_enum) = if input.peek(syn::token::Pound)
+            || input.peek(syn::token::Pub)
+            || input.peek(syn::token::Enum)
+        {
+            let token_enum = input.parse::<syn::ItemEnum>()?;
+            (TokenEnum::token_type(&token_enum), Some(token_enum))
+        } else {
+            (input.parse::<syn::Type>()?, None)
+        };
+
+        input.parse::<syn::token::Semi>()?;
+
+        let mut rules = vec![];
+        while !input.is_empty() {
+            rules.push(parse_rule(input, semantic_action_table)?);
+        }
+
+        Ok(Lexer {
+            public,
+            type_name,
+            user_state_type,
+            token_type,
+            token_enum,
+            rules,
+        })
+    }
+}
@@ -0,0 +1,175 @@
+//! Generating lexers in build scripts
+//!
+//! Instead of expanding `lexer!` on every build, a build script can generate the lexer once and
+//! write the code to `OUT_DIR`, where it can be inspected and included with
+//! `include!(concat!(env!("OUT_DIR"), "/lexer.rs"));`. Cargo reruns the build script only when
+//! the lexer definition changes.
+
+use crate::lexer_file::{generate_from_str, DefinitionError};
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// An error in build script code generation
+#[derive(Debug)]
+pub enum Error {
+    /// Reading the lexer definition or writing the generated code failed
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+
+    /// Syntax error in the lexer definition, with 1-based line and column numbers
+    Syntax {
+        path: Option<PathBuf>,
+        line: usize,
+        col: usize,
+        message: String,
+    },
+
+    /// The lexer definition is invalid, e.g. it uses an undefined variable. `location` is the
+    /// 1-based line and column numbers of the rule or binding with the error, when known.
+    Invalid {
+        path: Option<PathBuf>,
+        location: Option<(usize, usize)>,
+        message: String,
+    },
+}
+
+impl Error {
+    fn with_path(self, path: &Path) -> Error {
+        match self {
+            Error::Io { .. } => self,
+            Error::Syntax {
+                path: _,
+                line,
+                col,
+                message,
+            } => Error::Syntax {
+                path: Some(path.to_owned()),
+                line,
+                col,
+                message,
+            },
+            Error::Invalid {
+                path: _,
+                location,
+                message,
+            } => Error::Invalid {
+                path: Some(path.to_owned()),
+                location,
+                message,
+            },
+        }
+    }
+}
+
+impl From<DefinitionError> for Error {
+    fn from(error: DefinitionError) -> Error {
+        match error {
+            DefinitionError::Syntax { line, col, message } => Error::Syntax {
+                path: None,
+                line,
+                col,
+                message,
+            },
+            DefinitionError::Invalid { location, message } => Error::Invalid {
+                path: None,
+                location,
+                message,
+            },
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            Error::Syntax {
+                path,
+                line,
+                col,
+                message,
+            } => match path {
+                Some(path) => write!(f, "{}:{}:{}: {}", path.display(), line, col, message),
+                None => write!(f, "{}:{}: {}", line, col, message),
+            },
+            Error::Invalid {
+                path,
+                location,
+                message,
+            } => match (path, location) {
+                (Some(path), Some((line, col))) => {
+                    write!(f, "{}:{}:{}: {}", path.display(), line, col, message)
+                }
+                (Some(path), None) => write!(f, "{}: {}", path.display(), message),
+                (None, Some((line, col))) => write!(f, "{}:{}: {}", line, col, message),
+                (None, None) => f.write_str(message),
+            },
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { path: _, error } => Some(error),
+            Error::Syntax { .. } | Error::Invalid { .. } => None,
+        }
+    }
+}
+
+/// Generate Rust code for a lexer definition. `definition` has the same syntax as the body of
+/// `lexer! { ... }`.
+///
+/// The code is not formatted. Run `rustfmt` on it to inspect it.
+pub fn generate(definition: &str) -> Result<String, Error> {
+    Ok(generate_from_str(definition)?.to_string())
+}
+
+/// Generate a lexer from the definition in file `input` and write the code to file `output` in
+/// `OUT_DIR`. `input` is relative to the crate's `Cargo.toml`. To be called in build scripts.
+///
+/// Also prints `cargo:rerun-if-changed=<input>` to rerun the build script when the definition
+/// changes.
+///
+/// ```ignore
+/// // build.rs
+/// fn main() {
+///     lexgen_core::process_file("src/lexer.lex", "lexer.rs").unwrap();
+/// }
+///
+/// // src/lib.rs
+/// include!(concat!(env!("OUT_DIR"), "/lexer.rs"));
+/// ```
+pub fn process_file<P1: AsRef<Path>, P2: AsRef<Path>>(input: P1, output: P2) -> Result<(), Error> {
+    let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR")
+        .expect("CARGO_MANIFEST_DIR is not set, process_file should be called in build scripts");
+
+    let out_dir = std::env::var_os("OUT_DIR")
+        .expect("OUT_DIR is not set, process_file should be called in build scripts");
+
+    let input_path = Path::new(&manifest_dir).join(input);
+    let output_path = Path::new(&out_dir).join(output);
+
+    println!("cargo:rerun-if-changed={}", input_path.display());
+
+    let definition = std::fs::read_to_string(&input_path).map_err(|error| Error::Io {
+        path: input_path.clone(),
+        error,
+    })?;
+
+    let code = generate(&definition).map_err(|error| error.with_path(&input_path))?;
+
+    let code = format!(
+        "// Generated by lexgen from {}. Do not edit.\n{}\n",
+        input_path.display(),
+        code
+    );
+
+    std::fs::write(&output_path, code).map_err(|error| Error::Io {
+        path: output_path,
+        error,
+    })
+}
@@ -0,0 +1,582 @@
+//! Building lexers in code
+//!
+//! Regexes and rules can be built with the types in this module instead of writing them in
+//! `lexer!` syntax, e.g. to generate keyword rules from a list. Lexers built this way are compiled
+//! the same way as `lexer!` definitions: [`LexerBuilder`] generates code, e.g. in build scripts,
+//! [`RuntimeLexerBuilder`](crate::runtime::RuntimeLexerBuilder) builds lexers that run without
+//! generating code.
+//!
+//! ```
+//! use lexgen_core::builder::{LexerBuilder, Regex};
+//!
+//! let keywords = ["if", "else", "while"];
+//!
+//! let mut builder = LexerBuilder::new("Lexer", "Token")
+//!     .binding("ident_start", Regex::range('a', 'z').or(Regex::char('_')))
+//!     .skip(Regex::one_of([' ', '\n']).one_or_more());
+//!
+//! for keyword in keywords {
+//!     let token = format!("= Token::Keyword({:?})", keyword);
+//!     builder = builder.rule(Regex::string(keyword), &token);
+//! }
+//!
+//! let code = builder
+//!     .rule("$ident_start+", "=> |lexer| lexer.return_(Token::Ident(lexer.match_()))")
+//!     .generate()
+//!     .unwrap();
+//! ```
+
+use crate::ast::{
+    self, Anchor, Builtin, CharOrRange, CharSet, RightCtx, RuleOptions, RuleRhs, Var,
+};
+use crate::build::Error;
+use crate::inspect::Inspection;
+use crate::lexer_file::{self, DefinitionError};
+use crate::regex_to_nfa::get_builtin_regex;
+use crate::semantic_action_table::SemanticActionTable;
+
+use std::fmt;
+
+/// A regular expression
+#[derive(Debug, Clone)]
+pub struct Regex(pub(crate) ast::Regex);
+
+impl Regex {
+    /// A character: `'c'`
+    pub fn char(char: char) -> Regex {
+        Regex(ast::Regex::Char(char))
+    }
+
+    /// A string: `"str"`
+    pub fn string(str: &str) -> Regex {
+        Regex(ast::Regex::String(str.to_owned()))
+    }
+
+    /// A range of characters: `['a'-'z']`
+    pub fn range(start: char, end: char) -> Regex {
+        Regex(ast::Regex::CharSet(CharSet(vec![CharOrRange::Range(
+            start, end,
+        )])))
+    }
+
+    /// One of the given characters: `['a' 'b' 'c']`
+    pub fn one_of<I: IntoIterator<Item = char>>(chars: I) -> Regex {
+        Regex(ast::Regex::CharSet(CharSet(
+            chars.into_iter().map(CharOrRange::Char).collect(),
+        )))
+    }
+
+    /// Any character: `_`
+    pub fn any() -> Regex {
+        Regex(ast::Regex::Any)
+    }
+
+    /// End of input: `$`
+    pub fn end_of_input() -> Regex {
+        Regex(ast::Regex::EndOfInput)
+    }
+
+    /// A variable defined with a binding: `$name`. The variable should be bound before the regex
+    /// is used in a binding or rule.
+    pub fn var(name: &str) -> Regex {
+        Regex(ast::Regex::Var(Var(name.to_owned())))
+    }
+
+    /// A built-in regex: `$$name`. See the README for the list of built-in regexes. Unknown names
+    /// are reported when the regex is used in a binding or rule.
+    pub fn builtin(name: &str) -> Regex {
+        Regex(ast::Regex::Builtin(Builtin(name.to_owned())))
+    }
+
+    /// Concatenation of the regexes. Matches the empty string when `regexes` is empty.
+    pub fn seq<I: IntoIterator<Item = Regex>>(regexes: I) -> Regex {
+        regexes
+            .into_iter()
+            .reduce(Regex::then)
+            .unwrap_or_else(|| Regex::string(""))
+    }
+
+    /// Alternation of the regexes: `re1 | re2 | ...`
+    ///
+    /// Panics when `regexes` is empty.
+    pub fn alt<I: IntoIterator<Item = Regex>>(regexes: I) -> Regex {
+        regexes
+            .into_iter()
+            .reduce(Regex::or)
+            .expect("Regex::alt called with no regexes")
+    }
+
+    /// Concatenation: `self other`
+    pub fn then(self, other: Regex) -> Regex {
+        Regex(ast::Regex::Concat(Box::new(self.0), Box::new(other.0)))
+    }
+
+    /// Alternation: `self | other`
+    pub fn or(self, other: Regex) -> Regex {
+        Regex(ast::Regex::Or(Box::new(self.0), Box::new(other.0)))
+    }
+
+    /// `self*`
+    pub fn zero_or_more(self) -> Regex {
+        Regex(ast::Regex::ZeroOrMore(Box::new(self.0)))
+    }
+
+    /// `self+`
+    pub fn one_or_more(self) -> Regex {
+        Regex(ast::Regex::OneOrMore(Box::new(self.0)))
+    }
+
+    /// `self?`
+    pub fn optional(self) -> Regex {
+        Regex(ast::Regex::ZeroOrOne(Box::new(self.0)))
+    }
+
+    /// Characters of `self` excluding the characters of `other`: `self # other`. Both regexes
+    /// should be character sets.
+    pub fn except(self, other: Regex) -> Regex {
+        Regex(ast::Regex::Diff(Box::new(self.0), Box::new(other.0)))
+    }
+}
+
+/// Shows the regex in `lexer!` syntax
+impl fmt::Display for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// A regex with an optional anchor, left context, and right context
+#[derive(Debug, Clone)]
+pub struct RegexCtx(pub(crate) ast::RegexCtx);
+
+impl RegexCtx {
+    pub fn new(re: Regex) -> RegexCtx {
+        RegexCtx(ast::RegexCtx {
+            anchor: None,
+            left_ctx: None,
+            re: re.0,
+            right_ctx: None,
+        })
+    }
+
+    /// Match only at the beginning of a line: `^ re`
+    pub fn at_line_start(mut self) -> RegexCtx {
+        self.0.anchor = Some(Anchor::LineStart);
+        self
+    }
+
+    /// Match only at the beginning of the input: `^^ re`
+    pub fn at_input_start(mut self) -> RegexCtx {
+        self.0.anchor = Some(Anchor::InputStart);
+        self
+    }
+
+    /// Match only after a character in the character set `left_ctx`: `left_ctx < re`
+    pub fn preceded_by(mut self, left_ctx: Regex) -> RegexCtx {
+        self.0.left_ctx = Some(ast::LeftCtx::Char(left_ctx.0));
+        self
+    }
+
+    /// Match only when followed by `right_ctx`: `re > right_ctx`
+    pub fn followed_by(mut self, right_ctx: Regex) -> RegexCtx {
+        self.0.right_ctx = Some(RightCtx {
+            re: right_ctx.0,
+            negated: false,
+        });
+        self
+    }
+
+    /// Match only when not followed by `right_ctx`: `re >! right_ctx`
+    pub fn not_followed_by(mut self, right_ctx: Regex) -> RegexCtx {
+        self.0.right_ctx = Some(RightCtx {
+            re: right_ctx.0,
+            negated: true,
+        });
+        self
+    }
+}
+
+impl From<Regex> for RegexCtx {
+    fn from(re: Regex) -> RegexCtx {
+        RegexCtx::new(re)
+    }
+}
+
+/// Shows the regex with its anchor and contexts in `lexer!` syntax
+impl fmt::Display for RegexCtx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Left-hand side of a rule: a regex with contexts, and rule options
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub(crate) lhs: ast::RegexCtx,
+    pub(crate) options: RuleOptions,
+}
+
+impl Rule {
+    pub fn new<R: Into<RegexCtx>>(re: R) -> Rule {
+        Rule {
+            lhs: re.into().0,
+            options: RuleOptions::default(),
+        }
+    }
+
+    /// Set the rule's priority: `#[priority = <priority>]`
+    pub fn priority(mut self, priority: i32) -> Rule {
+        self.options.priority = priority;
+        self
+    }
+
+    /// Stop at the rule's shortest match: `#[shortest]`
+    pub fn shortest(mut self) -> Rule {
+        self.options.shortest = true;
+        self
+    }
+}
+
+impl From<Regex> for Rule {
+    fn from(re: Regex) -> Rule {
+        Rule::new(re)
+    }
+}
+
+impl From<RegexCtx> for Rule {
+    fn from(re: RegexCtx) -> Rule {
+        Rule::new(re)
+    }
+}
+
+/// Regexes that can be bound to variables: [`Regex`]s, [`RegexCtx`]s, and strings in `lexer!`
+/// syntax
+pub trait IntoRegexCtx {
+    fn into_regex_ctx(self) -> Result<RegexCtx, Error>;
+}
+
+impl IntoRegexCtx for Regex {
+    fn into_regex_ctx(self) -> Result<RegexCtx, Error> {
+        Ok(self.into())
+    }
+}
+
+impl IntoRegexCtx for RegexCtx {
+    fn into_regex_ctx(self) -> Result<RegexCtx, Error> {
+        Ok(self)
+    }
+}
+
+impl IntoRegexCtx for &str {
+    fn into_regex_ctx(self) -> Result<RegexCtx, Error> {
+        lexer_file::parse_regex_ctx(self)
+            .map(RegexCtx)
+            .map_err(|error| match error {
+                DefinitionError::Syntax { line, col, message } => Error::Syntax {
+                    path: None,
+                    line,
+                    col,
+                    message: format!("{} (in regex {:?})", message, self),
+                },
+                DefinitionError::Invalid { .. } => error.into(),
+            })
+    }
+}
+
+impl IntoRegexCtx for String {
+    fn into_regex_ctx(self) -> Result<RegexCtx, Error> {
+        self.as_str().into_regex_ctx()
+    }
+}
+
+/// Left-hand sides of rules: [`Rule`]s, [`Regex`]s, [`RegexCtx`]s, and strings in `lexer!`
+/// syntax
+pub trait IntoRule {
+    fn into_rule(self) -> Result<Rule, Error>;
+}
+
+impl IntoRule for Rule {
+    fn into_rule(self) -> Result<Rule, Error> {
+        Ok(self)
+    }
+}
+
+impl<R: IntoRegexCtx> IntoRule for R {
+    fn into_rule(self) -> Result<Rule, Error> {
+        self.into_regex_ctx().map(Rule::new)
+    }
+}
+
+/// Builds a lexer definition, to generate code for it with [`generate`](LexerBuilder::generate)
+/// or to inspect it with [`inspect`](LexerBuilder::inspect).
+///
+/// Right-hand sides of rules are given in `lexer!` syntax, as the part of the rule after the
+/// regex, without the trailing comma. For example, `"= Token::Plus"` or `"=> |lexer|
+/// lexer.switch(LexerRule::Comment)"`.
+#[derive(Debug)]
+pub struct LexerBuilder {
+    public: bool,
+    type_name: String,
+    user_state_type: Option<String>,
+    token_type: String,
+    error_type: Option<String>,
+    definition: Definition,
+}
+
+impl LexerBuilder {
+    /// Start building a lexer named `type_name` that returns `token_type`, like `type_name ->
+    /// token_type;` in `lexer!` syntax
+    pub fn new(type_name: &str, token_type: &str) -> LexerBuilder {
+        LexerBuilder {
+            public: false,
+            type_name: type_name.to_owned(),
+            user_state_type: None,
+            token_type: token_type.to_owned(),
+            error_type: None,
+            definition: Definition::default(),
+        }
+    }
+
+    /// Make the lexer public: `pub type_name -> token_type;`
+    pub fn public(mut self) -> LexerBuilder {
+        self.public = true;
+        self
+    }
+
+    /// Set the user state type: `type_name(user_state_type) -> token_type;`
+    pub fn user_state(mut self, user_state_type: &str) -> LexerBuilder {
+        self.user_state_type = Some(user_state_type.to_owned());
+        self
+    }
+
+    /// Set the user error type: `type Error = error_type;`
+    pub fn error_type(mut self, error_type: &str) -> LexerBuilder {
+        self.error_type = Some(error_type.to_owned());
+        self
+    }
+
+    /// Bind a regex to a variable, to be used in rules and later bindings as `$name`: `let name =
+    /// re;`
+    pub fn binding<R: IntoRegexCtx>(mut self, name: &str, re: R) -> LexerBuilder {
+        self.definition.binding(name, re.into_regex_ctx());
+        self
+    }
+
+    /// Start a new rule set: `rule name { ... }`. Rules added before the first `rule_set` call
+    /// are added to the `Init` rule set.
+    pub fn rule_set(mut self, name: &str) -> LexerBuilder {
+        self.definition.rule_set(name);
+        self
+    }
+
+    /// Add a rule with a right-hand side, e.g. `"= Token::Plus"`
+    pub fn rule<R: IntoRule>(mut self, rule: R, rhs: &str) -> LexerBuilder {
+        let rhs = lexer_file::parse_rule_rhs(&format!("{},", rhs)).map_err(Error::from);
+        self.definition.rule(rule.into_rule(), rhs);
+        self
+    }
+
+    /// Add a rule that skips the matched input: `re,`
+    pub fn skip<R: IntoRule>(mut self, rule: R) -> LexerBuilder {
+        self.definition.rule(rule.into_rule(), Ok(RuleRhs::None));
+        self
+    }
+
+    /// Generate code for the lexer, the same as `lexgen_core::generate` for a definition in
+    /// `lexer!` syntax
+    pub fn generate(self) -> Result<String, Error> {
+        let (lexer, semantic_action_table) = self.into_lexer()?;
+        let code = crate::generate_lexer(lexer, semantic_action_table)?;
+        Ok(code.to_string())
+    }
+
+    /// Compile the lexer for inspecting
+    pub fn inspect(self) -> Result<Inspection, Error> {
+        let (lexer, semantic_action_table) = self.into_lexer()?;
+        Inspection::from_parsed(lexer, semantic_action_table)
+    }
+
+    fn into_lexer(self) -> Result<(ast::Lexer, SemanticActionTable), Error> {
+        let LexerBuilder {
+            public,
+            type_name,
+            user_state_type,
+            token_type,
+            error_type,
+            definition,
+        } = self;
+
+        let type_name = parse_str::<syn::Ident>(&type_name, "lexer name")?;
+        let user_state_type = user_state_type
+            .map(|ty| parse_str::<syn::Type>(&ty, "user state type"))
+            .transpose()?;
+        let token_type = parse_str::<syn::Type>(&token_type, "token type")?;
+        let error_type = error_type
+            .map(|ty| parse_str::<syn::Type>(&ty, "error type"))
+            .transpose()?;
+
+        definition.into_lexer(public, type_name, user_state_type, token_type, error_type)
+    }
+}
+
+fn parse_str<T: syn::parse::Parse>(str: &str, what: &str) -> Result<T, Error> {
+    syn::parse_str::<T>(str).map_err(|error| Error::Invalid {
+        path: None,
+        location: None,
+        message: format!("Invalid {} {:?}: {}", what, str, error),
+    })
+}
+
+/// Bindings and rule sets added to a builder
+#[derive(Debug, Default)]
+pub(crate) struct Definition {
+    bindings: Vec<(String, RegexCtx)>,
+
+    /// Rule sets with their rules
+    rule_sets: Vec<(String, Vec<(Rule, RuleRhs)>)>,
+
+    /// The first error in the added bindings and rules, reported when building the lexer
+    error: Option<Error>,
+}
+
+impl Definition {
+    pub(crate) fn binding(&mut self, name: &str, re: Result<RegexCtx, Error>) {
+        let re = re.and_then(|re| self.check_names(&re.0).map(|()| re));
+        if let Some(re) = self.check(re) {
+            self.bindings.push((name.to_owned(), re));
+        }
+    }
+
+    pub(crate) fn rule_set(&mut self, name: &str) {
+        self.rule_sets.push((name.to_owned(), vec![]));
+    }
+
+    pub(crate) fn rule(&mut self, rule: Result<Rule, Error>, rhs: Result<RuleRhs, Error>) {
+        let rule = rule.and_then(|rule| self.check_names(&rule.lhs).map(|()| rule));
+        let (rule, rhs) = match (self.check(rule), self.check(rhs)) {
+            (Some(rule), Some(rhs)) => (rule, rhs),
+            _ => return,
+        };
+
+        if self.rule_sets.is_empty() {
+            self.rule_set("Init");
+        }
+
+        self.rule_sets.last_mut().unwrap().1.push((rule, rhs));
+    }
+
+    /// Check that the built-in regexes used in `re` exist, and the variables used in `re` are
+    /// bound by the bindings added so far
+    fn check_names(&self, re: &ast::RegexCtx) -> Result<(), Error> {
+        let regexes = std::iter::once(&re.re)
+            .chain(match &re.left_ctx {
+                Some(ast::LeftCtx::Char(left_ctx)) => Some(left_ctx),
+                Some(ast::LeftCtx::Pred(_)) | None => None,
+            })
+            .chain(re.right_ctx.as_ref().map(|right_ctx| &right_ctx.re));
+
+        let mut builtins: Vec<Builtin> = vec![];
+        let mut vars: Vec<Var> = vec![];
+        for regex in regexes {
+            regex.for_each_builtin(&mut |builtin| builtins.push(builtin.clone()));
+            regex.for_each_var(&mut |var| vars.push(var.clone()));
+        }
+
+        let in_regex = |message: String| Error::Invalid {
+            path: None,
+            location: None,
+            message: format!("{} (in regex \"{}\")", message, re),
+        };
+
+        for builtin in &builtins {
+            get_builtin_regex(builtin).map_err(|error| in_regex(error.to_string()))?;
+        }
+
+        for var in &vars {
+            if !self.bindings.iter().any(|(name, _)| *name == var.0) {
+                return Err(in_regex(format!("Unbound variable {:?}", var.0)));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Record the error in `result`, if it's the first one
+    fn check<T>(&mut self, result: Result<T, Error>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(error) => {
+                self.error.get_or_insert(error);
+                None
+            }
+        }
+    }
+
+    /// Build the lexer definition. Bindings come before rule sets, so they can be used in all
+    /// rule sets.
+    pub(crate) fn into_lexer(
+        self,
+        public: bool,
+        type_name: syn::Ident,
+        user_state_type: Option<syn::Type>,
+        token_type: syn::Type,
+        error_type: Option<syn::Type>,
+    ) -> Result<(ast::Lexer, SemanticActionTable), Error> {
+        let Definition {
+            bindings,
+            rule_sets,
+            error,
+        } = self;
+
+        if let Some(error) = error {
+            return Err(error);
+        }
+
+        let mut semantic_action_table = SemanticActionTable::new();
+        let mut rules: Vec<ast::Rule> = vec![];
+
+        if let Some(ty) = error_type {
+            rules.push(ast::Rule::ErrorType { ty });
+        }
+
+        for (name, re) in bindings {
+            rules.push(ast::Rule::Binding {
+                var: Var(name),
+                params: vec![],
+                re: re.0,
+                span: None,
+            });
+        }
+
+        for (name, rule_set_rules) in rule_sets {
+            let name = parse_str::<syn::Ident>(&name, "rule set name")?;
+
+            let rules_ = rule_set_rules
+                .into_iter()
+                .map(|(Rule { lhs, options }, rhs)| ast::SingleRule {
+                    options,
+                    lhs,
+                    rhs: semantic_action_table.add(rhs),
+                    span: None,
+                })
+                .collect();
+
+            rules.push(ast::Rule::RuleSet {
+                name,
+                rules: rules_,
+                span: None,
+            });
+        }
+
+        let lexer = ast::Lexer {
+            public,
+            type_name,
+            user_state_type,
+            token_type,
+            token_enum: None,
+            rules,
+        };
+
+        Ok((lexer, semantic_action_table))
+    }
+}
@@ -1,7 +1,8 @@
 pub mod codegen;
+pub mod dot;
+pub mod minimize;
 pub mod simplify;
 
-#[cfg(test)]
 pub mod simulate;
 
 use crate::collections::{Map, Set};
@@ -12,7 +13,7 @@ use std::convert::TryFrom;
 use std::iter::{FromIterator, IntoIterator};
 
 /// Deterministic finite automate, parameterized on values of accepting states.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DFA<T, A> {
     // Indexed by `StateIdx`
     states: Vec<State<T, A>>,
@@ -30,7 +31,7 @@ impl StateIdx {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct State<T, A> {
     // Is this the initial state of a rule set? This is important as failure transitions in initial
     // states consume the current character, but failure transitions in other states don't. (#12)
@@ -82,6 +83,10 @@ impl<A> DFA<StateIdx, A> {
         StateIdx(0)
     }
 
+    pub fn n_states(&self) -> usize {
+        self.states.len()
+    }
+
     pub fn make_state_accepting(&mut self, state: StateIdx, accept: AcceptingState<A>) {
         self.states[state.0].accepting.push(accept);
     }
@@ -92,11 +97,21 @@ impl<A> DFA<StateIdx, A> {
         new_state_idx
     }
 
-    #[cfg(test)]
     pub fn is_accepting_state(&self, state: StateIdx) -> bool {
         !self.states[state.0].accepting.is_empty()
     }
 
+    /// Accepting states, with the values they accept
+    pub fn iter_accepting_states(
+        &self,
+    ) -> impl Iterator<Item = (StateIdx, &[AcceptingState<A>])> + '_ {
+        self.states
+            .iter()
+            .enumerate()
+            .filter(|(_, state)| !state.accepting.is_empty())
+            .map(|(state_idx, state)| (StateIdx(state_idx), &state.accepting[..]))
+    }
+
     pub fn add_char_transition(&mut self, state: StateIdx, char: char, next: StateIdx) {
         let old = self.states[state.0].char_transitions.insert(char, next);
         assert!(
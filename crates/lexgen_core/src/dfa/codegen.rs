@@ -1,4 +1,5 @@
 mod ctx;
+mod keywords;
 mod search_table;
 
 use ctx::CgCtx;
@@ -6,11 +7,12 @@ use ctx::CgCtx;
 use super::simplify::Trans;
 use super::{State, StateIdx, DFA};
 
-use crate::ast::{RuleKind, RuleRhs};
+use crate::ast::{Anchor, RuleKind, RuleRhs};
 use crate::collections::{Map, Set};
+use crate::left_ctx::{CompiledLeftCtx, LeftCtxIdx, LeftCtxs};
 use crate::nfa::AcceptingState;
 use crate::range_map::{Range, RangeMap};
-use crate::right_ctx::{RightCtxDFAs, RightCtxIdx};
+use crate::right_ctx::{RightCtxDFA, RightCtxDFAs, RightCtxIdx};
 use crate::semantic_action_table::{SemanticActionIdx, SemanticActionTable};
 
 use std::convert::TryFrom;
@@ -67,9 +69,23 @@ impl Fold for SubstLifetimeFolder {
     }
 }
 
+/// Statistics of the generated code, for inspecting lexers
+#[derive(Debug, Clone, Copy)]
+pub struct CodegenStats {
+    /// Number of states in the simplified DFA
+    pub n_states: usize,
+
+    /// Number of states that are generated inline in their only predecessor
+    pub n_inlined_states: usize,
+
+    /// Number of binary search tables generated for large character sets
+    pub n_search_tables: usize,
+}
+
 pub fn reify(
     dfa: DFA<Trans<SemanticActionIdx>, SemanticActionIdx>,
-    right_ctx_dfas: &RightCtxDFAs<StateIdx>,
+    left_ctxs: &LeftCtxs,
+    right_ctx_dfas: &RightCtxDFAs<Trans<()>>,
     semantic_actions: SemanticActionTable,
     user_state_type: Option<syn::Type>,
     user_error_type: Option<syn::Type>,
@@ -77,7 +93,9 @@ pub fn reify(
     lexer_name: syn::Ident,
     token_type: syn::Type,
     public: bool,
-) -> TokenStream {
+) -> (TokenStream, CodegenStats) {
+    let n_states = dfa.states.len();
+
     let rule_name_enum_name =
         syn::Ident::new(&(lexer_name.to_string() + "Rule"), lexer_name.span());
 
@@ -95,6 +113,7 @@ pub fn reify(
         token_type,
         user_error_type,
         rule_states,
+        left_ctxs,
     );
 
     let (user_state_lifetimes, user_state_type, user_state_type_static) = match user_state_type {
@@ -135,10 +154,18 @@ pub fn reify(
     let semantic_action_fns =
         generate_semantic_action_fns(&ctx, &user_state_lifetimes, &semantic_action_fn_ret_ty);
 
+    let left_ctx_fns = generate_left_ctx_fns(&mut ctx, left_ctxs, &user_state_lifetimes);
+
     let right_ctx_fns = generate_right_ctx_fns(&mut ctx, right_ctx_dfas);
 
     let search_tables = ctx.take_search_tables();
 
+    let stats = CodegenStats {
+        n_states,
+        n_inlined_states: ctx.n_inlined_states(),
+        n_search_tables: search_tables.iter().count(),
+    };
+
     let binary_search_fn = if search_tables.is_empty() {
         quote!()
     } else {
@@ -191,10 +218,10 @@ pub fn reify(
     // those methods are not used.
     let lexer_struct_name = syn::Ident::new(&(lexer_name.to_string() + "_"), lexer_name.span());
 
-    quote!(
+    let code = quote!(
         // An enum for the rule sets in the DFA. `Init` is the initial, unnamed rule set.
         #[derive(Clone, Copy)]
-        enum #rule_name_enum_name {
+        #visibility enum #rule_name_enum_name {
             #(#rule_name_idents,)*
         }
 
@@ -213,6 +240,7 @@ pub fn reify(
             #lexer_struct_name<'input, #(#user_state_lifetimes,)* I, #user_state_type>;
 
         // Methods below for using in semantic actions
+        #[allow(dead_code)]
         impl<'input, #(#user_state_lifetimes,)* I: Iterator<Item = char> + Clone, S>
                 #lexer_struct_name<'input, #(#user_state_lifetimes,)* I, S>
         {
@@ -250,6 +278,43 @@ pub fn reify(
             fn peek(&mut self) -> Option<char> {
                 self.0.peek()
             }
+
+            fn peek_nth(&self, n: usize) -> Option<char> {
+                self.0.peek_nth(n)
+            }
+
+            fn peek_str(&self, len: usize) -> ::std::borrow::Cow<'input, str> {
+                self.0.peek_str(len)
+            }
+
+            fn starts_with(&self, prefix: &str) -> bool {
+                self.0.starts_with(prefix)
+            }
+
+            fn next_char(&mut self) -> Option<char> {
+                self.0.next()
+            }
+
+            fn skip_while<F: FnMut(char) -> bool>(&mut self, pred: F) {
+                self.0.skip_while(pred)
+            }
+
+            fn skip_until(&mut self, str: &str) -> bool {
+                self.0.skip_until(str)
+            }
+
+            // Used by `new_at` and other constructors that start lexing at a given location and
+            // rule set
+            fn start_at(
+                mut self,
+                start: ::lexgen_util::Loc,
+                prev_char: Option<char>,
+                rule: #rule_name_enum_name,
+            ) -> Self {
+                self.0.start_at(start, prev_char);
+                self.switch::<()>(rule);
+                self
+            }
         }
 
         impl<'input, #(#user_state_lifetimes,)* S: ::std::default::Default>
@@ -258,6 +323,16 @@ pub fn reify(
             #visibility fn new(input: &'input str) -> Self {
                 #lexer_struct_name(::lexgen_util::Lexer::new(input))
             }
+
+            #[allow(dead_code)]
+            #visibility fn new_at(
+                input: &'input str,
+                start: ::lexgen_util::Loc,
+                prev_char: Option<char>,
+                rule: #rule_name_enum_name,
+            ) -> Self {
+                Self::new(input).start_at(start, prev_char, rule)
+            }
         }
 
         impl<'input #(,#user_state_lifetimes)*>
@@ -266,6 +341,17 @@ pub fn reify(
             #visibility fn new_with_state(input: &'input str, user_state: #user_state_type) -> Self {
                 #lexer_struct_name(::lexgen_util::Lexer::new_with_state(input, user_state))
             }
+
+            #[allow(dead_code)]
+            #visibility fn new_with_state_at(
+                input: &'input str,
+                user_state: #user_state_type,
+                start: ::lexgen_util::Loc,
+                prev_char: Option<char>,
+                rule: #rule_name_enum_name,
+            ) -> Self {
+                Self::new_with_state(input, user_state).start_at(start, prev_char, rule)
+            }
         }
 
         impl<#(#user_state_lifetimes,)* I: Iterator<Item = char> + Clone, S: ::std::default::Default>
@@ -274,6 +360,16 @@ pub fn reify(
             #visibility fn new_from_iter(iter: I) -> Self {
                 #lexer_struct_name(::lexgen_util::Lexer::new_from_iter(iter))
             }
+
+            #[allow(dead_code)]
+            #visibility fn new_from_iter_at(
+                iter: I,
+                start: ::lexgen_util::Loc,
+                prev_char: Option<char>,
+                rule: #rule_name_enum_name,
+            ) -> Self {
+                Self::new_from_iter(iter).start_at(start, prev_char, rule)
+            }
         }
 
         impl<#(#user_state_lifetimes,)* I: Iterator<Item = char> + Clone>
@@ -282,11 +378,39 @@ pub fn reify(
             #visibility fn new_from_iter_with_state(iter: I, user_state: #user_state_type_static) -> Self {
                 #lexer_struct_name(::lexgen_util::Lexer::new_from_iter_with_state(iter, user_state))
             }
+
+            #[allow(dead_code)]
+            #visibility fn new_from_iter_with_state_at(
+                iter: I,
+                user_state: #user_state_type_static,
+                start: ::lexgen_util::Loc,
+                prev_char: Option<char>,
+                rule: #rule_name_enum_name,
+            ) -> Self {
+                Self::new_from_iter_with_state(iter, user_state).start_at(start, prev_char, rule)
+            }
+        }
+
+        impl<'input, #(#user_state_lifetimes,)* I: Iterator<Item = char> + Clone>
+                #lexer_struct_name<'input, #(#user_state_lifetimes,)* I, #user_state_type>
+        {
+            /// Yield `Spanned` tokens instead of `(Loc, Token, Loc)` triples
+            #[allow(dead_code)]
+            #visibility fn spanned(self) -> ::lexgen_util::SpannedIter<Self> {
+                ::lexgen_util::SpannedIter::new(self)
+            }
+
+            /// Attach a source (e.g. file) identifier to locations of tokens and errors
+            #[allow(dead_code)]
+            #visibility fn with_source<Id: Clone>(self, source: Id) -> ::lexgen_util::WithSource<Self, Id> {
+                ::lexgen_util::WithSource::new(self, source)
+            }
         }
 
         #(#search_tables)*
         #binary_search_fn
         #semantic_action_fns
+        #(#left_ctx_fns)*
         #(#right_ctx_fns)*
 
         impl<'input, #(#user_state_lifetimes,)* I: Iterator<Item = char> + Clone> Iterator for
@@ -307,7 +431,9 @@ pub fn reify(
                 }
             }
         }
-    )
+    );
+
+    (code, stats)
 }
 
 fn generate_switch(ctx: &CgCtx, enum_name: &syn::Ident) -> TokenStream {
@@ -383,9 +509,14 @@ fn generate_state_arm(
         predecessors: _,
     } = state;
 
-    let fail = || -> TokenStream {
+    // Details for `InvalidToken` errors raised in this state
+    let expected = describe_expected_chars(state);
+    let rule_set = ctx.rule_set_name(StateIdx(state_idx)).to_owned();
+
+    // `char` is the character that we failed to match, or `None` on end-of-input
+    let fail = |char: TokenStream| -> TokenStream {
         let action = generate_semantic_action_call(&quote!(semantic_action));
-        quote!(match self.0.backtrack() {
+        quote!(match self.0.backtrack_with_details(#char, #expected, #rule_set) {
             Err(err) => return Some(Err(err)),
             Ok(semantic_action) => #action,
         })
@@ -393,10 +524,12 @@ fn generate_state_arm(
 
     // When we can't take char or range transitions, take the 'any' transition if it exists, or
     // fail (backtrack or raise error)
-    let default_action = any_transition
-        .as_ref()
-        .map(|any_transition| generate_any_transition(ctx, states, any_transition, fail()))
-        .unwrap_or_else(fail);
+    let default_action = match any_transition {
+        Some(any_transition) => {
+            generate_any_transition(ctx, states, any_transition, fail(quote!(Some(char))))
+        }
+        None => fail(quote!(Some(char))),
+    };
 
     let state_char_arms = generate_state_char_arms(
         ctx,
@@ -411,7 +544,7 @@ fn generate_state_arm(
     let end_of_input_default_action = if state_idx == 0 {
         quote!(return None;)
     } else {
-        fail()
+        fail(quote!(None))
     };
 
     let end_of_input_action = match end_of_input_transition {
@@ -432,12 +565,21 @@ fn generate_state_arm(
         #end_of_input_action
     );
 
+    // Initial states record the previous character, to be able to check anchors (`^`, `^^`) in
+    // accepting states
+    let mark_rule_start = if *initial {
+        quote!(self.0.mark_rule_start();)
+    } else {
+        quote!()
+    };
+
     if state_idx == 0 {
         assert!(initial);
 
         // See #12 for the special case in state 0 (rule Init)
         quote!(
             self.reset_match();
+            #mark_rule_start
 
             match self.0.next() {
                 None => {
@@ -455,18 +597,13 @@ fn generate_state_arm(
         let mut rhss: Vec<(TokenStream, TokenStream)> = Vec::with_capacity(accepting.len());
         let mut default = quote!();
 
-        for AcceptingState { value, right_ctx } in accepting.iter() {
-            match right_ctx {
-                Some(right_ctx) => {
-                    let right_ctx_fn = right_ctx_fn_name(ctx.lexer_name(), right_ctx);
-                    let semantic_fn = ctx.semantic_action_fn_ident(*value);
-                    rhss.push((
-                        quote!(#right_ctx_fn(self.0.__iter.clone())),
-                        quote!(self.0.set_accepting_state(#semantic_fn)),
-                    ));
+        for accepting_state in accepting.iter() {
+            let semantic_fn = ctx.semantic_action_fn_ident(accepting_state.value);
+            match accepting_state_cond(ctx, accepting_state) {
+                Some(cond) => {
+                    rhss.push((cond, quote!(self.0.set_accepting_state(#semantic_fn))));
                 }
                 None => {
-                    let semantic_fn = ctx.semantic_action_fn_ident(*value);
                     default = quote!(self.0.set_accepting_state(#semantic_fn););
                     break;
                 }
@@ -480,6 +617,7 @@ fn generate_state_arm(
         }
 
         quote!(
+            #mark_rule_start
             #set_accepting_state
 
             match self.0.next() {
@@ -495,17 +633,84 @@ fn generate_state_arm(
         )
     } else {
         // Non-accepting state
-        quote!(match self.0.next() {
-            None => {
-                #end_of_input_action
-            }
-            Some(char) => {
-                match char {
-                    #(#state_char_arms,)*
+        quote!(
+            #mark_rule_start
+
+            match self.0.next() {
+                None => {
+                    #end_of_input_action
+                }
+                Some(char) => {
+                    match char {
+                        #(#state_char_arms,)*
+                    }
                 }
             }
+        )
+    }
+}
+
+/// Number of characters and ranges to show in expected character descriptions of `InvalidToken`
+/// errors before eliding the rest
+const MAX_EXPECTED_ITEMS: usize = 10;
+
+/// Generate a description of the characters accepted in a state, for `InvalidToken` errors.
+/// Example: `'0'-'9', '_', end of input`.
+fn describe_expected_chars<A1, A2>(state: &State<A1, A2>) -> String {
+    if state.any_transition.is_some() {
+        return "any character".to_owned();
+    }
+
+    // Merge characters and ranges, in order
+    let mut ranges: Vec<(u32, u32)> = state
+        .char_transitions
+        .keys()
+        .map(|char| (u32::from(*char), u32::from(*char)))
+        .chain(
+            state
+                .range_transitions
+                .iter()
+                .map(|range| (range.start, range.end)),
+        )
+        .collect();
+
+    ranges.sort_unstable();
+
+    let mut merged_ranges: Vec<(u32, u32)> = vec![];
+    for (start, end) in ranges {
+        match merged_ranges.last_mut() {
+            Some(last) if last.1 + 1 >= start => last.1 = last.1.max(end),
+            _ => merged_ranges.push((start, end)),
+        }
+    }
+
+    if merged_ranges == [(0, u32::from(char::MAX))] && state.end_of_input_transition.is_none() {
+        return "any character".to_owned();
+    }
+
+    let mut items: Vec<String> = merged_ranges
+        .iter()
+        .take(MAX_EXPECTED_ITEMS)
+        .map(|(start, end)| {
+            let start = char::from_u32(*start).unwrap();
+            let end = char::from_u32(*end).unwrap();
+            if start == end {
+                format!("{:?}", start)
+            } else {
+                format!("{:?}-{:?}", start, end)
+            }
         })
+        .collect();
+
+    if merged_ranges.len() > MAX_EXPECTED_ITEMS {
+        items.push("...".to_owned());
     }
+
+    if state.end_of_input_transition.is_some() {
+        items.push("end of input".to_owned());
+    }
+
+    items.join(", ")
 }
 
 fn generate_any_transition(
@@ -666,6 +871,7 @@ fn generate_semantic_action_call(action_fn: &TokenStream) -> TokenStream {
         Err(err) => Err(::lexgen_util::LexerError {
             location: self.match_loc().0,
             kind: ::lexgen_util::LexerErrorKind::Custom(err),
+            details: None,
         }),
     });
 
@@ -688,36 +894,18 @@ fn generate_semantic_action_fns(
     semantic_action_fn_ret_ty: &TokenStream,
 ) -> TokenStream {
     let lexer_name = ctx.lexer_name();
-    let token_type = ctx.token_type();
 
     let fns: Vec<TokenStream> = ctx
         .iter_semantic_actions()
         .map(|(idx, action)| {
             let ident = ctx.semantic_action_fn_ident(idx);
 
-            let rhs = match action {
-                RuleRhs::None => {
-                    quote!(|__lexer: &mut #lexer_name<'input, #(#user_state_lifetimes, )* I>| __lexer.continue_().map_token(Ok))
-                }
-
-                RuleRhs::Rhs { expr, kind } => {
-                    match kind {
-                        RuleKind::Simple => {
-                            quote!(|__lexer: &'lexer mut #lexer_name<'input, #(#user_state_lifetimes, )* I>| __lexer.return_(#expr).map_token(Ok))
-                        }
-                        RuleKind::Fallible => quote!(#expr),
-                        RuleKind::Infallible => {
-                            quote!(|__lexer: &'lexer mut #lexer_name<'input, #(#user_state_lifetimes, )* I>| {
-                                let semantic_action:
-                                    fn(&'lexer mut #lexer_name<'input, #(#user_state_lifetimes, )* I>) -> ::lexgen_util::SemanticActionResult<#token_type> =
-                                        #expr;
-
-                                semantic_action(__lexer).map_token(Ok)
-                            })
-                        }
-                    }
-                }
-            };
+            let rhs = generate_semantic_action_closure(
+                ctx,
+                action,
+                user_state_lifetimes,
+                semantic_action_fn_ret_ty,
+            );
 
             quote!(
                 #[allow(non_snake_case)]
@@ -732,6 +920,152 @@ fn generate_semantic_action_fns(
     quote!(#(#fns)*)
 }
 
+/// Generate a closure for a semantic action. The closure has type `fn(&'lexer mut Lexer<...>) ->
+/// <semantic_action_fn_ret_ty>`.
+fn generate_semantic_action_closure(
+    ctx: &CgCtx,
+    action: &RuleRhs,
+    user_state_lifetimes: &Vec<syn::Lifetime>,
+    semantic_action_fn_ret_ty: &TokenStream,
+) -> TokenStream {
+    let lexer_name = ctx.lexer_name();
+    let token_type = ctx.token_type();
+
+    match action {
+        RuleRhs::None => {
+            quote!(|__lexer: &mut #lexer_name<'input, #(#user_state_lifetimes, )* I>| __lexer.continue_().map_token(Ok))
+        }
+
+        RuleRhs::Rhs { expr, kind } => match kind {
+            RuleKind::Simple => {
+                quote!(|__lexer: &'lexer mut #lexer_name<'input, #(#user_state_lifetimes, )* I>| __lexer.return_(#expr).map_token(Ok))
+            }
+            RuleKind::Fallible => quote!(#expr),
+            RuleKind::Infallible => {
+                quote!(|__lexer: &'lexer mut #lexer_name<'input, #(#user_state_lifetimes, )* I>| {
+                    let semantic_action:
+                        fn(&'lexer mut #lexer_name<'input, #(#user_state_lifetimes, )* I>) -> ::lexgen_util::SemanticActionResult<#token_type> =
+                            #expr;
+
+                    semantic_action(__lexer).map_token(Ok)
+                })
+            }
+        },
+
+        RuleRhs::Keywords { keywords, rhs } => {
+            let lookup_fn_name = syn::Ident::new("keyword_idx", Span::call_site());
+
+            let keyword_strs: Vec<&str> = keywords
+                .iter()
+                .map(|(keyword, _)| keyword.as_str())
+                .collect();
+
+            let lookup_fn = keywords::generate_keyword_lookup_fn(&lookup_fn_name, &keyword_strs);
+
+            let keyword_arms: Vec<TokenStream> = keywords
+                .iter()
+                .enumerate()
+                .map(|(keyword_idx, (_, token))| {
+                    quote!(Some(#keyword_idx) => __lexer.return_(#token).map_token(Ok),)
+                })
+                .collect();
+
+            let rhs = generate_semantic_action_closure(
+                ctx,
+                rhs,
+                user_state_lifetimes,
+                semantic_action_fn_ret_ty,
+            );
+
+            quote!(|__lexer: &'lexer mut #lexer_name<'input, #(#user_state_lifetimes, )* I>| {
+                #lookup_fn
+
+                match #lookup_fn_name(&__lexer.0.match_str()) {
+                    #(#keyword_arms)*
+                    _ => {
+                        let action: fn(&'lexer mut #lexer_name<'input, #(#user_state_lifetimes, )* I>) -> #semantic_action_fn_ret_ty = #rhs;
+                        action(__lexer)
+                    }
+                }
+            })
+        }
+    }
+}
+
+fn left_ctx_fn_name(lexer_name: &syn::Ident, idx: &LeftCtxIdx) -> syn::Ident {
+    syn::Ident::new(
+        &format!("{}_LEFT_CTX_{}", lexer_name, idx.as_usize()),
+        Span::call_site(),
+    )
+}
+
+fn generate_left_ctx_fns(
+    ctx: &mut CgCtx,
+    left_ctxs: &LeftCtxs,
+    user_state_lifetimes: &Vec<syn::Lifetime>,
+) -> Vec<TokenStream> {
+    let mut fns = vec![];
+
+    let lexer_name = ctx.lexer_name().clone();
+
+    for (idx, left_ctx) in left_ctxs.iter() {
+        let fn_name = left_ctx_fn_name(&lexer_name, &idx);
+
+        let char_set = match left_ctx {
+            CompiledLeftCtx::Char(char_set) => char_set,
+            CompiledLeftCtx::Pred(pred) => {
+                fns.push(quote!(
+                    #[allow(non_snake_case)]
+                    fn #fn_name<'lexer, #(#user_state_lifetimes, )* 'input, I: Iterator<Item = char> + Clone>(lexer: &'lexer mut #lexer_name<'input, #(#user_state_lifetimes, )* I>) -> bool {
+                        let pred: fn(&'lexer mut #lexer_name<'input, #(#user_state_lifetimes, )* I>) -> bool = #pred;
+                        pred(lexer)
+                    }
+                ));
+                continue;
+            }
+        };
+
+        let ranges: Vec<(char, char)> = char_set
+            .iter()
+            .map(|range| {
+                (
+                    char::try_from(range.start).unwrap(),
+                    char::try_from(range.end).unwrap(),
+                )
+            })
+            .collect();
+
+        let guard = if ranges.is_empty() {
+            quote!(false)
+        } else if ranges.len() > MAX_GUARD_SIZE {
+            let binary_search_table_id = ctx.add_search_table(ranges);
+
+            quote!(binary_search(x, &#binary_search_table_id))
+        } else {
+            let range_checks: Vec<TokenStream> = ranges
+                .into_iter()
+                .map(|(range_begin, range_end)| {
+                    inclusive_range_contains(quote!(x), range_begin, range_end)
+                })
+                .collect();
+
+            quote!(#(#range_checks)||*)
+        };
+
+        fns.push(quote!(
+            #[allow(non_snake_case)]
+            fn #fn_name(char: Option<char>) -> bool {
+                match char {
+                    None => false,
+                    Some(x) => #guard,
+                }
+            }
+        ));
+    }
+
+    fns
+}
+
 fn right_ctx_fn_name(lexer_name: &syn::Ident, idx: &RightCtxIdx) -> syn::Ident {
     syn::Ident::new(
         &format!("{}_RIGHT_CTX_{}", lexer_name, idx.as_usize()),
@@ -741,22 +1075,25 @@ fn right_ctx_fn_name(lexer_name: &syn::Ident, idx: &RightCtxIdx) -> syn::Ident {
 
 fn generate_right_ctx_fns(
     ctx: &mut CgCtx,
-    right_ctx_dfas: &RightCtxDFAs<StateIdx>,
+    right_ctx_dfas: &RightCtxDFAs<Trans<()>>,
 ) -> Vec<TokenStream> {
     let mut fns = vec![];
 
     let lexer_name = ctx.lexer_name().clone();
 
-    for (idx, dfa) in right_ctx_dfas.iter() {
+    for (idx, RightCtxDFA { dfa, negated }) in right_ctx_dfas.iter() {
         let fn_name = right_ctx_fn_name(&lexer_name, &idx);
 
         let match_arms = generate_right_ctx_state_arms(ctx, dfa);
 
+        // The loop evaluates to whether the DFA matched
+        let negate = if *negated { quote!(!) } else { quote!() };
+
         fns.push(
             quote!(fn #fn_name<I: Iterator<Item = char> + Clone>(mut input: I) -> bool {
                 let mut state: usize = 0;
 
-                loop {
+                #negate loop {
                     match state {
                         #(#match_arms)*
                     }
@@ -768,7 +1105,7 @@ fn generate_right_ctx_fns(
     fns
 }
 
-fn generate_right_ctx_state_arms(ctx: &mut CgCtx, dfa: &DFA<StateIdx, ()>) -> Vec<TokenStream> {
+fn generate_right_ctx_state_arms(ctx: &mut CgCtx, dfa: &DFA<Trans<()>, ()>) -> Vec<TokenStream> {
     let DFA { states } = dfa;
 
     let mut match_arms: Vec<TokenStream> = vec![];
@@ -776,7 +1113,7 @@ fn generate_right_ctx_state_arms(ctx: &mut CgCtx, dfa: &DFA<StateIdx, ()>) -> Ve
     let n_states = states.len();
 
     for (state_idx, state) in states.iter().enumerate() {
-        let state_code: TokenStream = generate_right_ctx_state_arm(ctx, state, states);
+        let state_code: TokenStream = generate_right_ctx_state_arm(ctx, state);
 
         let state_idx_pat = if state_idx == n_states - 1 {
             quote!(_)
@@ -792,11 +1129,7 @@ fn generate_right_ctx_state_arms(ctx: &mut CgCtx, dfa: &DFA<StateIdx, ()>) -> Ve
     match_arms
 }
 
-fn generate_right_ctx_state_arm(
-    ctx: &mut CgCtx,
-    state: &State<StateIdx, ()>,
-    states: &[State<StateIdx, ()>],
-) -> TokenStream {
+fn generate_right_ctx_state_arm(ctx: &mut CgCtx, state: &State<Trans<()>, ()>) -> TokenStream {
     let State {
         initial: _,
         char_transitions,
@@ -807,27 +1140,29 @@ fn generate_right_ctx_state_arm(
         predecessors: _,
     } = state;
 
-    let state_char_arms =
-        generate_right_ctx_state_char_arms(ctx, states, char_transitions, range_transitions);
-
     // Make sure right contexts don't have right contexts. We don't allow this in the syntax
     // currently.
     for accepting_state in accepting {
         assert_eq!(accepting_state.right_ctx, None);
     }
 
+    // After simplification only the initial state can be accepting, when the right context
+    // matches the empty string
     if !accepting.is_empty() {
-        return quote!(return true);
+        return quote!(break true);
     }
 
+    let state_char_arms =
+        generate_right_ctx_state_char_arms(ctx, char_transitions, range_transitions);
+
     let eof = match end_of_input_transition {
-        Some(StateIdx(eof_next)) => quote!(state = #eof_next),
-        None => quote!(return false),
+        Some(next) => right_ctx_trans(next),
+        None => quote!(break false),
     };
 
     let def = match any_transition {
-        Some(StateIdx(any_next)) => quote!(state = #any_next),
-        None => quote!(return false),
+        Some(next) => right_ctx_trans(next),
+        None => quote!(break false),
     };
 
     quote!(
@@ -843,12 +1178,18 @@ fn generate_right_ctx_state_arm(
     )
 }
 
+fn right_ctx_trans(trans: &Trans<()>) -> TokenStream {
+    match trans {
+        Trans::Accept(_) => quote!(break true),
+        Trans::Trans(StateIdx(next_state)) => quote!(state = #next_state),
+    }
+}
+
 // NB. Does not add default case
 fn generate_right_ctx_state_char_arms(
     ctx: &mut CgCtx,
-    states: &[State<StateIdx, ()>],
-    char_transitions: &Map<char, StateIdx>,
-    range_transitions: &RangeMap<StateIdx>,
+    char_transitions: &Map<char, Trans<()>>,
+    range_transitions: &RangeMap<Trans<()>>,
 ) -> Vec<TokenStream> {
     // Arms of the `match` for the current character
     let mut state_char_arms: Vec<TokenStream> = vec![];
@@ -861,22 +1202,23 @@ fn generate_right_ctx_state_char_arms(
     let mut accept_chars: Set<char> = Default::default();
 
     for (char, next) in char_transitions {
-        if states[next.0].accepting.is_empty() {
-            state_chars.entry(*next).or_default().push(*char);
-        } else {
-            accept_chars.insert(*char);
+        match next {
+            Trans::Trans(next) => state_chars.entry(*next).or_default().push(*char),
+            Trans::Accept(_) => {
+                accept_chars.insert(*char);
+            }
         }
     }
 
     // Add char transitions
     for (StateIdx(next_state), chars) in state_chars.iter() {
         let pat = quote!(#(#chars)|*);
-        state_char_arms.push(quote!(#pat => self.state = #next_state));
+        state_char_arms.push(quote!(#pat => state = #next_state));
     }
 
     if !accept_chars.is_empty() {
         let accept_chars: Vec<char> = accept_chars.into_iter().collect();
-        state_char_arms.push(quote!(#(#accept_chars)|* => return true));
+        state_char_arms.push(quote!(#(#accept_chars)|* => break true));
     }
 
     // Same as above for range transitions. Use chain of "or"s for ranges with same transition.
@@ -892,10 +1234,11 @@ fn generate_right_ctx_state_char_arms(
         let start = char::try_from(*start).unwrap();
         let end = char::try_from(*end).unwrap();
 
-        if states[next.0].accepting.is_empty() {
-            state_ranges.entry(*next).or_default().push((start, end));
-        } else {
-            accept_ranges.insert((start, end));
+        match next {
+            Trans::Trans(next) => state_ranges.entry(*next).or_default().push((start, end)),
+            Trans::Accept(_) => {
+                accept_ranges.insert((start, end));
+            }
         }
     }
 
@@ -935,7 +1278,7 @@ fn generate_right_ctx_state_char_arms(
             quote!(#(#range_checks)||*)
         };
 
-        state_char_arms.push(quote!(x if #guard => return true));
+        state_char_arms.push(quote!(x if #guard => break true));
     }
 
     state_char_arms
@@ -949,12 +1292,11 @@ fn test_right_ctxs(
     let mut alts: Vec<(TokenStream, TokenStream)> = Vec::with_capacity(accepting_states.len());
     let mut default = default_rhs;
 
-    for AcceptingState { value, right_ctx } in accepting_states {
-        let action_code = generate_rhs_code(ctx, *value);
-        match right_ctx {
-            Some(right_ctx) => {
-                let right_ctx_fn = right_ctx_fn_name(ctx.lexer_name(), right_ctx);
-                alts.push((quote!(#right_ctx_fn(self.0.__iter.clone())), action_code));
+    for accepting_state in accepting_states {
+        let action_code = generate_rhs_code(ctx, accepting_state.value);
+        match accepting_state_cond(ctx, accepting_state) {
+            Some(cond) => {
+                alts.push((cond, action_code));
             }
             None => {
                 default = action_code;
@@ -972,10 +1314,56 @@ fn test_right_ctxs(
     action_code
 }
 
-fn inclusive_range_contains(value: TokenStream, range_start: char, range_end: char) -> TokenStream {
-    if range_start == range_end {
-        quote!(#value == #range_start)
+/// Generate the condition for accepting a match of a rule, checking the rule's anchor, left
+/// context, and right context. Returns `None` when the rule does not have any of these.
+fn accepting_state_cond(
+    ctx: &CgCtx,
+    accepting_state: &AcceptingState<SemanticActionIdx>,
+) -> Option<TokenStream> {
+    let AcceptingState {
+        value: _,
+        anchor,
+        left_ctx,
+        right_ctx,
+    } = accepting_state;
+
+    let mut conds: Vec<TokenStream> = vec![];
+
+    match anchor {
+        Some(Anchor::LineStart) => conds.push(quote!(self.0.at_line_start())),
+        Some(Anchor::InputStart) => conds.push(quote!(self.0.at_input_start())),
+        None => {}
+    }
+
+    if let Some(left_ctx) = left_ctx {
+        let left_ctx_fn = left_ctx_fn_name(ctx.lexer_name(), left_ctx);
+        if ctx.is_pred_left_ctx(left_ctx) {
+            conds.push(quote!(#left_ctx_fn(self)));
+        } else {
+            conds.push(quote!(#left_ctx_fn(self.0.rule_start_last_char())));
+        }
+    }
+
+    if let Some(right_ctx) = right_ctx {
+        let right_ctx_fn = right_ctx_fn_name(ctx.lexer_name(), right_ctx);
+        conds.push(quote!(#right_ctx_fn(self.0.__iter.clone())));
+    }
+
+    if conds.is_empty() {
+        None
     } else {
-        quote!((#range_start..=#range_end).contains(&#value))
+        Some(quote!(#(#conds)&&*))
+    }
+}
+
+fn inclusive_range_contains(value: TokenStream, range_start: char, range_end: char) -> TokenStream {
+    // Use `char` methods for common ASCII ranges to avoid `manual_is_ascii_check` lints in
+    // generated code
+    match (range_start, range_end) {
+        _ if range_start == range_end => quote!(#value == #range_start),
+        ('a', 'z') => quote!(#value.is_ascii_lowercase()),
+        ('A', 'Z') => quote!(#value.is_ascii_uppercase()),
+        ('0', '9') => quote!(#value.is_ascii_digit()),
+        _ => quote!((#range_start..=#range_end).contains(&#value)),
     }
 }
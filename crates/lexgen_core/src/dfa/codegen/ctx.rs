@@ -2,8 +2,9 @@ use super::search_table::SearchTableSet;
 use super::StateIdx;
 use super::DFA;
 use crate::ast::RuleRhs;
-use crate::collections::Map;
+use crate::collections::{Map, Set};
 use crate::dfa::simplify::Trans;
+use crate::left_ctx::{CompiledLeftCtx, LeftCtxIdx, LeftCtxs};
 use crate::semantic_action_table::{SemanticActionIdx, SemanticActionTable};
 
 /// Code generation state
@@ -24,6 +25,13 @@ pub struct CgCtx {
     /// final DFA.
     rule_states: Map<String, StateIdx>,
 
+    /// Maps states to the names of the rule sets they belong to
+    state_rule_sets: Map<StateIdx, String>,
+
+    /// Left contexts that are predicates. Predicate functions take the lexer handle, other left
+    /// context functions take the previous character.
+    pred_left_ctxs: Set<LeftCtxIdx>,
+
     /// Sorted vector of states with only one predecessor. These states will be inlined in the
     /// predecessor states and won't appear in the final code. Inlining these states significantly
     /// improves code size and runtime performance.
@@ -50,6 +58,7 @@ impl CgCtx {
         token_type: syn::Type,
         user_error_type: Option<syn::Type>,
         rule_states: Map<String, StateIdx>,
+        left_ctxs: &LeftCtxs,
     ) -> CgCtx {
         let inlined_states: Vec<StateIdx> = dfa
             .states
@@ -64,12 +73,49 @@ impl CgCtx {
             })
             .collect();
 
+        // Rule sets are not connected to each other in the DFA (rule sets are switched in semantic
+        // actions), so a state belongs to the rule set whose initial state reaches it
+        let mut state_rule_sets: Map<StateIdx, String> = Default::default();
+        for (rule_set, initial_state) in &rule_states {
+            let mut work_list: Vec<StateIdx> = vec![*initial_state];
+            while let Some(state_idx) = work_list.pop() {
+                if state_rule_sets
+                    .insert(state_idx, rule_set.clone())
+                    .is_some()
+                {
+                    continue;
+                }
+                let state = &dfa.states[state_idx.0];
+                let next_states = state
+                    .char_transitions
+                    .values()
+                    .chain(state.range_transitions.iter().map(|range| &range.value))
+                    .chain(state.any_transition.iter())
+                    .chain(state.end_of_input_transition.iter());
+                for next in next_states {
+                    if let Trans::Trans(next_state) = next {
+                        work_list.push(*next_state);
+                    }
+                }
+            }
+        }
+
+        let pred_left_ctxs: Set<LeftCtxIdx> = left_ctxs
+            .iter()
+            .filter_map(|(idx, left_ctx)| match left_ctx {
+                CompiledLeftCtx::Pred(_) => Some(idx),
+                CompiledLeftCtx::Char(_) => None,
+            })
+            .collect();
+
         CgCtx {
             semantic_action_table,
             lexer_name,
             token_type,
             user_error_type,
             rule_states,
+            state_rule_sets,
+            pred_left_ctxs,
             inlined_states,
             codegen_state: CgState {
                 search_tables: SearchTableSet::new(),
@@ -88,6 +134,10 @@ impl CgCtx {
         }
     }
 
+    pub fn is_pred_left_ctx(&self, left_ctx: &LeftCtxIdx) -> bool {
+        self.pred_left_ctxs.contains(left_ctx)
+    }
+
     pub fn n_inlined_states(&self) -> usize {
         self.inlined_states.len()
     }
@@ -112,6 +162,15 @@ impl CgCtx {
         &self.rule_states
     }
 
+    /// Name of the rule set that a state belongs to. Empty for states that are not reachable from
+    /// any of the rule sets.
+    pub fn rule_set_name(&self, state: StateIdx) -> &str {
+        self.state_rule_sets
+            .get(&state)
+            .map(String::as_str)
+            .unwrap_or_default()
+    }
+
     pub fn iter_semantic_actions(&self) -> impl Iterator<Item = (SemanticActionIdx, &RuleRhs)> {
         self.semantic_action_table.iter()
     }
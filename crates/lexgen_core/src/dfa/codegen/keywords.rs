@@ -0,0 +1,67 @@
+//! Code generation for keyword tables
+//!
+//! A rule with a keyword table checks whether the matched string is one of the keywords before
+//! running the rule's semantic action. Keywords are compiled to a trie of nested `match`es on the
+//! characters of the matched string, so a lookup visits each character at most once, and the
+//! keywords don't add any states to the lexer's DFA.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use std::collections::BTreeMap;
+
+#[derive(Default)]
+struct Trie {
+    /// Index of the keyword that ends at this node
+    keyword: Option<usize>,
+
+    /// `BTreeMap` to generate the same code for the same keywords
+    children: BTreeMap<char, Trie>,
+}
+
+/// Generates a function `fn <fn_name>(s: &str) -> Option<usize>` that returns the index of `s` in
+/// `keywords`.
+pub fn generate_keyword_lookup_fn(fn_name: &syn::Ident, keywords: &[&str]) -> TokenStream {
+    let mut trie = Trie::default();
+
+    for (keyword_idx, keyword) in keywords.iter().enumerate() {
+        let mut node = &mut trie;
+        for char in keyword.chars() {
+            node = node.children.entry(char).or_default();
+        }
+        node.keyword = Some(keyword_idx);
+    }
+
+    let lookup_code = generate_trie_node(&trie);
+
+    quote!(
+        fn #fn_name(s: &str) -> Option<usize> {
+            let mut chars = s.chars();
+            #lookup_code
+        }
+    )
+}
+
+fn generate_trie_node(node: &Trie) -> TokenStream {
+    let end_of_input = match node.keyword {
+        Some(keyword_idx) => quote!(Some(#keyword_idx)),
+        None => quote!(None),
+    };
+
+    let child_arms: Vec<TokenStream> = node
+        .children
+        .iter()
+        .map(|(char, child)| {
+            let child_code = generate_trie_node(child);
+            quote!(Some(#char) => #child_code,)
+        })
+        .collect();
+
+    quote!(
+        match chars.next() {
+            None => #end_of_input,
+            #(#child_arms)*
+            _ => None,
+        }
+    )
+}
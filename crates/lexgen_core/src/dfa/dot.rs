@@ -0,0 +1,162 @@
+use super::simplify::Trans;
+use super::{State, StateIdx, DFA};
+
+use crate::dot::{accepting_label, char_label, range_label, DotGraph};
+use crate::nfa::AcceptingState;
+
+#[cfg(test)]
+use std::fmt::Debug;
+
+/// Transitions of DFAs that can be drawn: transitions to states, and in simplified DFAs,
+/// transitions that accept without moving to a state
+pub trait DotTrans<A> {
+    /// Add a transition from node `from` to `graph`
+    fn add_to_dot(
+        &self,
+        graph: &mut DotGraph,
+        prefix: &str,
+        from: &str,
+        label: &str,
+        value_label: &dyn Fn(&A) -> String,
+    );
+}
+
+impl<A> DotTrans<A> for StateIdx {
+    fn add_to_dot(
+        &self,
+        graph: &mut DotGraph,
+        prefix: &str,
+        from: &str,
+        label: &str,
+        _value_label: &dyn Fn(&A) -> String,
+    ) {
+        graph.edge(from, &format!("{}{}", prefix, self.0), label);
+    }
+}
+
+impl<A> DotTrans<A> for Trans<A> {
+    fn add_to_dot(
+        &self,
+        graph: &mut DotGraph,
+        prefix: &str,
+        from: &str,
+        label: &str,
+        value_label: &dyn Fn(&A) -> String,
+    ) {
+        match self {
+            Trans::Trans(next) => {
+                <StateIdx as DotTrans<A>>::add_to_dot(
+                    next,
+                    graph,
+                    prefix,
+                    from,
+                    label,
+                    value_label,
+                );
+            }
+            Trans::Accept(accepting) => {
+                let accept = graph.accept_node(prefix, &accepting_labels(accepting, value_label));
+                graph.edge(from, &accept, label);
+            }
+        }
+    }
+}
+
+impl<T: DotTrans<A>, A> DFA<T, A> {
+    /// Add states and transitions of the DFA to `graph`. Node names start with `prefix`.
+    /// `initial_labels` are the labels of the arrows to initial states, e.g. rule set names.
+    /// `value_label` gives labels of accepting states.
+    pub fn add_to_dot(
+        &self,
+        graph: &mut DotGraph,
+        prefix: &str,
+        initial_labels: &[(String, StateIdx)],
+        value_label: &dyn Fn(&A) -> String,
+    ) {
+        let node = |state_idx: usize| format!("{}{}", prefix, state_idx);
+
+        for (state_idx, state) in self.states.iter().enumerate() {
+            if state.accepting.is_empty() {
+                graph.state(&node(state_idx), &state_idx.to_string(), false);
+            } else {
+                graph.state(
+                    &node(state_idx),
+                    &format!(
+                        "{}\n{}",
+                        state_idx,
+                        accepting_labels(&state.accepting, value_label)
+                    ),
+                    true,
+                );
+            }
+
+            if state.initial {
+                let label = initial_labels
+                    .iter()
+                    .find(|(_, initial_state)| initial_state.0 == state_idx)
+                    .map(|(label, _)| label.as_str())
+                    .unwrap_or("");
+                graph.initial(&node(state_idx), label);
+            }
+        }
+
+        for (state_idx, state) in self.states.iter().enumerate() {
+            let State {
+                initial: _,
+                char_transitions,
+                range_transitions,
+                any_transition,
+                end_of_input_transition,
+                accepting: _,
+                predecessors: _,
+            } = state;
+
+            let from = node(state_idx);
+
+            for (char, next) in char_transitions {
+                next.add_to_dot(graph, prefix, &from, &char_label(*char), value_label);
+            }
+
+            for range in range_transitions.iter() {
+                range.value.add_to_dot(
+                    graph,
+                    prefix,
+                    &from,
+                    &range_label(range.start, range.end),
+                    value_label,
+                );
+            }
+
+            if let Some(next) = any_transition {
+                next.add_to_dot(graph, prefix, &from, "_", value_label);
+            }
+
+            if let Some(next) = end_of_input_transition {
+                next.add_to_dot(graph, prefix, &from, "$", value_label);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+impl<T: DotTrans<A>, A: Debug> DFA<T, A> {
+    /// DOT graph of the DFA, with accepting states labelled with the `Debug` output of their
+    /// values
+    pub fn to_dot(&self) -> String {
+        let mut graph = DotGraph::new();
+        self.add_to_dot(&mut graph, "s", &[], &|value| format!("{:?}", value));
+        graph.finish()
+    }
+}
+
+/// Labels of the accepting states of a DFA state, one per line, in priority order
+fn accepting_labels<A>(
+    accepting: &[AcceptingState<A>],
+    value_label: &dyn Fn(&A) -> String,
+) -> String {
+    accepting
+        .iter()
+        .map(|accepting| accepting_label(accepting, value_label))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
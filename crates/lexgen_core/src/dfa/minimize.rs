@@ -0,0 +1,190 @@
+//! Minimization of right context DFAs
+//!
+//! Generated code for a right context stops as soon as the right context DFA reaches an accepting
+//! state, so transitions from accepting states are never taken and all accepting states are
+//! equivalent. We drop those transitions, then merge states with the same transitions using
+//! Moore's partition refinement. States of the result are numbered in the order they are reached
+//! from the initial state, so right contexts that are equal after minimization have equal DFAs.
+
+use super::{StateIdx, DFA};
+use crate::range_map::{Range, RangeMap};
+
+/// Equivalence class of a state, with the state's transitions to other classes
+#[derive(PartialEq, Eq)]
+struct Signature {
+    class: usize,
+    char_transitions: Vec<(char, usize)>,
+    range_transitions: Vec<Range<usize>>,
+    any_transition: Option<usize>,
+    end_of_input_transition: Option<usize>,
+}
+
+pub fn minimize_right_ctx(dfa: &DFA<StateIdx, ()>) -> DFA<StateIdx, ()> {
+    // Start with two classes: accepting and non-accepting states
+    let mut classes: Vec<usize> = dfa
+        .states
+        .iter()
+        .map(|state| usize::from(!state.accepting.is_empty()))
+        .collect();
+
+    let mut n_classes = 0;
+
+    loop {
+        let signatures: Vec<Signature> = (0..dfa.states.len())
+            .map(|state_idx| signature(dfa, &classes, StateIdx(state_idx)))
+            .collect();
+
+        let mut class_signatures: Vec<&Signature> = vec![];
+        let mut new_classes: Vec<usize> = Vec::with_capacity(signatures.len());
+
+        for signature in &signatures {
+            match class_signatures
+                .iter()
+                .position(|other| *other == signature)
+            {
+                Some(class) => new_classes.push(class),
+                None => {
+                    new_classes.push(class_signatures.len());
+                    class_signatures.push(signature);
+                }
+            }
+        }
+
+        classes = new_classes;
+
+        // Refining a partition never merges classes, so the partition is stable when the number
+        // of classes does not change
+        if class_signatures.len() == n_classes {
+            break;
+        }
+
+        n_classes = class_signatures.len();
+    }
+
+    // Number classes reachable from the initial state in the order they're reached, using the
+    // first state of a class as the representative
+    let mut representatives: Vec<StateIdx> = vec![dfa.initial_state()];
+    let mut class_indices: Vec<Option<StateIdx>> = vec![None; n_classes];
+    class_indices[classes[dfa.initial_state().0]] = Some(StateIdx(0));
+
+    let mut work_idx = 0;
+    while work_idx < representatives.len() {
+        let signature = signature(dfa, &classes, representatives[work_idx]);
+        work_idx += 1;
+
+        let next_classes = signature
+            .char_transitions
+            .iter()
+            .map(|(_, next)| *next)
+            .chain(signature.range_transitions.iter().map(|range| range.value))
+            .chain(signature.any_transition)
+            .chain(signature.end_of_input_transition);
+
+        for next_class in next_classes {
+            if class_indices[next_class].is_none() {
+                class_indices[next_class] = Some(StateIdx(representatives.len()));
+                let representative = classes.iter().position(|class| *class == next_class);
+                representatives.push(StateIdx(representative.unwrap()));
+            }
+        }
+    }
+
+    let (mut new_dfa, _) = DFA::new();
+
+    for _ in 1..representatives.len() {
+        new_dfa.new_state();
+    }
+
+    let class_idx = |class: usize| class_indices[class].unwrap();
+
+    for (new_state_idx, representative) in representatives.iter().enumerate() {
+        let new_state_idx = StateIdx(new_state_idx);
+
+        let Signature {
+            class: _,
+            char_transitions,
+            range_transitions,
+            any_transition,
+            end_of_input_transition,
+        } = signature(dfa, &classes, *representative);
+
+        for accepting_state in &dfa.states[representative.0].accepting {
+            new_dfa.make_state_accepting(new_state_idx, *accepting_state);
+        }
+
+        for (char, next) in char_transitions {
+            new_dfa.add_char_transition(new_state_idx, char, class_idx(next));
+        }
+
+        if !range_transitions.is_empty() {
+            new_dfa.set_range_transitions(
+                new_state_idx,
+                RangeMap::from_non_overlapping_sorted_ranges(
+                    range_transitions
+                        .into_iter()
+                        .map(|Range { start, end, value }| Range {
+                            start,
+                            end,
+                            value: class_idx(value),
+                        })
+                        .collect(),
+                ),
+            );
+        }
+
+        if let Some(next) = any_transition {
+            new_dfa.set_any_transition(new_state_idx, class_idx(next));
+        }
+
+        if let Some(next) = end_of_input_transition {
+            new_dfa.set_end_of_input_transition(new_state_idx, class_idx(next));
+        }
+    }
+
+    new_dfa
+}
+
+fn signature(dfa: &DFA<StateIdx, ()>, classes: &[usize], state_idx: StateIdx) -> Signature {
+    let state = &dfa.states[state_idx.0];
+    let class = classes[state_idx.0];
+
+    // Transitions of accepting states are never taken
+    if !state.accepting.is_empty() {
+        return Signature {
+            class,
+            char_transitions: vec![],
+            range_transitions: vec![],
+            any_transition: None,
+            end_of_input_transition: None,
+        };
+    }
+
+    let mut char_transitions: Vec<(char, usize)> = state
+        .char_transitions
+        .iter()
+        .map(|(char, next)| (*char, classes[next.0]))
+        .collect();
+    char_transitions.sort_unstable();
+
+    // Merge adjacent ranges that transition to the same class
+    let mut range_transitions: Vec<Range<usize>> = vec![];
+    for Range { start, end, value } in state.range_transitions.iter() {
+        let value = classes[value.0];
+        match range_transitions.last_mut() {
+            Some(last) if last.end + 1 == *start && last.value == value => last.end = *end,
+            _ => range_transitions.push(Range {
+                start: *start,
+                end: *end,
+                value,
+            }),
+        }
+    }
+
+    Signature {
+        class,
+        char_transitions,
+        range_transitions,
+        any_transition: state.any_transition.map(|next| classes[next.0]),
+        end_of_input_transition: state.end_of_input_transition.map(|next| classes[next.0]),
+    }
+}
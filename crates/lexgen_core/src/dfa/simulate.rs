@@ -1,20 +1,37 @@
 use super::{StateIdx, DFA};
 
+use crate::left_ctx::LeftCtxs;
+use crate::nfa::simulate::{check_anchor, check_left_ctx};
 pub use crate::nfa::simulate::{ErrorLoc, Matches};
 use crate::nfa::AcceptingState;
 use crate::range_map::Range;
 use crate::right_ctx::RightCtxDFAs;
 
 impl<A: Copy> DFA<StateIdx, A> {
+    #[cfg(test)]
     pub fn simulate<'input>(
         &self,
         input: &'input str,
+        left_ctxs: &LeftCtxs,
+        right_ctx_dfas: &RightCtxDFAs<StateIdx>,
+    ) -> (Matches<'input, A>, Option<ErrorLoc>) {
+        self.simulate_from(self.initial_state(), input, left_ctxs, right_ctx_dfas)
+    }
+
+    /// Same as `simulate`, but starts matching at `initial_state`, which should be the initial
+    /// state of a rule set. Semantic actions are not run, so the matches are all in the same rule
+    /// set.
+    pub fn simulate_from<'input>(
+        &self,
+        initial_state: StateIdx,
+        input: &'input str,
+        left_ctxs: &LeftCtxs,
         right_ctx_dfas: &RightCtxDFAs<StateIdx>,
     ) -> (Matches<'input, A>, Option<ErrorLoc>) {
         let mut values: Matches<'input, A> = vec![];
 
         // Current state
-        let mut state = StateIdx(0);
+        let mut state = initial_state;
 
         // See comments for the same variable in NFA simulation
         let mut last_match: Option<(usize, A, usize)> = None;
@@ -50,7 +67,7 @@ impl<A: Copy> DFA<StateIdx, A> {
                                 ));
 
                                 // Restart state machine
-                                state = StateIdx(0);
+                                state = initial_state;
                             }
                         }
                     }
@@ -58,7 +75,18 @@ impl<A: Copy> DFA<StateIdx, A> {
                         state = next_state;
 
                         // Check for accepting state
-                        for AcceptingState { value, right_ctx } in &self.states[state.0].accepting {
+                        for AcceptingState {
+                            value,
+                            anchor,
+                            left_ctx,
+                            right_ctx,
+                        } in &self.states[state.0].accepting
+                        {
+                            if !check_anchor(*anchor, input, match_start)
+                                || !check_left_ctx(left_ctxs, left_ctx, input, match_start)
+                            {
+                                continue;
+                            }
                             match right_ctx {
                                 None => {
                                     last_match =
@@ -66,8 +94,7 @@ impl<A: Copy> DFA<StateIdx, A> {
                                     break;
                                 }
                                 Some(right_ctx_idx) => {
-                                    let right_ctx_dfa = right_ctx_dfas.get(right_ctx_idx);
-                                    if simulate_right_ctx(right_ctx_dfa, char_indices.clone()) {
+                                    if right_ctx_dfas.matches(right_ctx_idx, char_indices.clone()) {
                                         last_match =
                                             Some((match_start, *value, char_idx + char.len_utf8()));
                                         break;
@@ -83,15 +110,25 @@ impl<A: Copy> DFA<StateIdx, A> {
             if let Some(next) = next_end_of_input(self, state) {
                 // Check for accepting state
                 state = next;
-                for AcceptingState { value, right_ctx } in &self.states[state.0].accepting {
+                for AcceptingState {
+                    value,
+                    anchor,
+                    left_ctx,
+                    right_ctx,
+                } in &self.states[state.0].accepting
+                {
+                    if !check_anchor(*anchor, input, match_start)
+                        || !check_left_ctx(left_ctxs, left_ctx, input, match_start)
+                    {
+                        continue;
+                    }
                     match right_ctx {
                         None => {
                             values.push((&input[match_start..], *value));
                             break 'outer;
                         }
                         Some(right_ctx_idx) => {
-                            let right_ctx_dfa = right_ctx_dfas.get(right_ctx_idx);
-                            if simulate_right_ctx(right_ctx_dfa, char_indices.clone()) {
+                            if right_ctx_dfas.matches(right_ctx_idx, char_indices.clone()) {
                                 values.push((&input[match_start..], *value));
                                 break 'outer;
                             }
@@ -113,7 +150,7 @@ impl<A: Copy> DFA<StateIdx, A> {
                         char_indices = input[match_start..].char_indices();
 
                         // Restart state machine
-                        state = StateIdx(0);
+                        state = initial_state;
                     }
                 }
                 None => {
@@ -127,6 +164,111 @@ impl<A: Copy> DFA<StateIdx, A> {
     }
 }
 
+/// A match found by `DFA::longest_match`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LongestMatch<A> {
+    /// Value of the matched rule
+    pub value: A,
+
+    /// Byte index of the end of the match
+    pub end: usize,
+
+    /// Whether the match ends with an end-of-input (`$`) transition
+    pub end_of_input: bool,
+}
+
+impl<A: Copy> DFA<StateIdx, A> {
+    /// Find the longest match of the rule set with initial state `initial_state` in `input`,
+    /// starting at byte index `start`. Anchors and left contexts are checked against the
+    /// character before `start`, as in the generated code.
+    pub fn longest_match(
+        &self,
+        initial_state: StateIdx,
+        input: &str,
+        start: usize,
+        left_ctxs: &LeftCtxs,
+        right_ctx_dfas: &RightCtxDFAs<StateIdx>,
+    ) -> Option<LongestMatch<A>> {
+        let mut state = initial_state;
+        let mut last_match: Option<LongestMatch<A>> = None;
+        let mut char_indices = input[start..].char_indices();
+
+        while let Some((char_idx, char)) = char_indices.next() {
+            state = match next(self, state, char) {
+                None => return last_match,
+                Some(next_state) => next_state,
+            };
+
+            if let Some(value) = self.accepting_value(
+                state,
+                input,
+                start,
+                left_ctxs,
+                right_ctx_dfas,
+                char_indices.clone(),
+            ) {
+                last_match = Some(LongestMatch {
+                    value,
+                    end: start + char_idx + char.len_utf8(),
+                    end_of_input: false,
+                });
+            }
+        }
+
+        if let Some(next_state) = next_end_of_input(self, state) {
+            if let Some(value) = self.accepting_value(
+                next_state,
+                input,
+                start,
+                left_ctxs,
+                right_ctx_dfas,
+                char_indices,
+            ) {
+                return Some(LongestMatch {
+                    value,
+                    end: input.len(),
+                    end_of_input: true,
+                });
+            }
+        }
+
+        last_match
+    }
+
+    /// Value of the first accepting state of `state` whose anchor and contexts hold
+    fn accepting_value(
+        &self,
+        state: StateIdx,
+        input: &str,
+        match_start: usize,
+        left_ctxs: &LeftCtxs,
+        right_ctx_dfas: &RightCtxDFAs<StateIdx>,
+        char_indices: std::str::CharIndices,
+    ) -> Option<A> {
+        self.states[state.0]
+            .accepting
+            .iter()
+            .find(
+                |AcceptingState {
+                     value: _,
+                     anchor,
+                     left_ctx,
+                     right_ctx,
+                 }| {
+                    check_anchor(*anchor, input, match_start)
+                        && check_left_ctx(left_ctxs, left_ctx, input, match_start)
+                        && match right_ctx {
+                            None => true,
+                            Some(right_ctx_idx) => {
+                                right_ctx_dfas.matches(right_ctx_idx, char_indices.clone())
+                            }
+                        }
+                },
+            )
+            .map(|accepting| accepting.value)
+    }
+}
+
 fn next<A>(dfa: &DFA<StateIdx, A>, state: StateIdx, char: char) -> Option<StateIdx> {
     let state = &dfa.states[state.0];
 
@@ -145,7 +287,7 @@ fn next<A>(dfa: &DFA<StateIdx, A>, state: StateIdx, char: char) -> Option<StateI
         return Some(next);
     }
 
-    return None;
+    None
 }
 
 fn next_end_of_input<A>(dfa: &DFA<StateIdx, A>, state: StateIdx) -> Option<StateIdx> {
@@ -154,17 +296,14 @@ fn next_end_of_input<A>(dfa: &DFA<StateIdx, A>, state: StateIdx) -> Option<State
 
 // Similar to `simulate`, but does not keep track of the last match as we don't need "longest
 // match" semantics and backtracking
-pub fn simulate_right_ctx(
-    dfa: &DFA<StateIdx, ()>,
-    mut char_indices: std::str::CharIndices,
-) -> bool {
+pub fn simulate_right_ctx(dfa: &DFA<StateIdx, ()>, char_indices: std::str::CharIndices) -> bool {
     let mut state = dfa.initial_state();
 
     if dfa.is_accepting_state(state) {
         return true;
     }
 
-    while let Some((_, char)) = char_indices.next() {
+    for (_, char) in char_indices {
         match next(dfa, state, char) {
             None => {
                 // Stuck
@@ -1,6 +1,7 @@
 use std::collections::{BTreeSet, HashSet};
 use std::fmt::{self, Display, Formatter};
 
+#[allow(dead_code)]
 pub struct BTreeSetDisplay<'a, A: Display>(pub &'a BTreeSet<A>);
 
 pub struct HashSetDisplay<'a, A: Display, S>(pub &'a HashSet<A, S>);
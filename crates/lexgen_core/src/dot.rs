@@ -0,0 +1,194 @@
+//! Graphviz DOT output for NFAs and DFAs
+//!
+//! Automata add their states and transitions to a `DotGraph`, with a prefix for node names, so
+//! that a lexer's DFA and its right context DFAs can be rendered in one graph. Transitions between
+//! the same states are merged into one edge, with the labels of the transitions separated by
+//! commas.
+
+use crate::ast::Anchor;
+use crate::collections::Map;
+use crate::nfa::AcceptingState;
+
+use std::convert::TryFrom;
+use std::fmt::Write;
+
+pub struct DotGraph {
+    /// Node and cluster declarations
+    body: String,
+
+    /// Current nesting of clusters, for indentation
+    depth: usize,
+
+    /// Edges, with labels of the transitions they represent. Edges are written after the nodes,
+    /// so that edges between clusters don't move nodes to other clusters.
+    edges: Vec<Edge>,
+
+    /// Maps source and target nodes to their edge in `edges`
+    edge_indices: Map<(String, String), usize>,
+
+    /// Counter for generating node names
+    fresh: usize,
+}
+
+struct Edge {
+    from: String,
+    to: String,
+    labels: Vec<String>,
+}
+
+impl DotGraph {
+    pub fn new() -> DotGraph {
+        DotGraph {
+            body: String::new(),
+            depth: 1,
+            edges: vec![],
+            edge_indices: Default::default(),
+            fresh: 0,
+        }
+    }
+
+    /// Start a cluster. Nodes added until the matching `end_cluster` are drawn in a box with the
+    /// given label.
+    pub fn begin_cluster(&mut self, name: &str, label: &str) {
+        self.line(&format!("subgraph cluster_{} {{", name));
+        self.depth += 1;
+        self.line(&format!("label=\"{}\";", escape(label)));
+    }
+
+    pub fn end_cluster(&mut self) {
+        self.depth -= 1;
+        self.line("}");
+    }
+
+    /// Add a state. Accepting states are drawn with double circles.
+    pub fn state(&mut self, node: &str, label: &str, accepting: bool) {
+        let shape = if accepting { "doublecircle" } else { "circle" };
+        self.line(&format!(
+            "{} [label=\"{}\", shape={}];",
+            node,
+            escape(label),
+            shape
+        ));
+    }
+
+    /// Mark `node` as an initial state, with an arrow from an invisible node. `label` is the name
+    /// of the rule set that starts at the state, if any.
+    pub fn initial(&mut self, node: &str, label: &str) {
+        let start = format!("{}_start", node);
+        self.line(&format!("{} [label=\"\", shape=none, width=0];", start));
+        self.edge(&start, node, label);
+    }
+
+    /// Add a fresh accepting node with the given label, used for transitions of simplified DFAs
+    /// that accept without moving to a state
+    pub fn accept_node(&mut self, prefix: &str, label: &str) -> String {
+        let node = format!("{}accept{}", prefix, self.fresh);
+        self.fresh += 1;
+        self.line(&format!(
+            "{} [label=\"{}\", shape=doubleoctagon];",
+            node,
+            escape(label)
+        ));
+        node
+    }
+
+    pub fn edge(&mut self, from: &str, to: &str, label: &str) {
+        match self.edge_indices.get(&(from.to_owned(), to.to_owned())) {
+            Some(edge_idx) => self.edges[*edge_idx].labels.push(label.to_owned()),
+            None => {
+                self.edge_indices
+                    .insert((from.to_owned(), to.to_owned()), self.edges.len());
+                self.edges.push(Edge {
+                    from: from.to_owned(),
+                    to: to.to_owned(),
+                    labels: vec![label.to_owned()],
+                });
+            }
+        }
+    }
+
+    pub fn finish(self) -> String {
+        let mut out = String::new();
+        out.push_str("digraph {\n");
+        out.push_str("    rankdir=LR;\n");
+        out.push_str(&self.body);
+        for Edge { from, to, labels } in &self.edges {
+            writeln!(
+                out,
+                "    {} -> {} [label=\"{}\"];",
+                from,
+                to,
+                escape(&labels.join(", "))
+            )
+            .unwrap();
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn line(&mut self, line: &str) {
+        for _ in 0..self.depth {
+            self.body.push_str("    ");
+        }
+        self.body.push_str(line);
+        self.body.push('\n');
+    }
+}
+
+/// Label of a character transition
+pub fn char_label(char: char) -> String {
+    format!("{:?}", char)
+}
+
+/// Label of a range transition
+pub fn range_label(start: u32, end: u32) -> String {
+    format!(
+        "{:?}-{:?}",
+        char::try_from(start).unwrap(),
+        char::try_from(end).unwrap()
+    )
+}
+
+/// Label of an accepting state: the value, followed by the anchor and contexts of the rule
+pub fn accepting_label<A>(
+    accepting: &AcceptingState<A>,
+    value_label: &dyn Fn(&A) -> String,
+) -> String {
+    let AcceptingState {
+        value,
+        anchor,
+        left_ctx,
+        right_ctx,
+    } = accepting;
+
+    let mut label = value_label(value);
+
+    match anchor {
+        Some(Anchor::LineStart) => label.push_str(" (^)"),
+        Some(Anchor::InputStart) => label.push_str(" (^^)"),
+        None => {}
+    }
+
+    if let Some(left_ctx) = left_ctx {
+        write!(label, " (left ctx {})", left_ctx.as_usize()).unwrap();
+    }
+
+    if let Some(right_ctx) = right_ctx {
+        write!(label, " (right ctx {})", right_ctx.as_usize()).unwrap();
+    }
+
+    label
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for char in s.chars() {
+        match char {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(char),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,319 @@
+//! Converting flex and ocamllex lexers to lexgen
+//!
+//! [`flex`] and [`ocamllex`] convert a flex (`.l`) or ocamllex (`.mll`) specification to a lexer
+//! definition in `lexer!` syntax. Named definitions become let bindings, and start conditions
+//! and entry points become rule sets. Semantic actions are not translated: rules are generated
+//! with a `todo!()` and the original action in a comment, except actions that only skip the
+//! input or switch to another rule set.
+//!
+//! Features without a lexgen equivalent (e.g. `REJECT` and `yymore` in flex actions, `as`
+//! bindings in ocamllex patterns) are reported as [`Warning`]s.
+//!
+//! ```
+//! let spec = r#"
+//! DIGIT [0-9]
+//! %x COMMENT
+//! %%
+//! {DIGIT}+        { return INT; }
+//! "/*"            BEGIN(COMMENT);
+//! [ \t\n]+        ;
+//! <COMMENT>"*/"   BEGIN(INITIAL);
+//! <COMMENT>.|\n   ;
+//! "#;
+//!
+//! let converted = lexgen_core::import::flex(spec).unwrap();
+//! assert!(converted.warnings.is_empty());
+//! assert!(converted.definition.contains("rule Comment {"));
+//! ```
+
+mod flex;
+mod ocamllex;
+
+use crate::ast::{Regex, RegexCtx};
+use crate::build::Error;
+
+use std::fmt;
+use std::fmt::Write;
+
+/// A lexer definition converted from another lexer generator's format
+#[derive(Debug, Clone)]
+pub struct Converted {
+    /// The lexer definition in `lexer!` syntax, for the lexer `Lexer` with token type `Token`
+    pub definition: String,
+
+    /// Parts of the input that could not be converted
+    pub warnings: Vec<Warning>,
+}
+
+/// A part of the input that could not be converted, or was converted approximately
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    /// 1-based line number of the part in the input
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.line, self.message)
+    }
+}
+
+/// Convert a flex specification. Start condition `INITIAL` becomes the `Init` rule set, other
+/// start conditions become rule sets with their names in camel case (`IN_STRING` becomes
+/// `InString`).
+pub fn flex(spec: &str) -> Result<Converted, Error> {
+    flex::convert(spec)
+}
+
+/// Convert an ocamllex specification. The first entry point becomes the `Init` rule set, other
+/// entry points become rule sets with their names in camel case (`read_string` becomes
+/// `ReadString`).
+pub fn ocamllex(spec: &str) -> Result<Converted, Error> {
+    ocamllex::convert(spec)
+}
+
+/// A converted lexer, before printing
+#[derive(Debug, Default)]
+struct Spec {
+    bindings: Vec<(String, Regex)>,
+
+    /// Rule sets, `Init` first
+    rule_sets: Vec<RuleSet>,
+}
+
+#[derive(Debug)]
+struct RuleSet {
+    name: String,
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    lhs: RegexCtx,
+    shortest: bool,
+    action: Action,
+}
+
+#[derive(Debug, Clone)]
+enum Action {
+    /// Skip the matched input: `<regex>,`
+    Skip,
+
+    /// Switch to the rule set: `<regex> => |lexer| lexer.switch(LexerRule::<name>),`
+    Switch(String),
+
+    /// An action to be translated by hand, with the original code
+    Todo(String),
+}
+
+impl Spec {
+    fn print(&self, format: &str) -> String {
+        let mut out = String::new();
+
+        writeln!(
+            out,
+            "// Converted from {}. Actions marked with `todo!()` need to be translated by hand.",
+            format
+        )
+        .unwrap();
+        writeln!(out, "Lexer -> Token;").unwrap();
+
+        if !self.bindings.is_empty() {
+            writeln!(out).unwrap();
+        }
+
+        for (name, re) in &self.bindings {
+            writeln!(out, "let {} = {};", name, re).unwrap();
+        }
+
+        for rule_set in &self.rule_sets {
+            writeln!(out).unwrap();
+            writeln!(out, "rule {} {{", rule_set.name).unwrap();
+            for rule in &rule_set.rules {
+                rule.print(&mut out);
+            }
+            writeln!(out, "}}").unwrap();
+        }
+
+        out
+    }
+}
+
+impl Rule {
+    fn print(&self, out: &mut String) {
+        if self.shortest {
+            writeln!(out, "    #[shortest]").unwrap();
+        }
+
+        match &self.action {
+            Action::Skip => writeln!(out, "    {},", self.lhs).unwrap(),
+            Action::Switch(rule_set) => writeln!(
+                out,
+                "    {} => |lexer| lexer.switch(LexerRule::{}),",
+                self.lhs, rule_set
+            )
+            .unwrap(),
+            Action::Todo(code) => {
+                writeln!(out, "    {} => |lexer| {{", self.lhs).unwrap();
+                for line in dedent(code) {
+                    if line.is_empty() {
+                        writeln!(out, "        //").unwrap();
+                    } else {
+                        writeln!(out, "        // {}", line).unwrap();
+                    }
+                }
+                writeln!(out, "        todo!()").unwrap();
+                writeln!(out, "    }},").unwrap();
+            }
+        }
+    }
+}
+
+/// Lines of `code` with the common indentation of the lines after the first one removed
+fn dedent(code: &str) -> Vec<&str> {
+    let mut lines = code.trim().lines();
+    let first = lines.next().unwrap_or("");
+    let rest: Vec<&str> = lines.map(str::trim_end).collect();
+
+    let indent = rest
+        .iter()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    std::iter::once(first)
+        .chain(
+            rest.into_iter()
+                .map(|line| line.get(indent..).unwrap_or("")),
+        )
+        .collect()
+}
+
+/// Convert a name in the input to a Rust identifier in camel case, for rule set names
+fn camel_case(name: &str) -> String {
+    let mut out = String::new();
+    for word in name.split(['_', '-', '\'']) {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            out.extend(first.to_uppercase());
+            if word.chars().all(|c| !c.is_lowercase()) {
+                out.extend(chars.flat_map(char::to_lowercase));
+            } else {
+                out.extend(chars);
+            }
+        }
+    }
+    ident(out)
+}
+
+/// Convert a name in the input to a Rust identifier, for binding names
+fn binding_name(name: &str) -> String {
+    ident(name.replace(['-', '\''], "_"))
+}
+
+/// Add a `_` to `name` if it's a Rust keyword
+fn ident(mut name: String) -> String {
+    if syn::parse_str::<syn::Ident>(&name).is_err() {
+        name.push('_');
+    }
+    name
+}
+
+/// A position in the input, for parsing
+struct Cursor<'a> {
+    input: &'a str,
+
+    /// Byte index of the rest of the input
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(input: &'a str) -> Cursor<'a> {
+        Cursor { input, pos: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.rest().chars().nth(n)
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos == self.input.len()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let char = self.peek()?;
+        self.pos += char.len_utf8();
+        Some(char)
+    }
+
+    /// Skip `str` if the rest of the input starts with it
+    fn eat(&mut self, str: &str) -> bool {
+        if self.rest().starts_with(str) {
+            self.pos += str.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, str: &str) -> Result<(), Error> {
+        if self.eat(str) {
+            Ok(())
+        } else {
+            Err(self.error(format!("Expected `{}`", str)))
+        }
+    }
+
+    /// Skip characters while `f` holds, return the skipped part
+    fn take_while<F: Fn(char) -> bool>(&mut self, f: F) -> &'a str {
+        let start = self.pos;
+        while let Some(char) = self.peek() {
+            if !f(char) {
+                break;
+            }
+            self.pos += char.len_utf8();
+        }
+        &self.input[start..self.pos]
+    }
+
+    /// Skip the rest of the line, including the newline. Returns the skipped part without the
+    /// newline.
+    fn line(&mut self) -> &'a str {
+        let line = self.take_while(|c| c != '\n');
+        self.eat("\n");
+        line
+    }
+
+    /// 1-based line number of the current position
+    fn line_number(&self) -> usize {
+        self.line_number_at(self.pos)
+    }
+
+    fn line_number_at(&self, pos: usize) -> usize {
+        self.input[..pos].matches('\n').count() + 1
+    }
+
+    fn error(&self, message: String) -> Error {
+        self.error_at(self.pos, message)
+    }
+
+    fn error_at(&self, pos: usize, message: String) -> Error {
+        let line_start = self.input[..pos].rfind('\n').map_or(0, |idx| idx + 1);
+        Error::Syntax {
+            path: None,
+            line: self.line_number_at(pos),
+            col: self.input[line_start..pos].chars().count() + 1,
+            message,
+        }
+    }
+}
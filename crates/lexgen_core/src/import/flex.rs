@@ -0,0 +1,965 @@
+//! Converting flex specifications
+
+use super::{binding_name, camel_case, Action, Converted, Cursor, Rule, RuleSet, Spec, Warning};
+use crate::ast::{Anchor, CharOrRange, CharSet, Regex, RegexCtx, RightCtx, Var};
+use crate::build::Error;
+use crate::raw_regex::{concat, posix_class, repeat};
+
+/// Functions and macros in actions that have no lexgen equivalent
+const UNSUPPORTED_IN_ACTIONS: [&str; 6] = [
+    "REJECT",
+    "yymore",
+    "yyless",
+    "unput",
+    "yy_push_state",
+    "yy_pop_state",
+];
+
+pub(super) fn convert(spec: &str) -> Result<Converted, Error> {
+    let mut parser = Parser {
+        cursor: Cursor::new(spec),
+        warnings: vec![],
+        definitions: vec![],
+        start_conditions: vec![],
+        rules: vec![],
+    };
+
+    parser.definitions_section()?;
+    parser.rules_section()?;
+    parser.user_code_section();
+
+    let Parser {
+        cursor: _,
+        warnings,
+        definitions,
+        start_conditions,
+        rules,
+    } = parser;
+
+    let mut rule_sets: Vec<RuleSet> = std::iter::once("INITIAL")
+        .chain(start_conditions.iter().map(|sc| sc.name.as_str()))
+        .map(|name| RuleSet {
+            name: rule_set_name(name),
+            rules: vec![],
+        })
+        .collect();
+
+    // Actions of `|` rules are the same as the next rule's action
+    let mut next_action: Option<Action> = None;
+    let mut rules_with_actions = Vec::with_capacity(rules.len());
+    for rule in rules.into_iter().rev() {
+        let action = match rule.action {
+            Some(action) => action,
+            None => next_action.clone().ok_or_else(|| Error::Syntax {
+                path: None,
+                line: rule.line,
+                col: 1,
+                message: "The last rule's action cannot be `|`".to_owned(),
+            })?,
+        };
+        next_action = Some(action.clone());
+        rules_with_actions.push((rule.conditions, rule.lhs, action));
+    }
+    rules_with_actions.reverse();
+
+    for (conditions, lhs, action) in rules_with_actions {
+        for (i, rule_set) in rule_sets.iter_mut().enumerate() {
+            let active = match &conditions {
+                Conditions::Default => i == 0 || !start_conditions[i - 1].exclusive,
+                Conditions::All => true,
+                Conditions::Named(names) => names.iter().any(|name| {
+                    if i == 0 {
+                        name == "INITIAL"
+                    } else {
+                        *name == start_conditions[i - 1].name
+                    }
+                }),
+            };
+            if active {
+                rule_set.rules.push(Rule {
+                    lhs: lhs.clone(),
+                    shortest: false,
+                    action: action.clone(),
+                });
+            }
+        }
+    }
+
+    let spec = Spec {
+        bindings: definitions
+            .into_iter()
+            .map(|(name, re)| (binding_name(&name), re))
+            .collect(),
+        rule_sets,
+    };
+
+    Ok(Converted {
+        definition: spec.print("flex"),
+        warnings,
+    })
+}
+
+/// Name of the rule set for a start condition
+fn rule_set_name(start_condition: &str) -> String {
+    if start_condition == "INITIAL" || start_condition == "0" {
+        "Init".to_owned()
+    } else {
+        camel_case(start_condition)
+    }
+}
+
+struct Parser<'a> {
+    cursor: Cursor<'a>,
+    warnings: Vec<Warning>,
+
+    /// Named definitions, with the names in the input
+    definitions: Vec<(String, Regex)>,
+
+    start_conditions: Vec<StartCondition>,
+    rules: Vec<FlexRule>,
+}
+
+struct StartCondition {
+    name: String,
+
+    /// Declared with `%x`. Rules without start conditions are not active in exclusive start
+    /// conditions.
+    exclusive: bool,
+}
+
+struct FlexRule {
+    conditions: Conditions,
+    lhs: RegexCtx,
+
+    /// `None` for `|`, which means the next rule's action
+    action: Option<Action>,
+
+    /// Line number of the rule, for errors
+    line: usize,
+}
+
+#[derive(Clone)]
+enum Conditions {
+    /// No start conditions: active in `INITIAL` and inclusive start conditions
+    Default,
+
+    /// `<*>`
+    All,
+
+    /// `<SC1,SC2,...>`
+    Named(Vec<String>),
+}
+
+impl Conditions {
+    fn union(&self, other: &Conditions) -> Conditions {
+        match (self, other) {
+            (Conditions::Default, other) | (other, Conditions::Default) => other.clone(),
+            (Conditions::All, _) | (_, Conditions::All) => Conditions::All,
+            (Conditions::Named(names1), Conditions::Named(names2)) => {
+                Conditions::Named(names1.iter().chain(names2.iter()).cloned().collect())
+            }
+        }
+    }
+}
+
+fn is_name_char(char: char) -> bool {
+    char.is_ascii_alphanumeric() || char == '_' || char == '-'
+}
+
+/// Whether `char` ends a pattern
+fn is_pattern_end(char: Option<char>) -> bool {
+    matches!(char, None | Some(' ' | '\t' | '\r' | '\n'))
+}
+
+impl<'a> Parser<'a> {
+    fn warn(&mut self, line: usize, message: String) {
+        self.warnings.push(Warning { line, message });
+    }
+
+    //
+    // Definitions section
+    //
+
+    fn definitions_section(&mut self) -> Result<(), Error> {
+        loop {
+            if self.cursor.at_end() {
+                return Err(self.cursor.error("Expected `%%`".to_owned()));
+            }
+
+            let line = self.cursor.line_number();
+
+            if self.cursor.eat("%%") {
+                self.cursor.line();
+                return Ok(());
+            }
+
+            if self.cursor.eat("%{") {
+                self.code_block()?;
+                self.warn(line, "Code in `%{ ... %}` is not converted".to_owned());
+                continue;
+            }
+
+            if matches!(self.cursor.peek(), Some(' ' | '\t' | '\n' | '\r'))
+                || self.cursor.rest().starts_with("/*")
+            {
+                self.indented_line()?;
+                continue;
+            }
+
+            if self.cursor.eat("%") {
+                self.directive()?;
+                continue;
+            }
+
+            self.definition()?;
+        }
+    }
+
+    /// Skip a `%{ ... %}` block. Expects the `%{` to be consumed.
+    fn code_block(&mut self) -> Result<&'a str, Error> {
+        let start = self.cursor.pos;
+        match self.cursor.rest().find("%}") {
+            Some(len) => {
+                self.cursor.pos += len + 2;
+                let code = &self.cursor.input[start..self.cursor.pos - 2];
+                self.cursor.line();
+                Ok(code)
+            }
+            None => Err(self.cursor.error("Unterminated `%{`".to_owned())),
+        }
+    }
+
+    /// Skip an indented line. Warns about the line if it's code, rather than a comment or
+    /// whitespace.
+    fn indented_line(&mut self) -> Result<(), Error> {
+        let line = self.cursor.line_number();
+        self.cursor
+            .take_while(|c| c == ' ' || c == '\t' || c == '\r');
+        if self.cursor.rest().starts_with("/*") {
+            self.comment()?;
+        }
+        let rest = self.cursor.line();
+        if !rest.trim().is_empty() {
+            self.warn(line, format!("Code {:?} is not converted", rest.trim()));
+        }
+        Ok(())
+    }
+
+    /// Skip a `/* ... */` comment
+    fn comment(&mut self) -> Result<(), Error> {
+        self.cursor.expect("/*")?;
+        match self.cursor.rest().find("*/") {
+            Some(len) => {
+                self.cursor.pos += len + 2;
+                Ok(())
+            }
+            None => Err(self.cursor.error("Unterminated comment".to_owned())),
+        }
+    }
+
+    /// Parse a directive after the `%`
+    fn directive(&mut self) -> Result<(), Error> {
+        let line = self.cursor.line_number();
+        let directive = self.cursor.take_while(|c| c.is_ascii_alphabetic());
+
+        match directive {
+            "s" | "x" | "start" | "Start" => {
+                let exclusive = directive == "x";
+                let names = self.cursor.line();
+                for name in names.split_whitespace() {
+                    self.start_conditions.push(StartCondition {
+                        name: name.to_owned(),
+                        exclusive,
+                    });
+                }
+            }
+
+            "option" => {
+                let options = self.cursor.line();
+                for option in options.split_whitespace() {
+                    if matches!(
+                        option,
+                        "caseless" | "case-insensitive" | "lex-compat" | "posix-compat"
+                    ) {
+                        self.warn(line, format!("Option `{}` is not supported", option));
+                    }
+                }
+            }
+
+            "top" => {
+                self.cursor.take_while(|c| c == ' ' || c == '\t');
+                self.cursor.expect("{")?;
+                self.c_block()?;
+                self.cursor.line();
+                self.warn(line, "Code in `%top{ ... }` is not converted".to_owned());
+            }
+
+            // Table sizes of AT&T lex, and output options
+            "a" | "e" | "k" | "n" | "o" | "p" | "array" | "pointer" => {
+                self.cursor.line();
+            }
+
+            _ => {
+                self.cursor.line();
+                self.warn(line, format!("Directive `%{}` is ignored", directive));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parse a named definition: `<name> <regex>`
+    fn definition(&mut self) -> Result<(), Error> {
+        let name = self.cursor.take_while(is_name_char);
+        if name.is_empty() {
+            return Err(self
+                .cursor
+                .error("Expected a definition, a directive, or `%%`".to_owned()));
+        }
+
+        if self.cursor.take_while(|c| c == ' ' || c == '\t').is_empty() {
+            return Err(self.cursor.error(format!(
+                "Expected whitespace after definition name {:?}",
+                name
+            )));
+        }
+
+        let re = self.alternation()?;
+        self.end_of_line()?;
+
+        self.definitions.push((name.to_owned(), re));
+        Ok(())
+    }
+
+    /// Skip whitespace and comments until the end of the line
+    fn end_of_line(&mut self) -> Result<(), Error> {
+        self.cursor
+            .take_while(|c| c == ' ' || c == '\t' || c == '\r');
+        if self.cursor.rest().starts_with("/*") {
+            self.comment()?;
+            self.cursor
+                .take_while(|c| c == ' ' || c == '\t' || c == '\r');
+        }
+        match self.cursor.peek() {
+            None => Ok(()),
+            Some('\n') => {
+                self.cursor.next();
+                Ok(())
+            }
+            Some(char) => Err(self.cursor.error(format!("Unexpected `{}`", char))),
+        }
+    }
+
+    //
+    // Rules section
+    //
+
+    fn rules_section(&mut self) -> Result<(), Error> {
+        // Start conditions of enclosing `<SC>{ ... }` scopes
+        let mut scopes: Vec<Conditions> = vec![];
+
+        while !self.cursor.at_end() {
+            let line = self.cursor.line_number();
+
+            if self.cursor.eat("%%") {
+                self.cursor.line();
+                break;
+            }
+
+            let indent = self
+                .cursor
+                .take_while(|c| c == ' ' || c == '\t' || c == '\r');
+
+            if matches!(self.cursor.peek(), None | Some('\n')) {
+                self.cursor.line();
+                continue;
+            }
+
+            if self.cursor.rest().starts_with("/*") {
+                self.comment()?;
+                self.end_of_line()?;
+                continue;
+            }
+
+            if self.cursor.eat("%{") {
+                self.code_block()?;
+                self.warn(line, "Code in `%{ ... %}` is not converted".to_owned());
+                continue;
+            }
+
+            if scopes.is_empty() && !indent.is_empty() {
+                let code = self.cursor.line();
+                self.warn(line, format!("Code {:?} is not converted", code.trim()));
+                continue;
+            }
+
+            if !scopes.is_empty() && self.cursor.eat("}") {
+                scopes.pop();
+                self.end_of_line()?;
+                continue;
+            }
+
+            let mut conditions = match scopes.last() {
+                Some(conditions) => conditions.clone(),
+                None => Conditions::Default,
+            };
+
+            if self.cursor.peek() == Some('<') && !self.cursor.rest().starts_with("<<EOF>>") {
+                conditions = conditions.union(&self.start_conditions()?);
+
+                // Start condition scope: `<SC>{`, with the `{` at the end of the line
+                if self.cursor.peek() == Some('{')
+                    && self.cursor.rest()[1..]
+                        .split('\n')
+                        .next()
+                        .unwrap()
+                        .trim()
+                        .is_empty()
+                {
+                    self.cursor.line();
+                    scopes.push(conditions);
+                    continue;
+                }
+            }
+
+            let lhs = if self.cursor.eat("<<EOF>>") {
+                RegexCtx {
+                    anchor: None,
+                    left_ctx: None,
+                    re: Regex::EndOfInput,
+                    right_ctx: None,
+                }
+            } else {
+                self.pattern()?
+            };
+
+            if !is_pattern_end(self.cursor.peek()) {
+                let char = self.cursor.peek().unwrap();
+                return Err(self
+                    .cursor
+                    .error(format!("Unexpected `{}` in pattern", char)));
+            }
+
+            let action = self.action()?;
+
+            self.rules.push(FlexRule {
+                conditions,
+                lhs,
+                action,
+                line,
+            });
+        }
+
+        if !scopes.is_empty() {
+            return Err(self
+                .cursor
+                .error("Unterminated start condition scope".to_owned()));
+        }
+
+        Ok(())
+    }
+
+    /// Parse start conditions of a rule: `<SC1,SC2,...>` or `<*>`
+    fn start_conditions(&mut self) -> Result<Conditions, Error> {
+        self.cursor.expect("<")?;
+
+        if self.cursor.eat("*>") {
+            return Ok(Conditions::All);
+        }
+
+        let mut names = vec![];
+        loop {
+            let pos = self.cursor.pos;
+            let name = self.cursor.take_while(is_name_char);
+            if name.is_empty() {
+                return Err(self.cursor.error("Expected a start condition".to_owned()));
+            }
+            if name != "INITIAL" && !self.start_conditions.iter().any(|sc| sc.name == name) {
+                return Err(self
+                    .cursor
+                    .error_at(pos, format!("Undeclared start condition {:?}", name)));
+            }
+            names.push(name.to_owned());
+
+            if self.cursor.eat(">") {
+                return Ok(Conditions::Named(names));
+            }
+            self.cursor.expect(",")?;
+        }
+    }
+
+    /// Parse the action of a rule. Returns `None` for `|`.
+    fn action(&mut self) -> Result<Option<Action>, Error> {
+        self.cursor
+            .take_while(|c| c == ' ' || c == '\t' || c == '\r');
+
+        let line = self.cursor.line_number();
+
+        let code = if self.cursor.eat("%{") {
+            self.code_block()?
+        } else if self.cursor.peek() == Some('{') {
+            self.cursor.next();
+            let start = self.cursor.pos - 1;
+            self.c_block()?;
+            let code = &self.cursor.input[start..self.cursor.pos];
+            self.end_of_line()?;
+            code
+        } else {
+            let code = self.cursor.line().trim();
+            if code == "|" {
+                return Ok(None);
+            }
+            code
+        };
+
+        Ok(Some(self.classify_action(code, line)))
+    }
+
+    /// Skip a C block, until the `}` matching an already consumed `{`
+    fn c_block(&mut self) -> Result<(), Error> {
+        let start = self.cursor.pos;
+        let mut depth = 1;
+
+        while let Some(char) = self.cursor.next() {
+            match char {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                '"' | '\'' => {
+                    while let Some(char_) = self.cursor.next() {
+                        if char_ == '\\' {
+                            self.cursor.next();
+                        } else if char_ == char || char_ == '\n' {
+                            break;
+                        }
+                    }
+                }
+                '/' if self.cursor.peek() == Some('*') => {
+                    self.cursor.pos -= 1;
+                    self.comment()?;
+                }
+                '/' if self.cursor.peek() == Some('/') => {
+                    self.cursor.take_while(|c| c != '\n');
+                }
+                _ => {}
+            }
+        }
+
+        Err(self
+            .cursor
+            .error_at(start - 1, "Unterminated `{` in action".to_owned()))
+    }
+
+    fn classify_action(&mut self, code: &str, line: usize) -> Action {
+        let body = match code
+            .strip_prefix('{')
+            .and_then(|code| code.strip_suffix('}'))
+        {
+            Some(body) => body.trim(),
+            None => code,
+        };
+
+        let body = strip_comments(body);
+        let body = body.trim();
+
+        if body.is_empty() || body == ";" {
+            return Action::Skip;
+        }
+
+        for name in UNSUPPORTED_IN_ACTIONS {
+            if contains_word(body, name) {
+                self.warn(
+                    line,
+                    format!("`{}` in action has no lexgen equivalent", name),
+                );
+            }
+        }
+
+        if let Some(start_condition) = begin_only(body) {
+            return Action::Switch(rule_set_name(start_condition));
+        }
+
+        Action::Todo(code.to_owned())
+    }
+
+    fn user_code_section(&mut self) {
+        let line = self.cursor.line_number();
+        if !self.cursor.rest().trim().is_empty() {
+            self.warn(line, "User code section is not converted".to_owned());
+        }
+    }
+
+    //
+    // Patterns
+    //
+
+    /// Parse a pattern with an optional `^` anchor, and `/` or `$` trailing context
+    fn pattern(&mut self) -> Result<RegexCtx, Error> {
+        let anchor = if self.cursor.eat("^") {
+            Some(Anchor::LineStart)
+        } else {
+            None
+        };
+
+        let re = self.alternation()?;
+
+        let right_ctx = if self.cursor.eat("/") {
+            Some(RightCtx {
+                re: self.alternation()?,
+                negated: false,
+            })
+        } else if self.cursor.peek() == Some('$') && is_pattern_end(self.cursor.peek_nth(1)) {
+            self.cursor.next();
+            Some(RightCtx {
+                re: Regex::Char('\n'),
+                negated: false,
+            })
+        } else {
+            None
+        };
+
+        Ok(RegexCtx {
+            anchor,
+            left_ctx: None,
+            re,
+            right_ctx,
+        })
+    }
+
+    fn alternation(&mut self) -> Result<Regex, Error> {
+        let mut re = self.concatenation()?;
+        while self.cursor.eat("|") {
+            re = Regex::Or(Box::new(re), Box::new(self.concatenation()?));
+        }
+        Ok(re)
+    }
+
+    fn concatenation(&mut self) -> Result<Regex, Error> {
+        let mut re: Option<Regex> = None;
+
+        loop {
+            let char = self.cursor.peek();
+            if is_pattern_end(char)
+                || matches!(char, Some('|' | ')' | '/'))
+                || (char == Some('$') && is_pattern_end(self.cursor.peek_nth(1)))
+            {
+                break;
+            }
+
+            let piece = self.repetition()?;
+            re = Some(match re {
+                None => piece,
+                Some(re) => concat(re, piece),
+            });
+        }
+
+        re.ok_or_else(|| self.cursor.error("Expected a pattern".to_owned()))
+    }
+
+    /// Parse an atom followed by repetition operators
+    fn repetition(&mut self) -> Result<Regex, Error> {
+        let mut re = self.atom()?;
+
+        loop {
+            match self.cursor.peek() {
+                Some('*') => re = Regex::ZeroOrMore(Box::new(re)),
+                Some('+') => re = Regex::OneOrMore(Box::new(re)),
+                Some('?') => re = Regex::ZeroOrOne(Box::new(re)),
+                Some('{') if self.cursor.peek_nth(1).is_some_and(|c| c.is_ascii_digit()) => {
+                    self.cursor.next();
+                    let (min, max) = self.bounds()?;
+                    re = repeat(re, min, max);
+                    continue;
+                }
+                _ => return Ok(re),
+            }
+            self.cursor.next();
+        }
+    }
+
+    /// Parse repetition bounds after the `{`: `n}`, `n,}`, or `n,m}`
+    fn bounds(&mut self) -> Result<(u32, Option<u32>), Error> {
+        let min = self.number()?;
+        let max = if self.cursor.eat(",") {
+            if self.cursor.peek() == Some('}') {
+                None
+            } else {
+                Some(self.number()?)
+            }
+        } else {
+            Some(min)
+        };
+        self.cursor.expect("}")?;
+
+        if let Some(max) = max {
+            if max < min {
+                return Err(self
+                    .cursor
+                    .error(format!("Invalid repetition bounds {{{},{}}}", min, max)));
+            }
+        }
+
+        Ok((min, max))
+    }
+
+    fn number(&mut self) -> Result<u32, Error> {
+        let pos = self.cursor.pos;
+        let digits = self.cursor.take_while(|c| c.is_ascii_digit());
+        digits
+            .parse()
+            .map_err(|_| self.cursor.error_at(pos, "Expected a number".to_owned()))
+    }
+
+    fn atom(&mut self) -> Result<Regex, Error> {
+        let pos = self.cursor.pos;
+        let char = self.cursor.next().unwrap();
+
+        match char {
+            '(' => {
+                if self.cursor.peek() == Some('?') {
+                    return Err(self
+                        .cursor
+                        .error_at(pos, "Pattern options `(?...)` are not supported".to_owned()));
+                }
+                let re = self.alternation()?;
+                self.cursor.expect(")")?;
+                Ok(re)
+            }
+
+            '[' => self.class(),
+
+            '"' => {
+                let mut str = String::new();
+                loop {
+                    match self.cursor.next() {
+                        None | Some('\n') => {
+                            return Err(self.cursor.error_at(pos, "Unterminated string".to_owned()))
+                        }
+                        Some('"') => return Ok(Regex::String(str)),
+                        Some('\\') => str.push(self.escape()?),
+                        Some(char) => str.push(char),
+                    }
+                }
+            }
+
+            '{' => {
+                let name = self.cursor.take_while(is_name_char);
+                self.cursor.expect("}")?;
+                if !self.definitions.iter().any(|(name_, _)| name_ == name) {
+                    return Err(self
+                        .cursor
+                        .error_at(pos, format!("Undefined definition {:?}", name)));
+                }
+                Ok(Regex::Var(Var(binding_name(name))))
+            }
+
+            '.' => Ok(Regex::Diff(
+                Box::new(Regex::Any),
+                Box::new(Regex::Char('\n')),
+            )),
+
+            '\\' => Ok(Regex::Char(self.escape()?)),
+
+            '*' | '+' | '?' => Err(self
+                .cursor
+                .error_at(pos, format!("Nothing to repeat with `{}`", char))),
+
+            char => Ok(Regex::Char(char)),
+        }
+    }
+
+    /// Parse a character class after the `[`
+    fn class(&mut self) -> Result<Regex, Error> {
+        let start = self.cursor.pos - 1;
+        let negated = self.cursor.eat("^");
+
+        let mut chars: Vec<CharOrRange> = vec![];
+        let mut builtins: Vec<Regex> = vec![];
+
+        let mut first = true;
+        loop {
+            match self.cursor.peek() {
+                None | Some('\n') => {
+                    return Err(self
+                        .cursor
+                        .error_at(start, "Unterminated character class".to_owned()))
+                }
+                Some(']') if !first => {
+                    self.cursor.next();
+                    break;
+                }
+                Some('[') if self.cursor.rest().starts_with("[:") => {
+                    let pos = self.cursor.pos;
+                    self.cursor.eat("[:");
+                    let name = self.cursor.take_while(|c| c != ':' && c != '\n');
+                    self.cursor.expect(":]")?;
+                    match posix_class(name) {
+                        Some(Regex::CharSet(CharSet(chars_))) => chars.extend(chars_),
+                        Some(re) => builtins.push(re),
+                        None => {
+                            return Err(self
+                                .cursor
+                                .error_at(pos, format!("Unsupported class [:{}:]", name)))
+                        }
+                    }
+                }
+                Some(_) => {
+                    let pos = self.cursor.pos;
+                    let char = self.class_char()?;
+                    if self.cursor.peek() == Some('-') && self.cursor.peek_nth(1) != Some(']') {
+                        self.cursor.next();
+                        let end = self.class_char()?;
+                        if end < char {
+                            return Err(self
+                                .cursor
+                                .error_at(pos, format!("Invalid range {:?}-{:?}", char, end)));
+                        }
+                        chars.push(CharOrRange::Range(char, end));
+                    } else {
+                        chars.push(CharOrRange::Char(char));
+                    }
+                }
+            }
+            first = false;
+        }
+
+        if self.cursor.rest().starts_with("{-}") || self.cursor.rest().starts_with("{+}") {
+            return Err(self
+                .cursor
+                .error("Character class operators `{-}` and `{+}` are not supported".to_owned()));
+        }
+
+        let mut regexes = builtins;
+        if !chars.is_empty() {
+            regexes.insert(0, Regex::CharSet(CharSet(chars)));
+        }
+
+        let re = regexes
+            .into_iter()
+            .reduce(|re1, re2| Regex::Or(Box::new(re1), Box::new(re2)))
+            .unwrap();
+
+        if negated {
+            Ok(Regex::Diff(Box::new(Regex::Any), Box::new(re)))
+        } else {
+            Ok(re)
+        }
+    }
+
+    fn class_char(&mut self) -> Result<char, Error> {
+        match self.cursor.next() {
+            Some('\\') => self.escape(),
+            Some('\n') | None => Err(self.cursor.error("Unterminated character class".to_owned())),
+            Some(char) => Ok(char),
+        }
+    }
+
+    /// Parse an escape sequence after the `\`
+    fn escape(&mut self) -> Result<char, Error> {
+        let pos = self.cursor.pos - 1;
+        let char = match self.cursor.next() {
+            None | Some('\n') => {
+                return Err(self
+                    .cursor
+                    .error_at(pos, "Unterminated escape sequence".to_owned()))
+            }
+            Some(char) => char,
+        };
+
+        let (digits, radix) = match char {
+            'n' => return Ok('\n'),
+            't' => return Ok('\t'),
+            'r' => return Ok('\r'),
+            'f' => return Ok('\x0c'),
+            'v' => return Ok('\x0b'),
+            'a' => return Ok('\x07'),
+            'b' => return Ok('\x08'),
+            '0'..='7' => {
+                self.cursor.pos -= 1;
+                let rest = self.cursor.rest();
+                let len = rest
+                    .chars()
+                    .take(3)
+                    .take_while(|c| ('0'..='7').contains(c))
+                    .count();
+                (&rest[..len], 8)
+            }
+            'x' => {
+                let rest = self.cursor.rest();
+                let len = rest
+                    .chars()
+                    .take(2)
+                    .take_while(|c| c.is_ascii_hexdigit())
+                    .count();
+                (&rest[..len], 16)
+            }
+            char => return Ok(char),
+        };
+
+        self.cursor.pos += digits.len();
+        u32::from_str_radix(digits, radix)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| {
+                self.cursor
+                    .error_at(pos, "Invalid escape sequence".to_owned())
+            })
+    }
+}
+
+/// Remove `/* ... */` and `// ...` comments in `code`
+fn strip_comments(code: &str) -> String {
+    let mut out = String::with_capacity(code.len());
+    let mut rest = code;
+    while let Some(start) = rest.find('/') {
+        let end = if rest[start..].starts_with("/*") {
+            rest[start..]
+                .find("*/")
+                .map_or(rest.len(), |len| start + len + 2)
+        } else if rest[start..].starts_with("//") {
+            rest[start..]
+                .find('\n')
+                .map_or(rest.len(), |len| start + len)
+        } else {
+            start + 1
+        };
+        if end == start + 1 {
+            out.push_str(&rest[..end]);
+        } else {
+            out.push_str(&rest[..start]);
+            out.push(' ');
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Whether `code` contains `word`, not as a part of a longer identifier
+fn contains_word(code: &str, word: &str) -> bool {
+    code.match_indices(word).any(|(idx, _)| {
+        let before = code[..idx].chars().next_back();
+        let after = code[idx + word.len()..].chars().next();
+        !before.is_some_and(|c| c.is_alphanumeric() || c == '_')
+            && !after.is_some_and(|c| c.is_alphanumeric() || c == '_')
+    })
+}
+
+/// If `code` is just `BEGIN(SC);` or `BEGIN SC;`, returns `SC`
+fn begin_only(code: &str) -> Option<&str> {
+    let code = code.trim().strip_suffix(';')?.trim();
+    let arg = code.strip_prefix("BEGIN")?.trim();
+    let name = match arg.strip_prefix('(') {
+        Some(arg) => arg.strip_suffix(')')?.trim(),
+        None => arg,
+    };
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        Some(name)
+    } else {
+        None
+    }
+}
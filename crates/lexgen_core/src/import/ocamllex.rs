@@ -0,0 +1,597 @@
+//! Converting ocamllex specifications
+
+use super::{binding_name, camel_case, Action, Converted, Cursor, Rule, RuleSet, Spec, Warning};
+use crate::ast::{CharOrRange, CharSet, Regex, RegexCtx, Var};
+use crate::build::Error;
+
+pub(super) fn convert(spec: &str) -> Result<Converted, Error> {
+    let mut parser = Parser {
+        cursor: Cursor::new(spec),
+        warnings: vec![],
+        bindings: vec![],
+        rule_sets: vec![],
+    };
+
+    parser.lexer()?;
+
+    let Parser {
+        cursor: _,
+        warnings,
+        bindings,
+        rule_sets,
+    } = parser;
+
+    let spec = Spec {
+        bindings: bindings
+            .into_iter()
+            .map(|(name, re)| (binding_name(&name), re))
+            .collect(),
+        rule_sets,
+    };
+
+    Ok(Converted {
+        definition: spec.print("ocamllex"),
+        warnings,
+    })
+}
+
+struct Parser<'a> {
+    cursor: Cursor<'a>,
+    warnings: Vec<Warning>,
+
+    /// Bindings, with the names in the input
+    bindings: Vec<(String, Regex)>,
+
+    /// Converted entry points
+    rule_sets: Vec<RuleSet>,
+}
+
+fn is_ident_char(char: char) -> bool {
+    char.is_ascii_alphanumeric() || char == '_' || char == '\''
+}
+
+impl<'a> Parser<'a> {
+    fn warn(&mut self, line: usize, message: String) {
+        self.warnings.push(Warning { line, message });
+    }
+
+    fn lexer(&mut self) -> Result<(), Error> {
+        self.skip_whitespace()?;
+        self.code_section("Header")?;
+
+        loop {
+            self.skip_whitespace()?;
+            if !self.keyword("let") {
+                break;
+            }
+            self.binding()?;
+        }
+
+        if self.keyword("refill") {
+            let line = self.cursor.line_number();
+            self.skip_whitespace()?;
+            self.cursor.expect("{")?;
+            self.ocaml_block()?;
+            self.warn(line, "Refill handler is not converted".to_owned());
+            self.skip_whitespace()?;
+        }
+
+        if !self.keyword("rule") {
+            return Err(self.cursor.error("Expected `let` or `rule`".to_owned()));
+        }
+
+        loop {
+            self.entry_point()?;
+            self.skip_whitespace()?;
+            if !self.keyword("and") {
+                break;
+            }
+        }
+
+        self.code_section("Trailer")?;
+        self.skip_whitespace()?;
+
+        if !self.cursor.at_end() {
+            return Err(self
+                .cursor
+                .error("Expected `and`, `|`, or end of input".to_owned()));
+        }
+
+        Ok(())
+    }
+
+    /// Skip a header or trailer `{ ... }`, if there's one
+    fn code_section(&mut self, what: &str) -> Result<(), Error> {
+        let line = self.cursor.line_number();
+        if self.cursor.eat("{") {
+            let code = self.ocaml_block()?;
+            if !code[1..code.len() - 1].trim().is_empty() {
+                self.warn(line, format!("{} is not converted", what));
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse a binding after the `let`: `<ident> = <regex>`
+    fn binding(&mut self) -> Result<(), Error> {
+        self.skip_whitespace()?;
+        let name = self.ident()?;
+        self.skip_whitespace()?;
+        self.cursor.expect("=")?;
+        let re = self.regex()?;
+        self.bindings.push((name.to_owned(), re));
+        Ok(())
+    }
+
+    /// Parse an entry point: `<ident> <args> = parse <cases>`
+    fn entry_point(&mut self) -> Result<(), Error> {
+        self.skip_whitespace()?;
+        let line = self.cursor.line_number();
+        let name = self.ident()?;
+
+        let mut args = vec![];
+        loop {
+            self.skip_whitespace()?;
+            if self.cursor.eat("=") {
+                break;
+            }
+            if self.cursor.eat("()") {
+                args.push("()");
+            } else {
+                args.push(self.ident()?);
+            }
+        }
+
+        if !args.is_empty() {
+            self.warn(
+                line,
+                format!(
+                    "Arguments of entry point `{}` are not supported: {}",
+                    name,
+                    args.join(" ")
+                ),
+            );
+        }
+
+        self.skip_whitespace()?;
+        let shortest = if self.keyword("shortest") {
+            true
+        } else if self.keyword("parse") {
+            false
+        } else {
+            return Err(self
+                .cursor
+                .error("Expected `parse` or `shortest`".to_owned()));
+        };
+
+        let rule_set_name = if self.rule_sets.is_empty() {
+            "Init".to_owned()
+        } else {
+            camel_case(name)
+        };
+
+        let mut rules = vec![];
+
+        self.skip_whitespace()?;
+        self.cursor.eat("|");
+
+        loop {
+            let lhs = RegexCtx {
+                anchor: None,
+                left_ctx: None,
+                re: self.regex()?,
+                right_ctx: None,
+            };
+
+            self.skip_whitespace()?;
+            self.cursor.expect("{")?;
+            let code = self.ocaml_block()?;
+
+            let body = code[1..code.len() - 1]
+                .split_whitespace()
+                .collect::<Vec<_>>();
+            let action = if body == [name, "lexbuf"] {
+                Action::Skip
+            } else {
+                Action::Todo(code.to_owned())
+            };
+
+            rules.push(Rule {
+                lhs,
+                shortest,
+                action,
+            });
+
+            self.skip_whitespace()?;
+            if !self.cursor.eat("|") {
+                break;
+            }
+        }
+
+        self.rule_sets.push(RuleSet {
+            name: rule_set_name,
+            rules,
+        });
+
+        Ok(())
+    }
+
+    /// Skip whitespace and comments
+    fn skip_whitespace(&mut self) -> Result<(), Error> {
+        loop {
+            self.cursor.take_while(char::is_whitespace);
+            if self.cursor.rest().starts_with("(*") {
+                self.comment()?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Skip a comment, which can be nested
+    fn comment(&mut self) -> Result<(), Error> {
+        let start = self.cursor.pos;
+        let mut depth = 0;
+        loop {
+            if self.cursor.eat("(*") {
+                depth += 1;
+            } else if self.cursor.eat("*)") {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(());
+                }
+            } else if self.cursor.peek() == Some('"') {
+                self.cursor.next();
+                self.string()?;
+            } else if self.cursor.next().is_none() {
+                return Err(self
+                    .cursor
+                    .error_at(start, "Unterminated comment".to_owned()));
+            }
+        }
+    }
+
+    /// Skip OCaml code until the `}` matching an already consumed `{`. Returns the code with the
+    /// braces.
+    fn ocaml_block(&mut self) -> Result<&'a str, Error> {
+        let start = self.cursor.pos - 1;
+        let mut depth = 1;
+
+        loop {
+            if self.cursor.rest().starts_with("(*") {
+                self.comment()?;
+                continue;
+            }
+
+            match self.cursor.next() {
+                None => return Err(self.cursor.error_at(start, "Unterminated `{`".to_owned())),
+                Some('{') => depth += 1,
+                Some('}') => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(&self.cursor.input[start..self.cursor.pos]);
+                    }
+                }
+                Some('"') => {
+                    self.string()?;
+                }
+                Some('\'') => {
+                    // Skip character literals, but not type variables like `'a`
+                    let rest = self.cursor.rest();
+                    if rest.starts_with('\\') {
+                        self.cursor.pos -= 1;
+                        self.char_literal()?;
+                    } else if rest.chars().nth(1) == Some('\'') {
+                        self.cursor.next();
+                        self.cursor.next();
+                    }
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    /// Skip `keyword` if the input starts with it
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let rest = self.cursor.rest();
+        if rest.starts_with(keyword) && !rest[keyword.len()..].starts_with(is_ident_char) {
+            self.cursor.pos += keyword.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Whether the input starts with a keyword that ends a regex
+    fn at_keyword(&self) -> bool {
+        let rest = self.cursor.rest();
+        ["and", "as", "let", "parse", "refill", "rule", "shortest"]
+            .iter()
+            .any(|keyword| {
+                rest.starts_with(keyword) && !rest[keyword.len()..].starts_with(is_ident_char)
+            })
+    }
+
+    fn ident(&mut self) -> Result<&'a str, Error> {
+        match self.cursor.peek() {
+            Some(char) if char.is_ascii_alphabetic() || char == '_' => {
+                Ok(self.cursor.take_while(is_ident_char))
+            }
+            _ => Err(self.cursor.error("Expected an identifier".to_owned())),
+        }
+    }
+
+    //
+    // Regexes
+    //
+
+    /// Parse a regex, with optional `as` bindings
+    fn regex(&mut self) -> Result<Regex, Error> {
+        let re = self.alternation()?;
+        loop {
+            self.skip_whitespace()?;
+            let line = self.cursor.line_number();
+            if !self.keyword("as") {
+                return Ok(re);
+            }
+            self.skip_whitespace()?;
+            let name = self.ident()?;
+            self.warn(
+                line,
+                format!("`as {}` is not supported, the binding is dropped", name),
+            );
+        }
+    }
+
+    fn alternation(&mut self) -> Result<Regex, Error> {
+        let mut re = self.concatenation()?;
+        loop {
+            self.skip_whitespace()?;
+            if !self.cursor.eat("|") {
+                return Ok(re);
+            }
+            re = Regex::Or(Box::new(re), Box::new(self.concatenation()?));
+        }
+    }
+
+    fn concatenation(&mut self) -> Result<Regex, Error> {
+        let mut re: Option<Regex> = None;
+
+        loop {
+            self.skip_whitespace()?;
+
+            let starts_atom = match self.cursor.peek() {
+                Some('\'' | '"' | '[' | '(' | '_') => true,
+                Some(char) if char.is_ascii_alphabetic() => !self.at_keyword(),
+                _ => false,
+            };
+
+            if !starts_atom {
+                break;
+            }
+
+            let piece = self.repetition()?;
+            re = Some(match re {
+                None => piece,
+                Some(re) => Regex::Concat(Box::new(re), Box::new(piece)),
+            });
+        }
+
+        re.ok_or_else(|| self.cursor.error("Expected a regex".to_owned()))
+    }
+
+    /// Parse a character set difference followed by repetition operators
+    fn repetition(&mut self) -> Result<Regex, Error> {
+        let mut re = self.difference()?;
+        loop {
+            self.skip_whitespace()?;
+            re = match self.cursor.peek() {
+                Some('*') => Regex::ZeroOrMore(Box::new(re)),
+                Some('+') => Regex::OneOrMore(Box::new(re)),
+                Some('?') => Regex::ZeroOrOne(Box::new(re)),
+                _ => return Ok(re),
+            };
+            self.cursor.next();
+        }
+    }
+
+    fn difference(&mut self) -> Result<Regex, Error> {
+        let mut re = self.atom()?;
+        loop {
+            self.skip_whitespace()?;
+            if !self.cursor.eat("#") {
+                return Ok(re);
+            }
+            self.skip_whitespace()?;
+            re = Regex::Diff(Box::new(re), Box::new(self.atom()?));
+        }
+    }
+
+    fn atom(&mut self) -> Result<Regex, Error> {
+        let pos = self.cursor.pos;
+
+        match self.cursor.peek() {
+            Some('\'') => Ok(Regex::Char(self.char_literal()?)),
+
+            Some('"') => {
+                self.cursor.next();
+                Ok(Regex::String(self.string()?))
+            }
+
+            Some('[') => self.char_set(),
+
+            Some('(') => {
+                self.cursor.next();
+                let re = self.regex()?;
+                self.skip_whitespace()?;
+                self.cursor.expect(")")?;
+                Ok(re)
+            }
+
+            _ => {
+                let name = self.ident()?;
+                match name {
+                    "_" => Ok(Regex::Any),
+                    "eof" => Ok(Regex::EndOfInput),
+                    _ => {
+                        if !self.bindings.iter().any(|(name_, _)| name_ == name) {
+                            return Err(self
+                                .cursor
+                                .error_at(pos, format!("Undefined regex {:?}", name)));
+                        }
+                        Ok(Regex::Var(Var(binding_name(name))))
+                    }
+                }
+            }
+        }
+    }
+
+    /// Parse a character set: `[ ... ]`
+    fn char_set(&mut self) -> Result<Regex, Error> {
+        let start = self.cursor.pos;
+        self.cursor.expect("[")?;
+        self.skip_whitespace()?;
+        let negated = self.cursor.eat("^");
+
+        let mut chars = vec![];
+        loop {
+            self.skip_whitespace()?;
+            match self.cursor.peek() {
+                Some(']') => {
+                    self.cursor.next();
+                    break;
+                }
+                Some('\'') => {
+                    let char = self.char_literal()?;
+                    self.skip_whitespace()?;
+                    if self.cursor.eat("-") {
+                        self.skip_whitespace()?;
+                        let end = self.char_literal()?;
+                        if end < char {
+                            return Err(self
+                                .cursor
+                                .error(format!("Invalid range {:?}-{:?}", char, end)));
+                        }
+                        chars.push(CharOrRange::Range(char, end));
+                    } else {
+                        chars.push(CharOrRange::Char(char));
+                    }
+                }
+                Some('"') => {
+                    self.cursor.next();
+                    chars.extend(self.string()?.chars().map(CharOrRange::Char));
+                }
+                None => {
+                    return Err(self
+                        .cursor
+                        .error_at(start, "Unterminated character set".to_owned()))
+                }
+                Some(char) => {
+                    return Err(self
+                        .cursor
+                        .error(format!("Unexpected `{}` in character set", char)))
+                }
+            }
+        }
+
+        if chars.is_empty() {
+            return Err(self
+                .cursor
+                .error_at(start, "Empty character set".to_owned()));
+        }
+
+        let re = Regex::CharSet(CharSet(chars));
+        if negated {
+            Ok(Regex::Diff(Box::new(Regex::Any), Box::new(re)))
+        } else {
+            Ok(re)
+        }
+    }
+
+    /// Parse a character literal: `'c'`
+    fn char_literal(&mut self) -> Result<char, Error> {
+        let start = self.cursor.pos;
+        self.cursor.expect("'")?;
+        let char = match self.cursor.next() {
+            Some('\\') => self.escape()?,
+            Some(char) => char,
+            None => return Err(self.cursor.error("Unterminated character".to_owned())),
+        };
+        if !self.cursor.eat("'") {
+            return Err(self
+                .cursor
+                .error_at(start, "Invalid character literal".to_owned()));
+        }
+        Ok(char)
+    }
+
+    /// Parse a string after the `"`
+    fn string(&mut self) -> Result<String, Error> {
+        let start = self.cursor.pos - 1;
+        let mut str = String::new();
+        loop {
+            match self.cursor.next() {
+                None => {
+                    return Err(self
+                        .cursor
+                        .error_at(start, "Unterminated string".to_owned()))
+                }
+                Some('"') => return Ok(str),
+                Some('\\') if self.cursor.peek() == Some('\n') => {
+                    // Line continuation: skip the newline and indentation
+                    self.cursor
+                        .take_while(|c| c == '\n' || c == ' ' || c == '\t');
+                }
+                Some('\\') => str.push(self.escape()?),
+                Some(char) => str.push(char),
+            }
+        }
+    }
+
+    /// Parse an escape sequence after the `\`
+    fn escape(&mut self) -> Result<char, Error> {
+        let pos = self.cursor.pos - 1;
+        let (digits, radix) = match self.cursor.next() {
+            Some('n') => return Ok('\n'),
+            Some('t') => return Ok('\t'),
+            Some('b') => return Ok('\x08'),
+            Some('r') => return Ok('\r'),
+            Some(char @ ('\\' | '"' | '\'' | ' ')) => return Ok(char),
+            Some('0'..='9') => {
+                self.cursor.pos -= 1;
+                (self.cursor.rest().get(..3), 10)
+            }
+            Some('x') => (self.cursor.rest().get(..2), 16),
+            Some('o') => (self.cursor.rest().get(..3), 8),
+            Some('u') if self.cursor.eat("{") => {
+                let digits = self.cursor.take_while(|c| c.is_ascii_hexdigit());
+                self.cursor.expect("}")?;
+                return u32::from_str_radix(digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| {
+                        self.cursor
+                            .error_at(pos, "Invalid escape sequence".to_owned())
+                    });
+            }
+            _ => {
+                return Err(self
+                    .cursor
+                    .error_at(pos, "Invalid escape sequence".to_owned()))
+            }
+        };
+
+        let digits = digits.ok_or_else(|| {
+            self.cursor
+                .error_at(pos, "Invalid escape sequence".to_owned())
+        })?;
+        self.cursor.pos += digits.len();
+        u32::from_str_radix(digits, radix)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| {
+                self.cursor
+                    .error_at(pos, "Invalid escape sequence".to_owned())
+            })
+    }
+}
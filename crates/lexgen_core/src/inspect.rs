@@ -0,0 +1,369 @@
+//! Inspecting compiled lexers
+//!
+//! An [`Inspection`] compiles a lexer definition the same way `lexer!` does, and keeps the
+//! intermediate NFAs and DFAs around, to print statistics and state machines, and to run the DFA
+//! on sample input without generating and compiling Rust code. State machines can be printed as
+//! text, or as Graphviz DOT graphs. Used by the `lexgen_inspect` tool.
+
+use crate::ast::Lexer;
+use crate::build::Error;
+use crate::collections::Map;
+use crate::dfa::simplify::{simplify, Trans};
+use crate::dfa::{StateIdx as DfaStateIdx, DFA};
+use crate::dot::DotGraph;
+use crate::left_ctx::LeftCtxs;
+use crate::lexer_file::parse_definition;
+use crate::nfa::NFA;
+use crate::right_ctx::RightCtxDFAs;
+use crate::semantic_action_table::{SemanticActionIdx, SemanticActionTable};
+use crate::{compile_lexer, generate_compiled_lexer, RuleSetInspection};
+
+use std::fmt;
+
+/// A compiled lexer definition
+pub struct Inspection {
+    /// DFA of all rule sets, before simplification
+    dfa: DFA<DfaStateIdx, SemanticActionIdx>,
+
+    /// Maps rule set names to their initial states in `dfa`
+    rule_set_states: Map<String, DfaStateIdx>,
+
+    left_ctxs: LeftCtxs,
+    right_ctx_dfas: RightCtxDFAs<DfaStateIdx>,
+
+    /// `dfa`, `rule_set_states`, and `right_ctx_dfas` after simplification
+    simplified_dfa: DFA<Trans<SemanticActionIdx>, SemanticActionIdx>,
+    simplified_rule_set_states: Map<String, DfaStateIdx>,
+    simplified_right_ctx_dfas: RightCtxDFAs<Trans<()>>,
+
+    /// Rule sets in the order they're defined
+    rule_sets: Vec<RuleSet>,
+
+    stats: Stats,
+}
+
+/// A rule set compiled to an NFA and DFA
+struct RuleSet {
+    name: String,
+    nfa: NFA<SemanticActionIdx>,
+
+    /// Number of states in the rule set's DFA, before the DFA is added to the lexer's DFA
+    n_dfa_states: usize,
+
+    /// Semantic actions of the rules of the rule set, with the rules' regexes
+    rules: Vec<(SemanticActionIdx, String)>,
+}
+
+/// Statistics of a compiled lexer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stats {
+    /// Rule sets in the order they're defined
+    pub rule_sets: Vec<RuleSetStats>,
+
+    /// Number of states in the DFA of all rule sets
+    pub n_dfa_states: usize,
+
+    /// Number of states in the simplified DFA, which is used to generate code
+    pub n_simplified_dfa_states: usize,
+
+    /// Number of states that are generated inline in their only predecessor
+    pub n_inlined_states: usize,
+
+    /// Number of binary search tables generated for large character sets
+    pub n_search_tables: usize,
+
+    /// Number of right context DFAs
+    pub n_right_ctxs: usize,
+
+    /// Size of the generated code, in bytes, before formatting
+    pub code_size: usize,
+}
+
+/// Statistics of a rule set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleSetStats {
+    pub name: String,
+    pub n_rules: usize,
+    pub n_nfa_states: usize,
+    pub n_dfa_states: usize,
+}
+
+/// Result of running a lexer on sample input with [`Inspection::run`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Run<'a, 'input> {
+    /// Matched input, in order
+    pub matches: Vec<Match<'a, 'input>>,
+
+    /// When the lexer fails, byte index of the start of the input that couldn't be matched
+    pub error: Option<usize>,
+}
+
+/// A rule matched by [`Inspection::run`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match<'a, 'input> {
+    /// The matched input
+    pub text: &'input str,
+
+    /// Index of the matched rule in its rule set, starting from 0
+    pub rule_idx: usize,
+
+    /// Regex of the matched rule
+    pub rule: &'a str,
+}
+
+impl Inspection {
+    /// Compile a lexer definition. `definition` has the same syntax as the body of
+    /// `lexer! { ... }`.
+    pub fn new(definition: &str) -> Result<Inspection, Error> {
+        let (lexer, semantic_action_table) = parse_definition(definition)?;
+        Inspection::from_parsed(lexer, semantic_action_table)
+    }
+
+    pub(crate) fn from_parsed(
+        lexer: Lexer,
+        semantic_action_table: SemanticActionTable,
+    ) -> Result<Inspection, Error> {
+        let mut lexer = compile_lexer(lexer, semantic_action_table, true)?;
+
+        let dfa = lexer.dfa.clone();
+        let rule_set_states = lexer.rule_set_states.clone();
+        let left_ctxs = lexer.left_ctxs.clone();
+        let right_ctx_dfas = lexer.right_ctx_dfas.clone();
+        let rule_sets: Vec<RuleSet> = std::mem::take(&mut lexer.rule_sets)
+            .into_iter()
+            .map(|rule_set| {
+                let RuleSetInspection {
+                    nfa,
+                    n_dfa_states,
+                    rules,
+                } = rule_set
+                    .inspection
+                    .expect("Rule set compiled without inspection");
+                RuleSet {
+                    name: rule_set.name,
+                    nfa,
+                    n_dfa_states,
+                    rules: rule_set.actions.into_iter().zip(rules).collect(),
+                }
+            })
+            .collect();
+
+        let mut simplified_rule_set_states = rule_set_states.clone();
+        let simplified_dfa = simplify(dfa.clone(), &mut simplified_rule_set_states);
+        let simplified_right_ctx_dfas = right_ctx_dfas.clone().simplify();
+
+        let (code, codegen_stats) = generate_compiled_lexer(lexer);
+
+        let stats = Stats {
+            rule_sets: rule_sets
+                .iter()
+                .map(|rule_set| RuleSetStats {
+                    name: rule_set.name.clone(),
+                    n_rules: rule_set.rules.len(),
+                    n_nfa_states: rule_set.nfa.n_states(),
+                    n_dfa_states: rule_set.n_dfa_states,
+                })
+                .collect(),
+            n_dfa_states: dfa.n_states(),
+            n_simplified_dfa_states: codegen_stats.n_states,
+            n_inlined_states: codegen_stats.n_inlined_states,
+            n_search_tables: codegen_stats.n_search_tables,
+            n_right_ctxs: right_ctx_dfas.iter().count(),
+            code_size: code.to_string().len(),
+        };
+
+        Ok(Inspection {
+            dfa,
+            rule_set_states,
+            left_ctxs,
+            right_ctx_dfas,
+            simplified_dfa,
+            simplified_rule_set_states,
+            simplified_right_ctx_dfas,
+            rule_sets,
+            stats,
+        })
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Names of the rule sets, in the order they're defined
+    pub fn rule_set_names(&self) -> impl Iterator<Item = &str> {
+        self.rule_sets.iter().map(|rule_set| rule_set.name.as_str())
+    }
+
+    /// Regexes of the rules of a rule set, in the order they're defined
+    pub fn rules(&self, rule_set: &str) -> Option<impl Iterator<Item = &str>> {
+        self.rule_set(rule_set)
+            .map(|rule_set| rule_set.rules.iter().map(|(_, rule)| rule.as_str()))
+    }
+
+    /// NFA of a rule set, in the same format as the `lexgen` debug output
+    pub fn nfa(&self, rule_set: &str) -> Option<String> {
+        self.rule_set(rule_set)
+            .map(|rule_set| rule_set.nfa.to_string())
+    }
+
+    /// DFA of all rule sets, followed by the initial states of the rule sets and the rules
+    /// accepted by the accepting states
+    pub fn dfa(&self) -> String {
+        let mut out = self.dfa.to_string();
+
+        out.push('\n');
+        for rule_set in &self.rule_sets {
+            out.push_str(&format!(
+                "{}: initial state {}\n",
+                rule_set.name, self.rule_set_states[&rule_set.name]
+            ));
+        }
+
+        out.push('\n');
+        for (state, accepting_states) in self.dfa.iter_accepting_states() {
+            for accepting_state in accepting_states {
+                out.push_str(&format!(
+                    "{:>5}: {}\n",
+                    format!("*{}", state),
+                    self.rule_label(accepting_state.value)
+                ));
+            }
+        }
+
+        out
+    }
+
+    /// NFA of a rule set, as a DOT graph
+    pub fn nfa_dot(&self, rule_set: &str) -> Option<String> {
+        let rule_set = self.rule_set(rule_set)?;
+        let mut graph = DotGraph::new();
+        rule_set
+            .nfa
+            .add_to_dot(&mut graph, "s", &rule_set.name, &|action| {
+                self.rule_label(*action)
+            });
+        Some(graph.finish())
+    }
+
+    /// DFA of all rule sets, with right context DFAs, as a DOT graph
+    pub fn dfa_dot(&self) -> String {
+        let mut graph = DotGraph::new();
+        self.dfa.add_to_dot(
+            &mut graph,
+            "s",
+            &self.initial_labels(&self.rule_set_states),
+            &|action| self.rule_label(*action),
+        );
+        self.right_ctx_dfas.add_to_dot(&mut graph);
+        graph.finish()
+    }
+
+    /// DFA of all rule sets, with right context DFAs, after simplification, as a DOT graph. This
+    /// is the DFA that code is generated for. Accepting states without transitions are removed in
+    /// simplification, transitions to those states accept directly.
+    pub fn simplified_dfa_dot(&self) -> String {
+        let mut graph = DotGraph::new();
+        self.simplified_dfa.add_to_dot(
+            &mut graph,
+            "s",
+            &self.initial_labels(&self.simplified_rule_set_states),
+            &|action| self.rule_label(*action),
+        );
+        self.simplified_right_ctx_dfas.add_to_dot(&mut graph);
+        graph.finish()
+    }
+
+    /// Run the DFA of a rule set on `input`. Semantic actions are not run, so all matches are in
+    /// the given rule set. Returns `None` if the rule set doesn't exist.
+    pub fn run<'a, 'input>(
+        &'a self,
+        rule_set: &str,
+        input: &'input str,
+    ) -> Option<Run<'a, 'input>> {
+        let initial_state = *self.rule_set_states.get(rule_set)?;
+
+        let (matches, error) =
+            self.dfa
+                .simulate_from(initial_state, input, &self.left_ctxs, &self.right_ctx_dfas);
+
+        let matches = matches
+            .into_iter()
+            .map(|(text, action)| {
+                let (_, rule_idx, rule) = self
+                    .find_rule(action)
+                    .expect("Semantic action without a rule");
+                Match {
+                    text,
+                    rule_idx,
+                    rule,
+                }
+            })
+            .collect();
+
+        Some(Run { matches, error })
+    }
+
+    /// Rule set names with their initial states in `rule_set_states`
+    fn initial_labels(
+        &self,
+        rule_set_states: &Map<String, DfaStateIdx>,
+    ) -> Vec<(String, DfaStateIdx)> {
+        self.rule_sets
+            .iter()
+            .map(|rule_set| (rule_set.name.clone(), rule_set_states[&rule_set.name]))
+            .collect()
+    }
+
+    /// Label of a semantic action in DOT graphs
+    fn rule_label(&self, action: SemanticActionIdx) -> String {
+        match self.find_rule(action) {
+            Some((rule_set, rule_idx, rule)) => format!("{} rule {}: {}", rule_set, rule_idx, rule),
+            None => String::new(),
+        }
+    }
+
+    fn rule_set(&self, name: &str) -> Option<&RuleSet> {
+        self.rule_sets.iter().find(|rule_set| rule_set.name == name)
+    }
+
+    /// Find the rule set name, rule index in the rule set, and regex of a semantic action
+    fn find_rule(&self, action: SemanticActionIdx) -> Option<(&str, usize, &str)> {
+        self.rule_sets.iter().find_map(|rule_set| {
+            rule_set
+                .rules
+                .iter()
+                .position(|(action_, _)| *action_ == action)
+                .map(|rule_idx| {
+                    (
+                        rule_set.name.as_str(),
+                        rule_idx,
+                        rule_set.rules[rule_idx].1.as_str(),
+                    )
+                })
+        })
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for RuleSetStats {
+            name,
+            n_rules,
+            n_nfa_states,
+            n_dfa_states,
+        } in &self.rule_sets
+        {
+            writeln!(
+                f,
+                "rule set {}: {} rules, {} NFA states, {} DFA states",
+                name, n_rules, n_nfa_states, n_dfa_states
+            )?;
+        }
+        writeln!(f, "DFA states: {}", self.n_dfa_states)?;
+        writeln!(f, "simplified DFA states: {}", self.n_simplified_dfa_states)?;
+        writeln!(f, "inlined states: {}", self.n_inlined_states)?;
+        writeln!(f, "search tables: {}", self.n_search_tables)?;
+        writeln!(f, "right contexts: {}", self.n_right_ctxs)?;
+        write!(f, "generated code: {} bytes", self.code_size)
+    }
+}
@@ -0,0 +1,87 @@
+//! Stuff related to left contexts
+//!
+//! A left context is a limited version of lookbehind. A rule can have at most one left context.
+//! Left contexts are either character sets or predicates.
+//!
+//! Character sets are checked against the character before the location where the current rule
+//! set started matching. Only if that character is in the set we consider the rule as a match. At
+//! the beginning of the input there is no previous character, so character set left contexts
+//! never match. Checking just the previous character means we don't need to keep the consumed
+//! input around, so left contexts work with iterator inputs as well.
+//!
+//! Predicates are Rust functions that take the lexer handle, and are used to check things that
+//! can't be seen in the previous character, e.g. the kind of the previous token recorded in the
+//! user state. They are only evaluated in generated code.
+
+use crate::ast::{Binding, LeftCtx, Var};
+use crate::collections::Map;
+use crate::lexer_file::DefinitionError;
+use crate::range_map::RangeMap;
+use crate::regex_to_nfa::{regex_to_range_map, CharSetUse};
+
+#[derive(Debug, Clone)]
+pub struct LeftCtxs {
+    left_ctxs: Vec<CompiledLeftCtx>,
+}
+
+#[derive(Debug, Clone)]
+pub enum CompiledLeftCtx {
+    Char(RangeMap<()>),
+    Pred(syn::Expr),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LeftCtxIdx(usize);
+
+impl LeftCtxIdx {
+    pub fn as_usize(&self) -> usize {
+        self.0
+    }
+}
+
+impl LeftCtxs {
+    pub fn new() -> Self {
+        LeftCtxs { left_ctxs: vec![] }
+    }
+
+    pub fn new_left_ctx(
+        &mut self,
+        bindings: &Map<Var, Binding>,
+        left_ctx: &LeftCtx,
+    ) -> Result<LeftCtxIdx, DefinitionError> {
+        let idx = self.left_ctxs.len();
+        self.left_ctxs.push(match left_ctx {
+            LeftCtx::Char(re) => {
+                CompiledLeftCtx::Char(regex_to_range_map(bindings, re, CharSetUse::LeftCtx)?)
+            }
+            LeftCtx::Pred(pred) => CompiledLeftCtx::Pred(pred.clone()),
+        });
+        Ok(LeftCtxIdx(idx))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (LeftCtxIdx, &CompiledLeftCtx)> {
+        self.left_ctxs
+            .iter()
+            .enumerate()
+            .map(|(i, left_ctx)| (LeftCtxIdx(i), left_ctx))
+    }
+
+    /// Whether any of the left contexts is a predicate
+    pub fn has_preds(&self) -> bool {
+        self.left_ctxs
+            .iter()
+            .any(|left_ctx| matches!(left_ctx, CompiledLeftCtx::Pred(_)))
+    }
+
+    /// Whether the left context holds when `char` is the previous character. Predicates can't be
+    /// evaluated without the generated code, they're assumed to hold.
+    pub fn matches(&self, left_ctx: &LeftCtxIdx, char: Option<char>) -> bool {
+        match &self.left_ctxs[left_ctx.as_usize()] {
+            CompiledLeftCtx::Char(char_set) => match char {
+                None => false,
+                Some(char) => char_set.iter().any(|range| range.contains(char)),
+            },
+            CompiledLeftCtx::Pred(_) => true,
+        }
+    }
+}
@@ -0,0 +1,266 @@
+//! Generating lexers from definitions in strings, used by `lexer_file!` and build scripts
+//!
+//! When used in a proc macro, the definition is tokenized with the compiler's tokenizer, so all
+//! tokens get the span of the macro invocation. To report syntax errors at their locations in the
+//! definition, when parsing fails we parse the definition again with `proc_macro2`'s fallback
+//! tokenizer, which keeps line and column numbers of tokens, and use the location of the error in
+//! the fallback parse.
+//!
+//! Errors found after parsing (e.g. undefined variables) are returned as
+//! `DefinitionError::Invalid`, with the location of the rule or binding with the error.
+//! `lexer_file!` reports those as panics, as `lexer!` does, prefixed with the file path and the
+//! location. Locations of these errors are also found by generating the lexer again with the
+//! fallback tokenizer.
+//!
+//! Line and column numbers of tokens need `proc_macro2`'s `span-locations` feature, enabled with
+//! this crate's `span-locations` feature. Without it errors are not located: syntax errors are
+//! reported at line 1, column 1, and `lexer_file!` is not available.
+
+use crate::ast;
+use crate::semantic_action_table::SemanticActionTable;
+
+use std::fmt;
+#[cfg(feature = "span-locations")]
+use std::path::{Path, PathBuf};
+
+use proc_macro2::{Span, TokenStream};
+#[cfg(feature = "span-locations")]
+use quote::quote;
+use syn::parse::Parser;
+
+/// An error in a lexer definition
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DefinitionError {
+    /// Syntax error, with 1-based line and column numbers
+    Syntax {
+        line: usize,
+        col: usize,
+        message: String,
+    },
+
+    /// Other errors, e.g. undefined variables, with the 1-based line and column numbers of the
+    /// rule or binding with the error, when known
+    Invalid {
+        location: Option<(usize, usize)>,
+        message: String,
+    },
+}
+
+impl DefinitionError {
+    pub(crate) fn invalid(message: String) -> DefinitionError {
+        DefinitionError::Invalid {
+            location: None,
+            message,
+        }
+    }
+
+    /// Set the location of an `Invalid` error without a location to the start of `span`
+    pub(crate) fn at(self, span: Option<Span>) -> DefinitionError {
+        match self {
+            DefinitionError::Invalid {
+                location: None,
+                message,
+            } => DefinitionError::Invalid {
+                location: span.and_then(span_location),
+                message,
+            },
+            _ => self,
+        }
+    }
+}
+
+impl fmt::Display for DefinitionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DefinitionError::Syntax { line, col, message } => {
+                write!(f, "{}:{}: {}", line, col, message)
+            }
+            DefinitionError::Invalid {
+                location: Some((line, col)),
+                message,
+            } => write!(f, "{}:{}: {}", line, col, message),
+            DefinitionError::Invalid {
+                location: None,
+                message,
+            } => message.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for DefinitionError {}
+
+/// Generate code for a lexer definition in a string
+pub fn generate_from_str(definition: &str) -> Result<TokenStream, DefinitionError> {
+    let (lexer, semantic_action_table) = parse_definition(definition)?;
+    crate::generate_lexer(lexer, semantic_action_table)
+}
+
+/// Parse a lexer definition in a string
+pub fn parse_definition(
+    definition: &str,
+) -> Result<(ast::Lexer, SemanticActionTable), DefinitionError> {
+    parse_str(definition, &parse_lexer)
+}
+
+/// Parse a regex with optional anchor and contexts in a string, e.g. `^ ['a'-'z']+ > ' '`
+pub fn parse_regex_ctx(re: &str) -> Result<ast::RegexCtx, DefinitionError> {
+    parse_str(re, &|tokens| ast::parse_regex_ctx.parse2(tokens))
+}
+
+/// Parse the right-hand side of a rule in a string, e.g. `= Token::Plus,`
+pub fn parse_rule_rhs(rhs: &str) -> Result<ast::RuleRhs, DefinitionError> {
+    parse_str(rhs, &|tokens| ast::parse_rule_rhs.parse2(tokens))
+}
+
+fn parse_lexer(tokens: TokenStream) -> syn::Result<(ast::Lexer, SemanticActionTable)> {
+    let mut semantic_action_table = SemanticActionTable::new();
+    let lexer = ast::make_lexer_parser(&mut semantic_action_table).parse2(tokens)?;
+    Ok((lexer, semantic_action_table))
+}
+
+/// Parse `input` with `parse`, reporting syntax errors with their locations in `input`
+fn parse_str<T>(
+    input: &str,
+    parse: &dyn Fn(TokenStream) -> syn::Result<T>,
+) -> Result<T, DefinitionError> {
+    let syntax_error = |message: String| -> DefinitionError {
+        let (line, col) = locate_error(input, parse);
+        DefinitionError::Syntax { line, col, message }
+    };
+
+    let tokens: TokenStream = match input.parse() {
+        Ok(tokens) => tokens,
+        Err(error) => return Err(syntax_error(error.to_string())),
+    };
+
+    parse(tokens).map_err(|error| syntax_error(error.to_string()))
+}
+
+#[cfg(feature = "span-locations")]
+pub fn expand(input: TokenStream) -> TokenStream {
+    let path_lit: syn::LitStr = match syn::parse2(input) {
+        Ok(path_lit) => path_lit,
+        Err(error) => return error.to_compile_error(),
+    };
+
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR")
+        .expect("lexer_file!: CARGO_MANIFEST_DIR is not set, is the crate built with cargo?");
+
+    let path: PathBuf = Path::new(&manifest_dir).join(path_lit.value());
+
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(error) => {
+            return syn::Error::new(
+                path_lit.span(),
+                format!("Unable to read {}: {}", path.display(), error),
+            )
+            .to_compile_error()
+        }
+    };
+
+    let path_str = match path.to_str() {
+        Some(path_str) => path_str,
+        None => {
+            return syn::Error::new(
+                path_lit.span(),
+                format!("Path {} is not valid UTF-8", path.display()),
+            )
+            .to_compile_error()
+        }
+    };
+
+    let lexer = match generate_from_str(&contents) {
+        Ok(lexer) => lexer,
+        Err(DefinitionError::Syntax { line, col, message }) => {
+            return syn::Error::new(
+                path_lit.span(),
+                format!("{}:{}:{}: {}", path_str, line, col, message),
+            )
+            .to_compile_error()
+        }
+        Err(DefinitionError::Invalid { message, .. }) => {
+            // Locations of spans from the compiler's tokenizer are in the `lexer_file!`
+            // invocation, not in the definition
+            match locate_invalid(&contents) {
+                Some((line, col)) => panic!("{}:{}:{}: {}", path_str, line, col, message),
+                None => panic!("{}: {}", path_str, message),
+            }
+        }
+    };
+
+    // `include_str!` makes cargo rebuild the crate when the file changes
+    quote!(
+        const _: &str = include_str!(#path_str);
+        #lexer
+    )
+}
+
+/// Parse the lexer definition with `proc_macro2`'s fallback tokenizer and return the 1-based line
+/// and column of the syntax error. Returns `(1, 1)` if the error can't be located.
+#[cfg(test)]
+pub fn locate_parse_error(contents: &str) -> (usize, usize) {
+    locate_error(contents, &parse_lexer)
+}
+
+/// Same as `locate_parse_error`, but parses `contents` with `parse`
+#[cfg(feature = "span-locations")]
+fn locate_error<T>(
+    contents: &str,
+    parse: &dyn Fn(TokenStream) -> syn::Result<T>,
+) -> (usize, usize) {
+    let location = with_fallback(|| {
+        let span = match contents.parse::<TokenStream>() {
+            Err(error) => Some(error.span()),
+            Ok(tokens) => parse(tokens).err().map(|error| error.span()),
+        };
+        span.and_then(span_location)
+    });
+
+    location.unwrap_or((1, 1))
+}
+
+#[cfg(not(feature = "span-locations"))]
+fn locate_error<T>(
+    _contents: &str,
+    _parse: &dyn Fn(TokenStream) -> syn::Result<T>,
+) -> (usize, usize) {
+    (1, 1)
+}
+
+/// Generate code for the lexer definition with `proc_macro2`'s fallback tokenizer and return the
+/// location of the `DefinitionError::Invalid` error
+#[cfg(feature = "span-locations")]
+fn locate_invalid(contents: &str) -> Option<(usize, usize)> {
+    with_fallback(|| match generate_from_str(contents) {
+        Err(DefinitionError::Invalid { location, .. }) => location,
+        _ => None,
+    })
+}
+
+/// Run `f` with `proc_macro2`'s fallback tokenizer, which keeps line and column numbers of tokens
+/// in proc macros
+#[cfg(feature = "span-locations")]
+fn with_fallback<T>(f: impl FnOnce() -> T) -> T {
+    proc_macro2::fallback::force();
+    let result = f();
+    proc_macro2::fallback::unforce();
+    result
+}
+
+/// 1-based line and column of the start of `span`. Spans of tokens from the compiler's tokenizer
+/// may not have locations.
+#[cfg(feature = "span-locations")]
+fn span_location(span: Span) -> Option<(usize, usize)> {
+    let start = span.start();
+    if start.line == 0 {
+        None
+    } else {
+        Some((start.line, start.column + 1))
+    }
+}
+
+#[cfg(not(feature = "span-locations"))]
+fn span_location(_span: Span) -> Option<(usize, usize)> {
+    None
+}
@@ -0,0 +1,626 @@
+//! The lexer generator behind the `lexgen` crate.
+//!
+//! The `lexgen` crate's `lexer!` and `lexer_file!` macros call [`lexer`] and [`lexer_file`]. In
+//! build scripts, [`process_file`] generates a lexer from a lexer definition file and writes the
+//! code to `OUT_DIR`, to be used with `include!`. Please see the [project README][1] for usage.
+//!
+//! [1]: https://github.com/osa1/lexgen
+
+#![allow(
+    clippy::collapsible_else_if,
+    clippy::enum_variant_names,
+    clippy::large_enum_variant,
+    clippy::too_many_arguments,
+    clippy::upper_case_acronyms
+)]
+
+mod ast;
+mod build;
+pub mod builder;
+mod builtin;
+mod char_ranges;
+mod collections;
+mod dfa;
+mod display;
+mod dot;
+pub mod import;
+pub mod inspect;
+mod left_ctx;
+mod lexer_file;
+mod nfa;
+mod nfa_to_dfa;
+mod range_map;
+mod raw_regex;
+mod regex_to_nfa;
+mod right_ctx;
+pub mod runtime;
+mod semantic_action_table;
+mod token_enum;
+
+#[cfg(test)]
+mod tests;
+
+pub use build::{generate, process_file, Error};
+
+use ast::{Binding, LeftCtx, Lexer, Regex, RegexCtx, RightCtx, Rule, RuleOptions, SingleRule, Var};
+use collections::{Map, Set};
+use dfa::{StateIdx as DfaStateIdx, DFA};
+use left_ctx::LeftCtxs;
+use lexer_file::DefinitionError;
+use nfa::NFA;
+use nfa_to_dfa::nfa_to_dfa;
+use right_ctx::RightCtxDFAs;
+use semantic_action_table::{SemanticActionIdx, SemanticActionTable};
+use token_enum::TokenEnum;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::parse::Parser;
+
+/// Expansion of `lexer! { ... }`. `input` is the body of the macro invocation.
+///
+/// Syntax errors are returned as `compile_error!` invocations. Other errors in the lexer
+/// definition cause a panic.
+pub fn lexer(input: TokenStream) -> TokenStream {
+    let mut semantic_action_table = SemanticActionTable::new();
+
+    let lexer = match ast::make_lexer_parser(&mut semantic_action_table).parse2(input) {
+        Ok(lexer) => lexer,
+        Err(error) => return error.to_compile_error(),
+    };
+
+    match generate_lexer(lexer, semantic_action_table) {
+        Ok(code) => code,
+        Err(DefinitionError::Invalid { message, .. }) => panic!("{}", message),
+        Err(error) => panic!("{}", error),
+    }
+}
+
+/// Expansion of `lexer_file!("path")`: same as [`lexer`], but reads the lexer definition from a
+/// file. The path is relative to the crate's `Cargo.toml`. Needs the `span-locations` feature.
+#[cfg(feature = "span-locations")]
+pub fn lexer_file(input: TokenStream) -> TokenStream {
+    lexer_file::expand(input)
+}
+
+/// A lexer definition compiled to a DFA, before code generation
+struct CompiledLexer {
+    public: bool,
+    type_name: syn::Ident,
+    user_state_type: Option<syn::Type>,
+    user_error_type: Option<syn::Type>,
+    token_type: syn::Type,
+    token_enum: Option<TokenEnum>,
+    semantic_action_table: SemanticActionTable,
+
+    /// DFA of all rule sets
+    dfa: DFA<DfaStateIdx, SemanticActionIdx>,
+
+    /// Maps rule set names to their initial states in `dfa`
+    rule_set_states: Map<String, DfaStateIdx>,
+
+    left_ctxs: LeftCtxs,
+    right_ctx_dfas: RightCtxDFAs<DfaStateIdx>,
+
+    /// Rule sets in the order they're defined
+    rule_sets: Vec<CompiledRuleSet>,
+}
+
+/// A compiled rule set
+struct CompiledRuleSet {
+    name: String,
+
+    /// Semantic actions of the rules of the rule set, in the order the rules are defined
+    actions: Vec<SemanticActionIdx>,
+
+    /// Only kept when compiling for inspection
+    inspection: Option<RuleSetInspection>,
+}
+
+/// NFA and rule regexes of a rule set, for inspecting the lexer
+struct RuleSetInspection {
+    nfa: NFA<SemanticActionIdx>,
+
+    /// Number of states in the rule set's DFA, before the DFA is added to the lexer's DFA
+    n_dfa_states: usize,
+
+    /// Regexes of the rules, in the same order as `CompiledRuleSet::actions`
+    rules: Vec<String>,
+}
+
+/// Compile a parsed lexer definition to Rust code
+fn generate_lexer(
+    lexer: Lexer,
+    semantic_action_table: SemanticActionTable,
+) -> Result<TokenStream, DefinitionError> {
+    let (code, _) = generate_compiled_lexer(compile_lexer(lexer, semantic_action_table, false)?);
+    Ok(code)
+}
+
+/// Compile a parsed lexer definition to a DFA. When `inspect` is true NFAs and regexes of rule
+/// sets are kept in `CompiledRuleSet::inspection`.
+fn compile_lexer(
+    lexer: Lexer,
+    semantic_action_table: SemanticActionTable,
+    inspect: bool,
+) -> Result<CompiledLexer, DefinitionError> {
+    let Lexer {
+        public,
+        type_name,
+        user_state_type,
+        token_type,
+        token_enum,
+        rules: top_level_rules,
+    } = lexer;
+
+    let mut token_enum = token_enum.map(TokenEnum::new);
+
+    // Maps DFA names to their initial states in the final DFA
+    let mut dfas: Map<String, dfa::StateIdx> = Default::default();
+
+    // Character sets for left contexts
+    let mut left_ctxs = LeftCtxs::new();
+
+    // DFAs generated for right contexts
+    let mut right_ctx_dfas = RightCtxDFAs::new();
+
+    let mut bindings: Map<Var, Binding> = Default::default();
+
+    // Right contexts of bindings. A binding with a right context can only be used as the entire
+    // regex of a rule (or another binding), which then inherits the right context.
+    let mut binding_right_ctxs: Map<Var, RightCtx> = Default::default();
+
+    let mut dfa: Option<DFA<DfaStateIdx, SemanticActionIdx>> = None;
+
+    let mut rule_sets: Vec<CompiledRuleSet> = vec![];
+
+    let mut user_error_type: Option<syn::Type> = None;
+
+    let have_named_rules = top_level_rules
+        .iter()
+        .any(|rule| matches!(rule, Rule::RuleSet { .. }));
+
+    for rule in top_level_rules {
+        if let (Some(token_enum), Rule::RuleSet { rules, .. } | Rule::UnnamedRules { rules }) =
+            (&mut token_enum, &rule)
+        {
+            for SingleRule { lhs, rhs, .. } in rules {
+                token_enum.add_rule(lhs, semantic_action_table.get(*rhs));
+            }
+        }
+
+        match rule {
+            Rule::Binding {
+                var,
+                params,
+                re,
+                span,
+            } => {
+                add_binding(&mut bindings, &mut binding_right_ctxs, var, params, re)
+                    .map_err(|error| error.at(span))?;
+            }
+            Rule::RuleSet { name, rules, span } => {
+                if name == "Init" {
+                    let dfa = dfa.insert(compile_rules(
+                        name.to_string(),
+                        rules,
+                        &bindings,
+                        &binding_right_ctxs,
+                        &mut left_ctxs,
+                        &mut right_ctx_dfas,
+                        &mut rule_sets,
+                        inspect,
+                    )?);
+                    let initial_state = dfa.initial_state();
+
+                    if dfas.insert(name.to_string(), initial_state).is_some() {
+                        return Err(DefinitionError::invalid(format!(
+                            "Rule set {:?} is defined multiple times",
+                            name.to_string()
+                        ))
+                        .at(span));
+                    }
+                } else {
+                    let dfa = dfa.as_mut().ok_or_else(|| {
+                        DefinitionError::invalid(
+                            "First rule set should be named \"Init\"".to_owned(),
+                        )
+                        .at(span)
+                    })?;
+
+                    let dfa_ = compile_rules(
+                        name.to_string(),
+                        rules,
+                        &bindings,
+                        &binding_right_ctxs,
+                        &mut left_ctxs,
+                        &mut right_ctx_dfas,
+                        &mut rule_sets,
+                        inspect,
+                    )?;
+
+                    let dfa_idx = dfa.add_dfa(dfa_);
+
+                    if dfas.insert(name.to_string(), dfa_idx).is_some() {
+                        return Err(DefinitionError::invalid(format!(
+                            "Rule set {:?} is defined multiple times",
+                            name.to_string()
+                        ))
+                        .at(span));
+                    }
+                }
+            }
+            Rule::UnnamedRules { rules } => {
+                if dfa.is_some() || have_named_rules {
+                    return Err(DefinitionError::invalid(format!(
+                        "Unnamed rules cannot be mixed with named rules. Make sure to either \
+                        have all your rules in `rule ... {} ... {}` syntax, or remove `rule`s \
+                        entirely and have your rules at the top-level.",
+                        '{', '}',
+                    ))
+                    .at(rules.first().and_then(|rule| rule.span)));
+                }
+
+                let dfa = dfa.insert(compile_rules(
+                    "Init".to_owned(),
+                    rules,
+                    &bindings,
+                    &binding_right_ctxs,
+                    &mut left_ctxs,
+                    &mut right_ctx_dfas,
+                    &mut rule_sets,
+                    inspect,
+                )?);
+                let initial_state = dfa.initial_state();
+                dfas.insert("Init".to_owned(), initial_state);
+            }
+            Rule::ErrorType { ty } => match user_error_type {
+                None => {
+                    user_error_type = Some(ty);
+                }
+                Some(_) => {
+                    return Err(DefinitionError::invalid(
+                        "Error type defined multiple times".to_owned(),
+                    ))
+                }
+            },
+        }
+    }
+
+    // There should be a rule with name "Init"
+    let dfa = match dfa {
+        Some(dfa) if dfas.contains_key("Init") => dfa,
+        _ => {
+            return Err(DefinitionError::invalid(format!(
+                "There should be a rule set named \"Init\". Current rules: {:?}",
+                dfas.keys().collect::<Vec<&String>>()
+            )))
+        }
+    };
+
+    Ok(CompiledLexer {
+        public,
+        type_name,
+        user_state_type,
+        user_error_type,
+        token_type,
+        token_enum,
+        semantic_action_table,
+        dfa,
+        rule_set_states: dfas,
+        left_ctxs,
+        right_ctx_dfas,
+        rule_sets,
+    })
+}
+
+/// Generate code for a compiled lexer
+fn generate_compiled_lexer(lexer: CompiledLexer) -> (TokenStream, dfa::codegen::CodegenStats) {
+    let CompiledLexer {
+        public,
+        type_name,
+        user_state_type,
+        user_error_type,
+        token_type,
+        token_enum,
+        semantic_action_table,
+        dfa,
+        mut rule_set_states,
+        left_ctxs,
+        right_ctx_dfas,
+        rule_sets: _,
+    } = lexer;
+
+    let dfa = dfa::simplify::simplify(dfa, &mut rule_set_states);
+
+    let right_ctx_dfas = right_ctx_dfas.simplify();
+
+    let (lexer, stats) = dfa::codegen::reify(
+        dfa,
+        &left_ctxs,
+        &right_ctx_dfas,
+        semantic_action_table,
+        user_state_type,
+        user_error_type,
+        rule_set_states,
+        type_name,
+        token_type,
+        public,
+    );
+
+    let token_enum = token_enum.map(TokenEnum::generate);
+
+    (
+        quote!(
+            #token_enum
+            #lexer
+        ),
+        stats,
+    )
+}
+
+/// Adds a `let` binding
+fn add_binding(
+    bindings: &mut Map<Var, Binding>,
+    binding_right_ctxs: &mut Map<Var, RightCtx>,
+    var: Var,
+    params: Vec<Var>,
+    re: RegexCtx,
+) -> Result<(), DefinitionError> {
+    if bindings.contains_key(&var) {
+        return Err(DefinitionError::invalid(format!(
+            "Variable {:?} is defined multiple times",
+            var.0
+        )));
+    }
+    if re.anchor.is_some() {
+        return Err(DefinitionError::invalid(format!(
+            "Variable {:?} has an anchor. Anchors (`^` and `^^`) can only be used in rules.",
+            var.0
+        )));
+    }
+    if re.left_ctx.is_some() {
+        return Err(DefinitionError::invalid(format!(
+            "Variable {:?} has a left context. Left contexts can only be used in rules.",
+            var.0
+        )));
+    }
+
+    if params.is_empty() {
+        if let Some(right_ctx) = resolve_right_ctx(binding_right_ctxs, &re.re, re.right_ctx)? {
+            binding_right_ctxs.insert(var.clone(), right_ctx);
+        }
+    } else {
+        if re.right_ctx.is_some() {
+            return Err(DefinitionError::invalid(format!(
+                "Variable {:?} has a right context. Right contexts cannot be used in \
+                parameterised bindings.",
+                var.0
+            )));
+        }
+        // Parameters shadow the variables with the same names
+        let mut binding_right_ctxs = binding_right_ctxs.clone();
+        for param in &params {
+            binding_right_ctxs.remove(param);
+        }
+        check_no_right_ctx_vars(&binding_right_ctxs, &re.re)?;
+    }
+
+    let binding = Binding { params, re: re.re };
+    check_no_cycles(bindings, &var, &binding)?;
+    bindings.insert(var, binding);
+
+    Ok(())
+}
+
+/// Fails if `binding`, the binding of `var`, uses `var`, directly or via other bindings.
+fn check_no_cycles(
+    bindings: &Map<Var, Binding>,
+    var: &Var,
+    binding: &Binding,
+) -> Result<(), DefinitionError> {
+    fn check(
+        bindings: &Map<Var, Binding>,
+        var: &Var,
+        binding: &Binding,
+        path: &mut Vec<Var>,
+        visited: &mut Set<Var>,
+    ) -> Result<(), DefinitionError> {
+        let mut used_vars: Vec<Var> = vec![];
+        binding.re.for_each_var(&mut |used_var| {
+            if !binding.params.contains(used_var) {
+                used_vars.push(used_var.clone());
+            }
+        });
+
+        for used_var in used_vars {
+            path.push(used_var.clone());
+            if used_var == *var {
+                let path: Vec<String> = path.iter().map(|var| format!("${}", var.0)).collect();
+                return Err(DefinitionError::invalid(format!(
+                    "Variable {:?} is defined in terms of itself: {}",
+                    var.0,
+                    path.join(" -> ")
+                )));
+            }
+            if visited.insert(used_var.clone()) {
+                if let Some(used_binding) = bindings.get(&used_var) {
+                    check(bindings, var, used_binding, path, visited)?;
+                }
+            }
+            path.pop();
+        }
+
+        Ok(())
+    }
+
+    check(
+        bindings,
+        var,
+        binding,
+        &mut vec![var.clone()],
+        &mut Default::default(),
+    )
+}
+
+fn compile_rules(
+    name: String,
+    rules: Vec<SingleRule>,
+    bindings: &Map<Var, Binding>,
+    binding_right_ctxs: &Map<Var, RightCtx>,
+    left_ctxs: &mut LeftCtxs,
+    right_ctx_dfas: &mut RightCtxDFAs<DfaStateIdx>,
+    rule_sets: &mut Vec<CompiledRuleSet>,
+    inspect: bool,
+) -> Result<DFA<DfaStateIdx, SemanticActionIdx>, DefinitionError> {
+    let mut nfa: NFA<SemanticActionIdx> = NFA::new();
+
+    let mut actions: Vec<SemanticActionIdx> = Vec::with_capacity(rules.len());
+    let mut rule_regexes: Vec<String> = vec![];
+
+    for SingleRule {
+        options,
+        lhs,
+        rhs,
+        span,
+    } in rules
+    {
+        actions.push(rhs);
+        if inspect {
+            rule_regexes.push(lhs.to_string());
+        }
+
+        add_rule(
+            &mut nfa,
+            options,
+            lhs,
+            rhs,
+            bindings,
+            binding_right_ctxs,
+            left_ctxs,
+            right_ctx_dfas,
+        )
+        .map_err(|error| error.at(span))?;
+    }
+
+    let dfa = nfa_to_dfa(&nfa);
+
+    let inspection = if inspect {
+        Some(RuleSetInspection {
+            nfa,
+            n_dfa_states: dfa.n_states(),
+            rules: rule_regexes,
+        })
+    } else {
+        None
+    };
+
+    rule_sets.push(CompiledRuleSet {
+        name,
+        actions,
+        inspection,
+    });
+
+    Ok(dfa)
+}
+
+/// Adds a rule of a rule set to the rule set's NFA
+fn add_rule(
+    nfa: &mut NFA<SemanticActionIdx>,
+    options: RuleOptions,
+    lhs: RegexCtx,
+    rhs: SemanticActionIdx,
+    bindings: &Map<Var, Binding>,
+    binding_right_ctxs: &Map<Var, RightCtx>,
+    left_ctxs: &mut LeftCtxs,
+    right_ctx_dfas: &mut RightCtxDFAs<DfaStateIdx>,
+) -> Result<(), DefinitionError> {
+    let RegexCtx {
+        anchor,
+        left_ctx,
+        re,
+        right_ctx,
+    } = lhs;
+
+    if let Some(LeftCtx::Char(left_ctx)) = &left_ctx {
+        check_no_right_ctx_vars(binding_right_ctxs, left_ctx)?;
+    }
+
+    let left_ctx = left_ctx
+        .as_ref()
+        .map(|left_ctx| left_ctxs.new_left_ctx(bindings, left_ctx))
+        .transpose()?;
+
+    let right_ctx = resolve_right_ctx(binding_right_ctxs, &re, right_ctx)?;
+
+    // Shortest match rules stop at the first accepting state, before the right context could be
+    // checked
+    if options.shortest && right_ctx.is_some() {
+        return Err(DefinitionError::invalid(format!(
+            "Shortest match rule `{}` has a right context. Right contexts cannot be used in \
+            `#[shortest]` rules.",
+            re
+        )));
+    }
+
+    let right_ctx = right_ctx
+        .as_ref()
+        .map(|right_ctx| right_ctx_dfas.new_right_ctx(bindings, right_ctx))
+        .transpose()?;
+
+    nfa.add_rule(bindings, &re, anchor, left_ctx, right_ctx, options, rhs)
+}
+
+/// Returns the right context of a rule or binding with regex `re` and right context `right_ctx`.
+///
+/// When `re` is just a reference to a variable with a right context the variable's right context
+/// is inherited. Fails if the variable's right context would be combined with another right
+/// context, or a variable with a right context is used in any other position.
+fn resolve_right_ctx(
+    binding_right_ctxs: &Map<Var, RightCtx>,
+    re: &Regex,
+    right_ctx: Option<RightCtx>,
+) -> Result<Option<RightCtx>, DefinitionError> {
+    if let Some(right_ctx) = &right_ctx {
+        check_no_right_ctx_vars(binding_right_ctxs, &right_ctx.re)?;
+    }
+
+    if let Regex::Var(var) = re {
+        if let Some(var_right_ctx) = binding_right_ctxs.get(var) {
+            if right_ctx.is_some() {
+                return Err(DefinitionError::invalid(format!(
+                    "Variable {:?} has a right context, it cannot be used with another right \
+                    context",
+                    var.0
+                )));
+            }
+            return Ok(Some(var_right_ctx.clone()));
+        }
+    }
+
+    check_no_right_ctx_vars(binding_right_ctxs, re)?;
+
+    Ok(right_ctx)
+}
+
+/// Fails if `re` uses a variable with a right context.
+fn check_no_right_ctx_vars(
+    binding_right_ctxs: &Map<Var, RightCtx>,
+    re: &Regex,
+) -> Result<(), DefinitionError> {
+    let mut right_ctx_var: Option<Var> = None;
+    re.for_each_var(&mut |var| {
+        if right_ctx_var.is_none() && binding_right_ctxs.contains_key(var) {
+            right_ctx_var = Some(var.clone());
+        }
+    });
+
+    match right_ctx_var {
+        None => Ok(()),
+        Some(var) => Err(DefinitionError::invalid(format!(
+            "Variable {:?} has a right context. Variables with right contexts can only be \
+            used as the entire regex of a rule or binding, e.g. `${} => ...`",
+            var.0, var.0
+        ))),
+    }
+}
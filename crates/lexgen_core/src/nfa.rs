@@ -1,9 +1,11 @@
-#[cfg(test)]
+pub mod dot;
 pub mod simulate;
 
-use crate::ast::{Regex, Var};
+use crate::ast::{Anchor, Binding, Regex, RuleOptions, Var};
 use crate::collections::{Map, Set};
 use crate::display::HashSetDisplay;
+use crate::left_ctx::LeftCtxIdx;
+use crate::lexer_file::DefinitionError;
 use crate::range_map::{Range, RangeMap};
 use crate::regex_to_nfa;
 use crate::right_ctx::RightCtxIdx;
@@ -13,6 +15,18 @@ use crate::right_ctx::RightCtxIdx;
 pub struct NFA<A> {
     // Indexed by `StateIdx`
     states: Vec<State<A>>,
+
+    // Options of rules with non-default options, indexed by the rules' accepting states
+    rule_options: Map<StateIdx, RuleInfo>,
+}
+
+#[derive(Debug)]
+struct RuleInfo {
+    options: RuleOptions,
+
+    // States of the rule. States of a rule are allocated together, starting with the accepting
+    // state.
+    states: std::ops::Range<usize>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -28,9 +42,11 @@ struct State<A> {
     accepting: Option<AcceptingState<A>>,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct AcceptingState<A> {
     pub value: A,
+    pub anchor: Option<Anchor>,
+    pub left_ctx: Option<LeftCtxIdx>,
     pub right_ctx: Option<RightCtxIdx>,
 }
 
@@ -51,6 +67,7 @@ impl<A> NFA<A> {
     pub fn new() -> NFA<A> {
         NFA {
             states: vec![State::new()],
+            rule_options: Default::default(),
         }
     }
 
@@ -58,10 +75,31 @@ impl<A> NFA<A> {
         StateIdx(0)
     }
 
+    pub fn n_states(&self) -> usize {
+        self.states.len()
+    }
+
     pub fn get_accepting_state(&self, state: StateIdx) -> Option<&AcceptingState<A>> {
         self.states[state.0].accepting.as_ref()
     }
 
+    /// Priority of the rule with the given accepting state
+    pub fn rule_priority(&self, accepting_state: StateIdx) -> i32 {
+        self.rule_options
+            .get(&accepting_state)
+            .map(|rule| rule.options.priority)
+            .unwrap_or(0)
+    }
+
+    /// If the rule with the given accepting state is a shortest match rule, returns whether the
+    /// state is one of the rule's states
+    pub fn is_shortest_match_rule_state(&self, accepting_state: StateIdx, state: StateIdx) -> bool {
+        match self.rule_options.get(&accepting_state) {
+            Some(rule) => rule.options.shortest && rule.states.contains(&state.0),
+            None => false,
+        }
+    }
+
     pub fn char_transitions(
         &self,
         state: StateIdx,
@@ -95,21 +133,56 @@ impl<A> NFA<A> {
 
     pub fn add_regex(
         &mut self,
-        bindings: &Map<Var, Regex>,
+        bindings: &Map<Var, Binding>,
         re: &Regex,
+        anchor: Option<Anchor>,
+        left_ctx: Option<LeftCtxIdx>,
         right_ctx: Option<RightCtxIdx>,
         value: A,
-    ) {
+    ) -> Result<(), DefinitionError> {
+        self.add_rule(
+            bindings,
+            re,
+            anchor,
+            left_ctx,
+            right_ctx,
+            RuleOptions::default(),
+            value,
+        )
+    }
+
+    pub fn add_rule(
+        &mut self,
+        bindings: &Map<Var, Binding>,
+        re: &Regex,
+        anchor: Option<Anchor>,
+        left_ctx: Option<LeftCtxIdx>,
+        right_ctx: Option<RightCtxIdx>,
+        options: RuleOptions,
+        value: A,
+    ) -> Result<(), DefinitionError> {
         let re_accepting_state = self.new_state();
 
-        self.make_state_accepting(re_accepting_state, value, right_ctx);
+        self.make_state_accepting(re_accepting_state, value, anchor, left_ctx, right_ctx);
 
         let re_initial_state = self.new_state();
         let nfa_initial_state = self.initial_state();
 
         self.add_empty_transition(nfa_initial_state, re_initial_state);
 
-        regex_to_nfa::add_re(self, bindings, re, re_initial_state, re_accepting_state);
+        regex_to_nfa::add_re(self, bindings, re, re_initial_state, re_accepting_state)?;
+
+        if options != RuleOptions::default() {
+            self.rule_options.insert(
+                re_accepting_state,
+                RuleInfo {
+                    options,
+                    states: re_accepting_state.0..self.states.len(),
+                },
+            );
+        }
+
+        Ok(())
     }
 
     pub fn add_char_transition(&mut self, state: StateIdx, char: char, next: StateIdx) {
@@ -135,7 +208,7 @@ impl<A> NFA<A> {
             range_start as u32,
             range_end as u32,
             set,
-            |values_1, values_2| values_1.extend(values_2.into_iter()),
+            |values_1, values_2| values_1.extend(values_2),
         );
     }
 
@@ -148,7 +221,7 @@ impl<A> NFA<A> {
         self.states[state.0]
             .range_transitions
             .insert_ranges(ranges.into_iter(), |values_1, values_2| {
-                values_1.extend(values_2.into_iter())
+                values_1.extend(values_2)
             });
     }
 
@@ -170,10 +243,20 @@ impl<A> NFA<A> {
         assert!(not_exists, "add_end_of_input_transition");
     }
 
-    fn make_state_accepting(&mut self, state: StateIdx, value: A, right_ctx: Option<RightCtxIdx>) {
-        let old = self.states[state.0]
-            .accepting
-            .replace(AcceptingState { value, right_ctx });
+    fn make_state_accepting(
+        &mut self,
+        state: StateIdx,
+        value: A,
+        anchor: Option<Anchor>,
+        left_ctx: Option<LeftCtxIdx>,
+        right_ctx: Option<RightCtxIdx>,
+    ) {
+        let old = self.states[state.0].accepting.replace(AcceptingState {
+            value,
+            anchor,
+            left_ctx,
+            right_ctx,
+        });
 
         assert!(old.is_none(), "make_state_accepting");
     }
@@ -222,16 +305,23 @@ impl<A> Display for NFA<A> {
             match accepting {
                 Some(AcceptingState {
                     value: _,
+                    anchor,
+                    left_ctx,
                     right_ctx,
-                }) => match right_ctx {
-                    Some(right_ctx_idx) => {
-                        write!(f, "{:>4}", format!("*{}", state_idx),)?;
-                        write!(f, " (ctx {})", right_ctx_idx.as_usize())?;
+                }) => {
+                    write!(f, "{:>4}", format!("*{}", state_idx))?;
+                    match anchor {
+                        Some(Anchor::LineStart) => write!(f, " (^)")?,
+                        Some(Anchor::InputStart) => write!(f, " (^^)")?,
+                        None => {}
                     }
-                    None => {
-                        write!(f, "{:>4}", format!("*{}", state_idx))?;
+                    if let Some(left_ctx_idx) = left_ctx {
+                        write!(f, " (left ctx {})", left_ctx_idx.as_usize())?;
                     }
-                },
+                    if let Some(right_ctx_idx) = right_ctx {
+                        write!(f, " (ctx {})", right_ctx_idx.as_usize())?;
+                    }
+                }
                 None => {
                     write!(f, "{:>4}:", state_idx)?;
                 }
@@ -0,0 +1,82 @@
+use super::{State, NFA};
+
+use crate::dot::{accepting_label, char_label, range_label, DotGraph};
+
+#[cfg(test)]
+use std::fmt::Debug;
+
+impl<A> NFA<A> {
+    /// Add states and transitions of the NFA to `graph`. Node names start with `prefix`.
+    /// `initial_label` is the label of the arrow to the initial state, `value_label` gives labels
+    /// of accepting states.
+    pub fn add_to_dot(
+        &self,
+        graph: &mut DotGraph,
+        prefix: &str,
+        initial_label: &str,
+        value_label: &dyn Fn(&A) -> String,
+    ) {
+        let node = |state_idx: usize| format!("{}{}", prefix, state_idx);
+
+        for (state_idx, state) in self.states.iter().enumerate() {
+            match &state.accepting {
+                Some(accepting) => graph.state(
+                    &node(state_idx),
+                    &format!("{}\n{}", state_idx, accepting_label(accepting, value_label)),
+                    true,
+                ),
+                None => graph.state(&node(state_idx), &state_idx.to_string(), false),
+            }
+        }
+
+        graph.initial(&node(0), initial_label);
+
+        for (state_idx, state) in self.states.iter().enumerate() {
+            let State {
+                char_transitions,
+                range_transitions,
+                empty_transitions,
+                any_transitions,
+                end_of_input_transitions,
+                accepting: _,
+            } = state;
+
+            let from = node(state_idx);
+
+            for next in empty_transitions {
+                graph.edge(&from, &node(next.0), "ε");
+            }
+
+            for (char, nexts) in char_transitions {
+                for next in nexts {
+                    graph.edge(&from, &node(next.0), &char_label(*char));
+                }
+            }
+
+            for range in range_transitions.iter() {
+                for next in &range.value {
+                    graph.edge(&from, &node(next.0), &range_label(range.start, range.end));
+                }
+            }
+
+            for next in any_transitions {
+                graph.edge(&from, &node(next.0), "_");
+            }
+
+            for next in end_of_input_transitions {
+                graph.edge(&from, &node(next.0), "$");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+impl<A: Debug> NFA<A> {
+    /// DOT graph of the NFA, with accepting states labelled with the `Debug` output of their
+    /// values
+    pub fn to_dot(&self) -> String {
+        let mut graph = DotGraph::new();
+        self.add_to_dot(&mut graph, "s", "", &|value| format!("{:?}", value));
+        graph.finish()
+    }
+}
@@ -1,17 +1,25 @@
+use crate::ast::Anchor;
+use crate::left_ctx::{LeftCtxIdx, LeftCtxs};
+
+#[cfg(test)]
 use super::{AcceptingState, StateIdx, NFA};
+#[cfg(test)]
 use crate::collections::Set;
-use crate::dfa::simulate::simulate_right_ctx;
+#[cfg(test)]
 use crate::dfa::StateIdx as DfaStateIdx;
+#[cfg(test)]
 use crate::right_ctx::RightCtxDFAs;
 
 pub type Matches<'input, A> = Vec<(&'input str, A)>;
 
 pub type ErrorLoc = usize;
 
+#[cfg(test)]
 impl<A: std::fmt::Debug + Copy> NFA<A> {
     pub fn simulate<'input>(
         &self,
         input: &'input str,
+        left_ctxs: &LeftCtxs,
         right_ctx_dfas: &RightCtxDFAs<DfaStateIdx>,
     ) -> (Matches<'input, A>, Option<ErrorLoc>) {
         let mut values: Matches<'input, A> = vec![];
@@ -72,9 +80,18 @@ impl<A: std::fmt::Debug + Copy> NFA<A> {
                     let mut states_sorted: Vec<StateIdx> = states.iter().copied().collect();
                     states_sorted.sort();
                     for state in states_sorted {
-                        if let Some(AcceptingState { value, right_ctx }) =
-                            &self.states[state.0].accepting
+                        if let Some(AcceptingState {
+                            value,
+                            anchor,
+                            left_ctx,
+                            right_ctx,
+                        }) = &self.states[state.0].accepting
                         {
+                            if !check_anchor(*anchor, input, match_start)
+                                || !check_left_ctx(left_ctxs, left_ctx, input, match_start)
+                            {
+                                continue;
+                            }
                             match right_ctx {
                                 None => {
                                     last_match =
@@ -82,8 +99,7 @@ impl<A: std::fmt::Debug + Copy> NFA<A> {
                                     break;
                                 }
                                 Some(right_ctx_idx) => {
-                                    let right_ctx_dfa = right_ctx_dfas.get(right_ctx_idx);
-                                    if simulate_right_ctx(right_ctx_dfa, char_indices.clone()) {
+                                    if right_ctx_dfas.matches(right_ctx_idx, char_indices.clone()) {
                                         last_match =
                                             Some((match_start, *value, char_idx + char.len_utf8()));
                                         break;
@@ -103,17 +119,25 @@ impl<A: std::fmt::Debug + Copy> NFA<A> {
                 states_sorted.sort();
 
                 for state in states_sorted {
-                    if let Some(AcceptingState { value, right_ctx }) =
-                        &self.states[state.0].accepting
+                    if let Some(AcceptingState {
+                        value,
+                        anchor,
+                        left_ctx,
+                        right_ctx,
+                    }) = &self.states[state.0].accepting
                     {
+                        if !check_anchor(*anchor, input, match_start)
+                            || !check_left_ctx(left_ctxs, left_ctx, input, match_start)
+                        {
+                            continue;
+                        }
                         match right_ctx {
                             None => {
                                 values.push((&input[match_start..], *value));
                                 break 'outer;
                             }
                             Some(right_ctx_idx) => {
-                                let right_ctx_dfa = right_ctx_dfas.get(right_ctx_idx);
-                                if simulate_right_ctx(right_ctx_dfa, char_indices.clone()) {
+                                if right_ctx_dfas.matches(right_ctx_idx, char_indices.clone()) {
                                     values.push((&input[match_start..], *value));
                                     break 'outer;
                                 }
@@ -151,13 +175,14 @@ impl<A: std::fmt::Debug + Copy> NFA<A> {
     }
 }
 
+#[cfg(test)]
 fn next<A>(nfa: &NFA<A>, states: &Set<StateIdx>, char: char) -> Set<StateIdx> {
     let mut next_states: Set<StateIdx> = Default::default();
 
     for state in states {
         // Char transitions
         if let Some(char_nexts) = nfa.states[state.0].char_transitions.get(&char) {
-            next_states.extend(char_nexts.into_iter());
+            next_states.extend(char_nexts.iter());
         }
 
         // Range transitions
@@ -174,6 +199,7 @@ fn next<A>(nfa: &NFA<A>, states: &Set<StateIdx>, char: char) -> Set<StateIdx> {
     nfa.compute_state_closure(&next_states)
 }
 
+#[cfg(test)]
 fn next_end_of_input<A>(nfa: &NFA<A>, states: &Set<StateIdx>) -> Set<StateIdx> {
     let mut next_states: Set<StateIdx> = Default::default();
 
@@ -183,3 +209,26 @@ fn next_end_of_input<A>(nfa: &NFA<A>, states: &Set<StateIdx>) -> Set<StateIdx> {
 
     nfa.compute_state_closure(&next_states)
 }
+
+/// Check the anchor of a rule, for a match starting at byte index `match_start`
+pub fn check_anchor(anchor: Option<Anchor>, input: &str, match_start: usize) -> bool {
+    let last_char = input[..match_start].chars().next_back();
+    match anchor {
+        None => true,
+        Some(Anchor::LineStart) => matches!(last_char, None | Some('\n')),
+        Some(Anchor::InputStart) => last_char.is_none(),
+    }
+}
+
+/// Check the left context of a rule, for a match starting at byte index `match_start`
+pub fn check_left_ctx(
+    left_ctxs: &LeftCtxs,
+    left_ctx: &Option<LeftCtxIdx>,
+    input: &str,
+    match_start: usize,
+) -> bool {
+    match left_ctx {
+        None => true,
+        Some(left_ctx) => left_ctxs.matches(left_ctx, input[..match_start].chars().next_back()),
+    }
+}
@@ -6,6 +6,7 @@ use crate::range_map::{Range, RangeMap};
 use crate::dfa::StateIdx as DfaStateIdx;
 use crate::nfa::StateIdx as NfaStateIdx;
 
+use std::cmp::Reverse;
 use std::collections::hash_map::Entry;
 use std::collections::BTreeSet;
 
@@ -51,9 +52,33 @@ pub fn nfa_to_dfa<A: Clone>(nfa: &NFA<A>) -> DFA<DfaStateIdx, A> {
         let mut any_transitions: Set<NfaStateIdx> = Default::default();
         let mut end_of_input_transitions: Set<NfaStateIdx> = Default::default();
 
+        // Accepting states of rules, sorted by priority. Sort is stable, so rules with the same
+        // priority are tried in the order they're defined.
+        let mut accepting_states: Vec<(i32, NfaStateIdx)> = current_nfa_states
+            .iter()
+            .copied()
+            .filter(|nfa_state| nfa.get_accepting_state(*nfa_state).is_some())
+            .map(|nfa_state| (nfa.rule_priority(nfa_state), nfa_state))
+            .collect();
+
+        accepting_states.sort_by_key(|(priority, _)| Reverse(*priority));
+
+        for (_, nfa_state) in &accepting_states {
+            let value = nfa.get_accepting_state(*nfa_state).unwrap();
+            dfa.make_state_accepting(current_dfa_state, value.clone());
+        }
+
+        // Shortest match rules stop at the first accepting state, so we don't follow transitions
+        // of a shortest match rule in a state where the rule accepts
+        let stopped_rule_state = |nfa_state: NfaStateIdx| {
+            accepting_states.iter().any(|(_, accepting_state)| {
+                nfa.is_shortest_match_rule_state(*accepting_state, nfa_state)
+            })
+        };
+
         for nfa_state in current_nfa_states.iter().copied() {
-            if let Some(value) = nfa.get_accepting_state(nfa_state) {
-                dfa.make_state_accepting(current_dfa_state, value.clone());
+            if stopped_rule_state(nfa_state) {
+                continue;
             }
 
             // Collect char transitions
@@ -70,7 +95,7 @@ pub fn nfa_to_dfa<A: Clone>(nfa: &NFA<A>) -> DFA<DfaStateIdx, A> {
                     range.start,
                     range.end,
                     range.value.clone(),
-                    |states_1, states_2| states_1.extend(states_2.into_iter()),
+                    |states_1, states_2| states_1.extend(states_2),
                 );
             }
 
@@ -4,7 +4,7 @@ use std::mem::take;
 /// A map of inclusive ranges, with insertion and iteration operations. Insertion allows
 /// overlapping ranges. When two ranges overlap, value of the overlapping parts is the union of
 /// values of the overlapping ranges.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RangeMap<A> {
     // NB. internally we don't have any overlaps. Overlapping ranges are split into smaller
     // non-overlapping ranges.
@@ -380,7 +380,7 @@ fn insert<A: Clone>(map: &mut RangeMap<Vec<A>>, range_start: u32, range_end: u32
     map2.insert(range_start, range_end, vec![value], |_, _| panic!());
 
     map.insert_ranges(map2.into_iter(), |values_1, values_2| {
-        values_1.extend(values_2.into_iter())
+        values_1.extend(values_2)
     });
 }
 
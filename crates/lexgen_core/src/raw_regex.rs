@@ -0,0 +1,542 @@
+//! Regexes in the conventional syntax: `regex!(r"[a-zA-Z_]\w*")`
+//!
+//! Strings in `regex!(...)` in lexer definitions are parsed as regexes in the conventional
+//! POSIX/PCRE-like syntax, and converted to [`Regex`]s. Features that cannot be implemented with a DFA
+//! (backreferences, lookaround) and features with a lexgen equivalent that should be used instead
+//! (anchors, lazy quantifiers) are reported as errors.
+//!
+//! `.` matches any character except `'\n'`. `\d`, `\w`, and `\s` are the ASCII classes.
+
+use crate::ast::{Builtin, CharOrRange, CharSet, Regex};
+
+/// Parse a regex in the conventional syntax. Errors are returned as messages with the position
+/// of the error in the regex.
+pub fn parse(pattern: &str) -> Result<Regex, String> {
+    let mut parser = Parser {
+        chars: pattern.chars().collect(),
+        pos: 0,
+    };
+
+    let re = parser.alternation()?;
+
+    match parser.peek() {
+        None => Ok(re),
+        Some(')') => Err(parser.error("Unmatched `)`")),
+        Some(char) => Err(parser.error(&format!("Unexpected `{}`", char))),
+    }
+}
+
+/// Concatenate regexes, merging characters and strings into strings
+pub(crate) fn concat(re1: Regex, re2: Regex) -> Regex {
+    // Keep concatenations left associative, as in the parser
+    if let Regex::Concat(re2_1, re2_2) = re2 {
+        return concat(concat(re1, *re2_1), *re2_2);
+    }
+
+    match (literal(&re1), literal(&re2)) {
+        (Some(str1), Some(str2)) => Regex::String(str1 + &str2),
+        (None, Some(_)) => match re1 {
+            Regex::Concat(re1_1, re1_2) if literal(&re1_2).is_some() => {
+                Regex::Concat(re1_1, Box::new(concat(*re1_2, re2)))
+            }
+            re1 => Regex::Concat(Box::new(re1), Box::new(re2)),
+        },
+        _ => Regex::Concat(Box::new(re1), Box::new(re2)),
+    }
+}
+
+fn literal(re: &Regex) -> Option<String> {
+    match re {
+        Regex::Char(char) => Some(char.to_string()),
+        Regex::String(str) => Some(str.clone()),
+        _ => None,
+    }
+}
+
+/// `re` repeated `min` to `max` times, or at least `min` times when `max` is `None`
+pub(crate) fn repeat(re: Regex, min: u32, max: Option<u32>) -> Regex {
+    let mut parts: Vec<Regex> = (0..min).map(|_| re.clone()).collect();
+    match max {
+        None => parts.push(Regex::ZeroOrMore(Box::new(re))),
+        Some(max) => {
+            parts.extend((min..max).map(|_| Regex::ZeroOrOne(Box::new(re.clone()))));
+        }
+    }
+    parts
+        .into_iter()
+        .reduce(concat)
+        .unwrap_or_else(|| Regex::String(String::new()))
+}
+
+/// Regex for a POSIX character class `[:name:]`
+pub(crate) fn posix_class(name: &str) -> Option<Regex> {
+    let builtin = match name {
+        "alnum" => "ascii_alphanumeric",
+        "alpha" => "ascii_alphabetic",
+        "cntrl" => "ascii_control",
+        "digit" => "ascii_digit",
+        "graph" => "ascii_graphic",
+        "lower" => "ascii_lowercase",
+        "punct" => "ascii_punctuation",
+        "upper" => "ascii_uppercase",
+        "xdigit" => "ascii_hexdigit",
+        "blank" => return Some(char_set(&[' ', '\t'])),
+        "space" => return Some(char_set(&[' ', '\t', '\n', '\r', '\x0b', '\x0c'])),
+        "print" => return Some(Regex::CharSet(CharSet(vec![CharOrRange::Range(' ', '~')]))),
+        _ => return None,
+    };
+    Some(Regex::Builtin(Builtin(builtin.to_owned())))
+}
+
+fn char_set(chars: &[char]) -> Regex {
+    Regex::CharSet(CharSet(
+        chars.iter().copied().map(CharOrRange::Char).collect(),
+    ))
+}
+
+/// Characters of `\d`, `\w`, and `\s`
+fn class_escape_chars(class: char) -> Vec<CharOrRange> {
+    match class.to_ascii_lowercase() {
+        'd' => vec![CharOrRange::Range('0', '9')],
+        'w' => vec![
+            CharOrRange::Range('a', 'z'),
+            CharOrRange::Range('A', 'Z'),
+            CharOrRange::Range('0', '9'),
+            CharOrRange::Char('_'),
+        ],
+        's' => [' ', '\t', '\n', '\r', '\x0b', '\x0c']
+            .into_iter()
+            .map(CharOrRange::Char)
+            .collect(),
+        _ => unreachable!(),
+    }
+}
+
+fn negate(re: Regex) -> Regex {
+    Regex::Diff(Box::new(Regex::Any), Box::new(re))
+}
+
+/// An escape sequence
+enum Escape {
+    Char(char),
+
+    /// `\d`, `\w`, `\s`, and their negations
+    Class(Regex),
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let char = self.peek()?;
+        self.pos += 1;
+        Some(char)
+    }
+
+    fn eat(&mut self, char: char) -> bool {
+        if self.peek() == Some(char) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn starts_with(&self, str: &str) -> bool {
+        let mut chars = self.chars[self.pos..].iter();
+        str.chars().all(|char| chars.next() == Some(&char))
+    }
+
+    fn error(&self, message: &str) -> String {
+        self.error_at(self.pos, message)
+    }
+
+    fn error_at(&self, pos: usize, message: &str) -> String {
+        format!("{} (at character {} of the regex)", message, pos + 1)
+    }
+
+    fn alternation(&mut self) -> Result<Regex, String> {
+        let mut re = self.concatenation()?;
+        while self.eat('|') {
+            re = Regex::Or(Box::new(re), Box::new(self.concatenation()?));
+        }
+        Ok(re)
+    }
+
+    fn concatenation(&mut self) -> Result<Regex, String> {
+        let mut re: Option<Regex> = None;
+
+        while !matches!(self.peek(), None | Some('|' | ')')) {
+            let piece = self.repetition()?;
+            re = Some(match re {
+                None => piece,
+                Some(re) => concat(re, piece),
+            });
+        }
+
+        // Empty alternatives, e.g. in `a|`, match the empty string
+        Ok(re.unwrap_or_else(|| Regex::String(String::new())))
+    }
+
+    /// Parse an atom followed by quantifiers
+    fn repetition(&mut self) -> Result<Regex, String> {
+        let mut re = self.atom()?;
+
+        loop {
+            let pos = self.pos;
+            match self.peek() {
+                Some('*') => {
+                    self.next();
+                    re = Regex::ZeroOrMore(Box::new(re));
+                }
+                Some('+') => {
+                    self.next();
+                    re = Regex::OneOrMore(Box::new(re));
+                }
+                Some('?') => {
+                    self.next();
+                    re = Regex::ZeroOrOne(Box::new(re));
+                }
+                Some('{')
+                    if self
+                        .chars
+                        .get(self.pos + 1)
+                        .is_some_and(char::is_ascii_digit) =>
+                {
+                    self.next();
+                    let (min, max) = self.bounds()?;
+                    re = repeat(re, min, max);
+                }
+                _ => return Ok(re),
+            }
+
+            match self.peek() {
+                Some('?') => {
+                    return Err(self.error_at(
+                        pos,
+                        "Lazy quantifiers are not supported, use `#[shortest]` rules instead",
+                    ))
+                }
+                Some('+') => {
+                    return Err(self.error_at(pos, "Possessive quantifiers are not supported"))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Parse repetition bounds after the `{`: `n}`, `n,}`, or `n,m}`
+    fn bounds(&mut self) -> Result<(u32, Option<u32>), String> {
+        let start = self.pos - 1;
+        let min = self.number()?;
+        let max = if self.eat(',') {
+            if self.peek() == Some('}') {
+                None
+            } else {
+                Some(self.number()?)
+            }
+        } else {
+            Some(min)
+        };
+
+        if !self.eat('}') {
+            return Err(self.error("Expected `}`"));
+        }
+
+        match max {
+            Some(max) if max < min => Err(self.error_at(
+                start,
+                &format!("Invalid repetition bounds {{{},{}}}", min, max),
+            )),
+            _ => Ok((min, max)),
+        }
+    }
+
+    fn number(&mut self) -> Result<u32, String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .map_err(|_| self.error_at(start, "Expected a number"))
+    }
+
+    fn atom(&mut self) -> Result<Regex, String> {
+        let pos = self.pos;
+        let char = self.next().unwrap();
+
+        match char {
+            '(' => {
+                self.group_prefix(pos)?;
+                let re = self.alternation()?;
+                if !self.eat(')') {
+                    return Err(self.error_at(pos, "Unclosed `(`"));
+                }
+                Ok(re)
+            }
+
+            '[' => self.class(),
+
+            '.' => Ok(negate(Regex::Char('\n'))),
+
+            '\\' => match self.escape(false)? {
+                Escape::Char(char) => Ok(Regex::Char(char)),
+                Escape::Class(re) => Ok(re),
+            },
+
+            '^' | '$' => Err(self.error_at(
+                pos,
+                "Anchors are not supported in regex strings, use `^` before the regex, or `$` \
+                 for end of input",
+            )),
+
+            '*' | '+' | '?' => {
+                Err(self.error_at(pos, &format!("Nothing to repeat with `{}`", char)))
+            }
+
+            char => Ok(Regex::Char(char)),
+        }
+    }
+
+    /// Parse the part of a group after `(` that specifies the kind of the group. Non-capturing
+    /// and named groups are the same as plain groups, as lexgen regexes do not capture.
+    fn group_prefix(&mut self, pos: usize) -> Result<(), String> {
+        if !self.eat('?') {
+            return Ok(());
+        }
+
+        if self.eat(':') {
+            return Ok(());
+        }
+
+        if self.starts_with("<=") || self.starts_with("<!") {
+            return Err(self.error_at(pos, "Lookbehind is not supported, use left context (`<`)"));
+        }
+
+        if self.starts_with("=") || self.starts_with("!") {
+            return Err(self.error_at(
+                pos,
+                "Lookahead is not supported, use right context (`>` and `>!`)",
+            ));
+        }
+
+        // Named groups: `(?<name>...)`, `(?P<name>...)`, `(?'name'...)`
+        let close = if self.eat('<') || (self.eat('P') && self.eat('<')) {
+            '>'
+        } else if self.eat('\'') {
+            '\''
+        } else {
+            return Err(self.error_at(
+                pos,
+                "Inline flags and other `(?...)` groups are not supported",
+            ));
+        };
+
+        while let Some(char) = self.next() {
+            if char == close {
+                return Ok(());
+            }
+            if !(char.is_alphanumeric() || char == '_') {
+                break;
+            }
+        }
+
+        Err(self.error_at(pos, "Invalid group name"))
+    }
+
+    /// Parse a character class after the `[`
+    fn class(&mut self) -> Result<Regex, String> {
+        let start = self.pos - 1;
+        let negated = self.eat('^');
+
+        let mut chars: Vec<CharOrRange> = vec![];
+        let mut others: Vec<Regex> = vec![];
+
+        let mut first = true;
+        loop {
+            let pos = self.pos;
+            match self.peek() {
+                None => return Err(self.error_at(start, "Unclosed `[`")),
+
+                Some(']') if !first => {
+                    self.next();
+                    break;
+                }
+
+                Some('[') if self.starts_with("[:") => {
+                    self.pos += 2;
+                    let name_start = self.pos;
+                    while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+                        self.pos += 1;
+                    }
+                    let name: String = self.chars[name_start..self.pos].iter().collect();
+                    if !(self.eat(':') && self.eat(']')) {
+                        return Err(self.error_at(pos, "Invalid POSIX class"));
+                    }
+                    match posix_class(&name) {
+                        Some(Regex::CharSet(CharSet(chars_))) => chars.extend(chars_),
+                        Some(re) => others.push(re),
+                        None => {
+                            return Err(
+                                self.error_at(pos, &format!("Unknown POSIX class [:{}:]", name))
+                            )
+                        }
+                    }
+                }
+
+                Some(_) => {
+                    let char = match self.class_char()? {
+                        Escape::Char(char) => char,
+                        Escape::Class(Regex::CharSet(CharSet(chars_))) => {
+                            chars.extend(chars_);
+                            first = false;
+                            continue;
+                        }
+                        Escape::Class(re) => {
+                            others.push(re);
+                            first = false;
+                            continue;
+                        }
+                    };
+
+                    if self.peek() == Some('-')
+                        && self.chars.get(self.pos + 1).is_some_and(|c| *c != ']')
+                    {
+                        self.next();
+                        let end = match self.class_char()? {
+                            Escape::Char(char) => char,
+                            Escape::Class(_) => {
+                                return Err(self.error_at(pos, "Invalid range in character class"))
+                            }
+                        };
+                        if end < char {
+                            return Err(self.error_at(
+                                pos,
+                                &format!("Invalid range {:?}-{:?} in character class", char, end),
+                            ));
+                        }
+                        chars.push(CharOrRange::Range(char, end));
+                    } else {
+                        chars.push(CharOrRange::Char(char));
+                    }
+                }
+            }
+            first = false;
+        }
+
+        if !chars.is_empty() {
+            others.insert(0, Regex::CharSet(CharSet(chars)));
+        }
+
+        let re = others
+            .into_iter()
+            .reduce(|re1, re2| Regex::Or(Box::new(re1), Box::new(re2)))
+            .unwrap();
+
+        Ok(if negated { negate(re) } else { re })
+    }
+
+    fn class_char(&mut self) -> Result<Escape, String> {
+        match self.next() {
+            Some('\\') => self.escape(true),
+            Some(char) => Ok(Escape::Char(char)),
+            None => unreachable!(),
+        }
+    }
+
+    /// Parse an escape sequence after the `\`. In character classes `\b` is backspace.
+    fn escape(&mut self, in_class: bool) -> Result<Escape, String> {
+        let pos = self.pos - 1;
+
+        let char = match self.next() {
+            Some(char) => char,
+            None => return Err(self.error_at(pos, "Unfinished escape sequence")),
+        };
+
+        let char =
+            match char {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                'f' => '\x0c',
+                'v' => '\x0b',
+                'a' => '\x07',
+                'e' => '\x1b',
+                '0' => '\0',
+                'b' if in_class => '\x08',
+
+                'd' | 'w' | 's' => {
+                    return Ok(Escape::Class(Regex::CharSet(CharSet(class_escape_chars(
+                        char,
+                    )))))
+                }
+                'D' | 'W' | 'S' => {
+                    return Ok(Escape::Class(negate(Regex::CharSet(CharSet(
+                        class_escape_chars(char),
+                    )))))
+                }
+
+                'x' | 'u' => {
+                    let digits: String = if self.eat('{') {
+                        let start = self.pos;
+                        while self.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
+                            self.pos += 1;
+                        }
+                        let digits = self.chars[start..self.pos].iter().collect();
+                        if !self.eat('}') {
+                            return Err(self.error_at(pos, "Invalid escape sequence"));
+                        }
+                        digits
+                    } else {
+                        let len = if char == 'x' { 2 } else { 4 };
+                        let digits: String = self.chars[self.pos..]
+                            .iter()
+                            .take(len)
+                            .take_while(|c| c.is_ascii_hexdigit())
+                            .collect();
+                        if digits.len() != len {
+                            return Err(self.error_at(pos, "Invalid escape sequence"));
+                        }
+                        self.pos += len;
+                        digits
+                    };
+                    u32::from_str_radix(&digits, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or_else(|| self.error_at(pos, "Invalid escape sequence"))?
+                }
+
+                '1'..='9' | 'k' | 'g' => {
+                    return Err(self.error_at(pos, "Backreferences are not supported"))
+                }
+
+                'b' | 'B' | 'A' | 'z' | 'Z' | 'G' => {
+                    return Err(self.error_at(pos, "Anchors are not supported in regex strings"))
+                }
+
+                'p' | 'P' => return Err(self.error_at(
+                    pos,
+                    "Unicode properties are not supported, use built-in regexes (`$$alphabetic` \
+                     etc.) instead",
+                )),
+
+                char if char.is_ascii_alphanumeric() => {
+                    return Err(self.error_at(pos, &format!("Unknown escape sequence `\\{}`", char)))
+                }
+
+                char => char,
+            };
+
+        Ok(Escape::Char(char))
+    }
+}
@@ -0,0 +1,424 @@
+use crate::ast::{Binding, Builtin, CharOrRange, CharSet, Regex, Var};
+use crate::builtin::{BuiltinCharRange, BUILTIN_RANGES};
+use crate::collections::Map;
+use crate::lexer_file::DefinitionError;
+use crate::nfa::{StateIdx, NFA};
+use crate::range_map::{Range, RangeMap};
+
+pub fn add_re<A>(
+    nfa: &mut NFA<A>,
+    bindings: &Map<Var, Binding>,
+    re: &Regex,
+    current: StateIdx,
+    cont: StateIdx,
+) -> Result<(), DefinitionError> {
+    match re {
+        Regex::Builtin(builtin_name) => {
+            let builtin = get_builtin_regex(builtin_name)?;
+
+            let ranges: Vec<Range<()>> = builtin
+                .get_ranges()
+                .iter()
+                .copied()
+                .map(|(start, end)| Range {
+                    start,
+                    end,
+                    value: (),
+                })
+                .collect();
+
+            let map = RangeMap::from_non_overlapping_sorted_ranges(ranges);
+
+            nfa.add_range_transitions(current, map, cont);
+        }
+
+        Regex::Var(var) => {
+            let re = get_var(bindings, var)?;
+            add_re(nfa, bindings, re, current, cont)?;
+        }
+
+        Regex::Call(var, args) => {
+            let re = expand_call(bindings, var, args)?;
+            add_re(nfa, bindings, &re, current, cont)?;
+        }
+
+        Regex::Char(char) => {
+            nfa.add_char_transition(current, *char, cont);
+        }
+
+        Regex::String(str) => {
+            let mut iter = str.chars().peekable();
+            let mut current = current;
+            while let Some(char) = iter.next() {
+                let next = if iter.peek().is_some() {
+                    nfa.new_state()
+                } else {
+                    cont
+                };
+                nfa.add_char_transition(current, char, next);
+                current = next;
+            }
+        }
+
+        Regex::CharSet(set) if !set.is_simple() => {
+            let map = regex_to_range_map(bindings, re, CharSetUse::CharSet)?;
+            nfa.add_range_transitions(current, map, cont);
+        }
+
+        Regex::CharSet(set) => {
+            for char in &set.0 {
+                match char {
+                    CharOrRange::Char(char) => {
+                        nfa.add_char_transition(current, *char, cont);
+                    }
+                    CharOrRange::Range(range_start, range_end) => {
+                        nfa.add_range_transition(current, *range_start, *range_end, cont);
+                    }
+                    CharOrRange::Builtin(_) | CharOrRange::Var(_) => unreachable!(),
+                }
+            }
+        }
+
+        Regex::ZeroOrMore(re) => {
+            let re_init = nfa.new_state();
+            let re_cont = nfa.new_state();
+            add_re(nfa, bindings, re, re_init, re_cont)?;
+            nfa.add_empty_transition(current, cont);
+            nfa.add_empty_transition(current, re_init);
+            nfa.add_empty_transition(re_cont, cont);
+            nfa.add_empty_transition(re_cont, re_init);
+        }
+
+        Regex::OneOrMore(re) => {
+            let re_init = nfa.new_state();
+            let re_cont = nfa.new_state();
+            add_re(nfa, bindings, re, re_init, re_cont)?;
+            nfa.add_empty_transition(current, re_init);
+            nfa.add_empty_transition(re_cont, cont);
+            nfa.add_empty_transition(re_cont, re_init);
+        }
+
+        Regex::ZeroOrOne(re) => {
+            let re_init = nfa.new_state();
+            add_re(nfa, bindings, re, re_init, cont)?;
+            nfa.add_empty_transition(current, cont);
+            nfa.add_empty_transition(current, re_init);
+        }
+
+        Regex::Concat(re1, re2) => {
+            let re1_cont = nfa.new_state();
+            add_re(nfa, bindings, re1, current, re1_cont)?;
+            add_re(nfa, bindings, re2, re1_cont, cont)?;
+        }
+
+        Regex::Or(re1, re2) => {
+            let re1_init = nfa.new_state();
+            let re2_init = nfa.new_state();
+            add_re(nfa, bindings, re1, re1_init, cont)?;
+            add_re(nfa, bindings, re2, re2_init, cont)?;
+            nfa.add_empty_transition(current, re1_init);
+            nfa.add_empty_transition(current, re2_init);
+        }
+
+        Regex::Any => {
+            nfa.add_any_transition(current, cont);
+        }
+
+        Regex::EndOfInput => {
+            nfa.add_end_of_input_transition(current, cont);
+        }
+
+        Regex::Diff(_, _) => {
+            let map = regex_to_range_map(bindings, re, CharSetUse::Diff)?;
+            nfa.add_range_transitions(current, map, cont);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn get_builtin_regex(builtin: &Builtin) -> Result<BuiltinCharRange, DefinitionError> {
+    BUILTIN_RANGES
+        .iter()
+        .find_map(|(name, builtin_)| {
+            if *name == builtin.0 {
+                Some(*builtin_)
+            } else {
+                None
+            }
+        })
+        .ok_or_else(|| DefinitionError::invalid(format!("Unknown builtin regex: {}", builtin.0)))
+}
+
+/// Where a regex is used as a character set, for error messages
+#[derive(Debug, Clone, Copy)]
+pub enum CharSetUse<'a> {
+    /// Operand of `#`
+    Diff,
+
+    /// Left context: `<re> < ...`
+    LeftCtx,
+
+    /// In a character set: `[...]`
+    CharSet,
+
+    /// Variable in a character set: `[$var]`
+    CharSetVar(&'a Var),
+}
+
+impl CharSetUse<'_> {
+    /// Error for a use of `what` (e.g. strings) in the character set
+    fn not_char_set(&self, what: &str) -> DefinitionError {
+        DefinitionError::invalid(match self {
+            CharSetUse::Diff => format!("{} cannot be used in char sets (`#`)", what),
+            CharSetUse::CharSet => format!("{} cannot be used in char sets (`[...]`)", what),
+            CharSetUse::LeftCtx => format!(
+                "{} cannot be used in left contexts (`<re> < ...`), left contexts should be \
+                character sets",
+                what
+            ),
+            CharSetUse::CharSetVar(var) => format!(
+                "{} cannot be used in `${}` in `[...]`, variables in `[...]` should be \
+                character sets",
+                what, var.0
+            ),
+        })
+    }
+}
+
+pub fn regex_to_range_map(
+    bindings: &Map<Var, Binding>,
+    re: &Regex,
+    use_: CharSetUse,
+) -> Result<RangeMap<()>, DefinitionError> {
+    let map = match re {
+        Regex::Builtin(builtin) => {
+            let builtin = get_builtin_regex(builtin)?;
+            let ranges: Vec<Range<()>> = builtin
+                .get_ranges()
+                .iter()
+                .copied()
+                .map(|(start, end)| Range {
+                    start,
+                    end,
+                    value: (),
+                })
+                .collect();
+            RangeMap::from_non_overlapping_sorted_ranges(ranges)
+        }
+
+        Regex::Var(var) => {
+            let re = get_var(bindings, var)?;
+            regex_to_range_map(bindings, re, use_)?
+        }
+
+        Regex::Call(var, args) => {
+            let re = expand_call(bindings, var, args)?;
+            regex_to_range_map(bindings, &re, use_)?
+        }
+
+        Regex::Char(char) => {
+            let mut map = RangeMap::new();
+            map.insert(*char as u32, *char as u32, (), merge_values);
+            map
+        }
+
+        Regex::String(_) => return Err(use_.not_char_set("strings")),
+
+        Regex::CharSet(char_set) => {
+            let mut map = RangeMap::new();
+
+            // TODO: Quadratic behavior below, `RangeMap::insert` is O(number of ranges)
+            for char_or_range in char_set.0.iter() {
+                match char_or_range {
+                    CharOrRange::Char(char) => {
+                        map.insert(*char as u32, *char as u32, (), merge_values);
+                    }
+                    CharOrRange::Range(start, end) => {
+                        map.insert(*start as u32, *end as u32, (), merge_values);
+                    }
+                    CharOrRange::Builtin(builtin) => {
+                        let builtin_map =
+                            regex_to_range_map(bindings, &Regex::Builtin(builtin.clone()), use_)?;
+                        map.insert_ranges(builtin_map.into_iter(), merge_values);
+                    }
+                    CharOrRange::Var(var) => {
+                        let var_map = regex_to_range_map(
+                            bindings,
+                            &Regex::Var(var.clone()),
+                            CharSetUse::CharSetVar(var),
+                        )?;
+                        map.insert_ranges(var_map.into_iter(), merge_values);
+                    }
+                }
+            }
+
+            map
+        }
+
+        Regex::ZeroOrMore(_) => return Err(use_.not_char_set("`*`")),
+
+        Regex::OneOrMore(_) => return Err(use_.not_char_set("`+`")),
+
+        Regex::ZeroOrOne(_) => return Err(use_.not_char_set("`?`")),
+
+        Regex::Concat(_, _) => return Err(use_.not_char_set("concatenation (`<re1> <re2>`)")),
+
+        Regex::Or(re1, re2) => {
+            let mut map1 = regex_to_range_map(bindings, re1, use_)?;
+            let map2 = regex_to_range_map(bindings, re2, use_)?;
+
+            map1.insert_ranges(map2.into_iter(), merge_values);
+
+            map1
+        }
+
+        Regex::Any => {
+            let mut map = RangeMap::new();
+            map.insert(0, char::MAX as u32, (), merge_values);
+            map
+        }
+
+        Regex::EndOfInput => return Err(use_.not_char_set("`$`")),
+
+        Regex::Diff(re1, re2) => {
+            let mut map1 = regex_to_range_map(bindings, re1, use_)?;
+            let map2 = regex_to_range_map(bindings, re2, use_)?;
+            map1.remove_ranges(&map2);
+            map1
+        }
+    };
+
+    Ok(map)
+}
+
+fn merge_values(_val1: &mut (), _val2: ()) {}
+
+/// Returns the regex bound to a variable that is not parameterised
+fn get_var<'a>(bindings: &'a Map<Var, Binding>, var: &Var) -> Result<&'a Regex, DefinitionError> {
+    let binding = get_binding(bindings, var)?;
+
+    if !binding.params.is_empty() {
+        return Err(DefinitionError::invalid(format!(
+            "Variable {:?} is parameterised, it should be used with arguments: `${}!(...)`",
+            var.0, var.0
+        )));
+    }
+
+    Ok(&binding.re)
+}
+
+fn get_binding<'a>(
+    bindings: &'a Map<Var, Binding>,
+    var: &Var,
+) -> Result<&'a Binding, DefinitionError> {
+    bindings
+        .get(var)
+        .ok_or_else(|| DefinitionError::invalid(format!("Unbound variable {:?}", var.0)))
+}
+
+/// Expands a use of a parameterised binding: `$var!(args)`.
+///
+/// Arguments are substituted for the parameters as they are, so variables in the arguments refer
+/// to the bindings at the use site, never to the parameters of the binding.
+fn expand_call(
+    bindings: &Map<Var, Binding>,
+    var: &Var,
+    args: &[Regex],
+) -> Result<Regex, DefinitionError> {
+    let binding = get_binding(bindings, var)?;
+
+    if binding.params.is_empty() {
+        return Err(DefinitionError::invalid(format!(
+            "Variable {:?} is not parameterised, it cannot be used with arguments",
+            var.0
+        )));
+    }
+
+    if binding.params.len() != args.len() {
+        return Err(DefinitionError::invalid(format!(
+            "Variable {:?} has {} parameter(s), but it's used with {} argument(s)",
+            var.0,
+            binding.params.len(),
+            args.len()
+        )));
+    }
+
+    let args: Map<&Var, &Regex> = binding.params.iter().zip(args.iter()).collect();
+
+    substitute(&binding.re, &args)
+}
+
+/// Replaces parameters in a parameterised binding's regex with the arguments
+fn substitute(re: &Regex, args: &Map<&Var, &Regex>) -> Result<Regex, DefinitionError> {
+    let substitute_box = |re: &Regex| substitute(re, args).map(Box::new);
+
+    Ok(match re {
+        Regex::Var(var) => match args.get(var) {
+            Some(arg) => (*arg).clone(),
+            None => re.clone(),
+        },
+
+        Regex::Call(var, call_args) => {
+            if args.contains_key(var) {
+                return Err(DefinitionError::invalid(format!(
+                    "Parameter {:?} is not parameterised, it cannot be used with arguments",
+                    var.0
+                )));
+            }
+            Regex::Call(
+                var.clone(),
+                call_args
+                    .iter()
+                    .map(|arg| substitute(arg, args))
+                    .collect::<Result<_, _>>()?,
+            )
+        }
+
+        Regex::CharSet(CharSet(chars)) => {
+            let mut new_chars = Vec::with_capacity(chars.len());
+            for char in chars {
+                match char {
+                    CharOrRange::Var(var) if args.contains_key(var) => {
+                        let arg = args[var];
+                        if !char_set_items(arg, &mut new_chars) {
+                            return Err(DefinitionError::invalid(format!(
+                                "Parameter {:?} is used in a character set, but argument `{}` is \
+                                not a character set",
+                                var.0, arg
+                            )));
+                        }
+                    }
+                    _ => new_chars.push(char.clone()),
+                }
+            }
+            Regex::CharSet(CharSet(new_chars))
+        }
+
+        Regex::ZeroOrMore(re) => Regex::ZeroOrMore(substitute_box(re)?),
+        Regex::OneOrMore(re) => Regex::OneOrMore(substitute_box(re)?),
+        Regex::ZeroOrOne(re) => Regex::ZeroOrOne(substitute_box(re)?),
+        Regex::Concat(re1, re2) => Regex::Concat(substitute_box(re1)?, substitute_box(re2)?),
+        Regex::Or(re1, re2) => Regex::Or(substitute_box(re1)?, substitute_box(re2)?),
+        Regex::Diff(re1, re2) => Regex::Diff(substitute_box(re1)?, substitute_box(re2)?),
+
+        Regex::Builtin(_) | Regex::Char(_) | Regex::String(_) | Regex::Any | Regex::EndOfInput => {
+            re.clone()
+        }
+    })
+}
+
+/// Adds character set elements matching the same characters as `re` to `chars`. Returns `false`
+/// if `re` cannot be written as character set elements.
+fn char_set_items(re: &Regex, chars: &mut Vec<CharOrRange>) -> bool {
+    match re {
+        Regex::Char(char) => chars.push(CharOrRange::Char(*char)),
+        Regex::CharSet(CharSet(set_chars)) => chars.extend(set_chars.iter().cloned()),
+        Regex::Builtin(builtin) => chars.push(CharOrRange::Builtin(builtin.clone())),
+        Regex::Var(var) => chars.push(CharOrRange::Var(var.clone())),
+        Regex::Any => chars.push(CharOrRange::Range('\0', char::MAX)),
+        Regex::Or(re1, re2) => return char_set_items(re1, chars) && char_set_items(re2, chars),
+        _ => return false,
+    }
+    true
+}
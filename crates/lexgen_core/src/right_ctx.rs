@@ -0,0 +1,122 @@
+//! Stuff related to right contexts
+//!
+//! A right context is a limited version of lookahead. A rule can have at most one right context.
+//! When a rule has right context, after the regex for the rule matches, we run the DFA for the
+//! right context with cloned input stream. Only if it matches we consider the the rule as a match.
+//! This provides a simple "lookahead" support, which should be good enough when lexing programming
+//! languages.
+//!
+//! A negated right context (`>! re`) is the opposite: the rule is considered as a match only if the
+//! DFA for the right context fails to match, which includes the case where the input ends before
+//! the DFA can match.
+//!
+//! Right context DFAs are minimized, and equal right contexts share one DFA, so rules with the
+//! same right context use the same function in the generated code.
+
+use crate::ast::{Binding, RightCtx, Var};
+use crate::collections::Map;
+use crate::dfa::dot::DotTrans;
+use crate::dfa::minimize::minimize_right_ctx;
+use crate::dfa::simplify::{simplify, Trans};
+use crate::dfa::{StateIdx, DFA};
+use crate::dot::DotGraph;
+use crate::lexer_file::DefinitionError;
+use crate::nfa::NFA;
+use crate::nfa_to_dfa::nfa_to_dfa;
+
+#[derive(Debug, Clone)]
+pub struct RightCtxDFAs<S> {
+    dfas: Vec<RightCtxDFA<S>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RightCtxDFA<S> {
+    pub dfa: DFA<S, ()>,
+    pub negated: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RightCtxIdx(usize);
+
+impl RightCtxIdx {
+    pub fn as_usize(&self) -> usize {
+        self.0
+    }
+}
+
+impl<A> RightCtxDFAs<A> {
+    pub fn new() -> Self {
+        RightCtxDFAs { dfas: vec![] }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (RightCtxIdx, &RightCtxDFA<A>)> {
+        self.dfas
+            .iter()
+            .enumerate()
+            .map(|(i, dfa)| (RightCtxIdx(i), dfa))
+    }
+}
+
+impl<T: DotTrans<()>> RightCtxDFAs<T> {
+    /// Add right context DFAs to `graph`, each in a separate cluster
+    pub fn add_to_dot(&self, graph: &mut DotGraph) {
+        for (right_ctx_idx, RightCtxDFA { dfa, negated }) in self.dfas.iter().enumerate() {
+            let label = if *negated {
+                format!("negated right ctx {}", right_ctx_idx)
+            } else {
+                format!("right ctx {}", right_ctx_idx)
+            };
+            let prefix = format!("ctx{}_", right_ctx_idx);
+            graph.begin_cluster(&format!("ctx{}", right_ctx_idx), &label);
+            dfa.add_to_dot(graph, &prefix, &[], &|()| String::new());
+            graph.end_cluster();
+        }
+    }
+}
+
+impl RightCtxDFAs<StateIdx> {
+    pub fn new_right_ctx(
+        &mut self,
+        bindings: &Map<Var, Binding>,
+        right_ctx: &RightCtx,
+    ) -> Result<RightCtxIdx, DefinitionError> {
+        let RightCtx { re, negated } = right_ctx;
+
+        let mut nfa: NFA<()> = NFA::new();
+        nfa.add_regex(bindings, re, None, None, None, ())?;
+
+        let right_ctx_dfa = RightCtxDFA {
+            dfa: minimize_right_ctx(&nfa_to_dfa(&nfa)),
+            negated: *negated,
+        };
+
+        Ok(
+            match self.dfas.iter().position(|dfa| *dfa == right_ctx_dfa) {
+                Some(idx) => RightCtxIdx(idx),
+                None => {
+                    let idx = self.dfas.len();
+                    self.dfas.push(right_ctx_dfa);
+                    RightCtxIdx(idx)
+                }
+            },
+        )
+    }
+
+    pub fn matches(&self, right_ctx: &RightCtxIdx, char_indices: std::str::CharIndices) -> bool {
+        let RightCtxDFA { dfa, negated } = &self.dfas[right_ctx.as_usize()];
+        crate::dfa::simulate::simulate_right_ctx(dfa, char_indices) != *negated
+    }
+
+    pub fn simplify(self) -> RightCtxDFAs<Trans<()>> {
+        RightCtxDFAs {
+            dfas: self
+                .dfas
+                .into_iter()
+                .map(|RightCtxDFA { dfa, negated }| RightCtxDFA {
+                    dfa: simplify::<(), ()>(dfa, &mut Default::default()),
+                    negated,
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,313 @@
+//! Lexers built and run at runtime
+//!
+//! A [`RuntimeLexer`] is compiled from a lexer definition in a string, or from rules added with a
+//! [`RuntimeLexerBuilder`], to the same DFA as `lexer!`, and is run by interpreting the DFA. Rules
+//! are matched the same way as in generated lexers: longest match first, then rule priority, with
+//! anchors and contexts.
+//!
+//! Semantic actions are not run. Rules without a right-hand side (`<regex>,`) skip the matched
+//! input, other rules yield a [`Token`] with the index of the rule in its rule set. Since rule sets
+//! can only be switched in semantic actions, a runtime lexer runs a single rule set. For the same
+//! reason left context predicates (`{ ... } <`), which are Rust code, are not supported.
+
+use crate::ast::{RuleKind, RuleRhs};
+use crate::build::Error;
+use crate::builder::{Definition, IntoRegexCtx, IntoRule};
+use crate::collections::Map;
+use crate::dfa::simulate::LongestMatch;
+use crate::dfa::{StateIdx as DfaStateIdx, DFA};
+use crate::left_ctx::LeftCtxs;
+use crate::lexer_file::{parse_definition, DefinitionError};
+use crate::right_ctx::RightCtxDFAs;
+use crate::semantic_action_table::SemanticActionIdx;
+use crate::{compile_lexer, CompiledLexer};
+
+use std::fmt;
+use std::ops::Range;
+
+/// A lexer compiled at runtime
+pub struct RuntimeLexer {
+    /// DFA of all rule sets
+    dfa: DFA<DfaStateIdx, SemanticActionIdx>,
+
+    left_ctxs: LeftCtxs,
+    right_ctx_dfas: RightCtxDFAs<DfaStateIdx>,
+
+    /// Rule set names and initial states, in the order they're defined
+    rule_sets: Vec<(String, DfaStateIdx)>,
+
+    /// Maps semantic actions to their rules
+    rules: Map<SemanticActionIdx, RuleInfo>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RuleInfo {
+    /// Index of the rule in its rule set
+    rule_idx: usize,
+
+    /// Whether the rule skips the matched input
+    skip: bool,
+}
+
+/// A token yielded by a [`RuntimeLexer`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    /// Index of the matched rule in its rule set, starting from 0
+    pub rule: usize,
+
+    /// Byte range of the token in the input
+    pub span: Range<usize>,
+}
+
+/// Error raised when no rule matches the input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidToken {
+    /// Byte index of the start of the input that couldn't be matched
+    pub location: usize,
+}
+
+impl fmt::Display for InvalidToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid token at byte {}", self.location)
+    }
+}
+
+impl std::error::Error for InvalidToken {}
+
+impl RuntimeLexer {
+    /// Compile a lexer definition. `definition` has the same syntax as the body of
+    /// `lexer! { ... }`. Right-hand sides of rules are parsed, but not used.
+    pub fn new(definition: &str) -> Result<RuntimeLexer, Error> {
+        let (lexer, semantic_action_table) = parse_definition(definition)?;
+        Ok(RuntimeLexer::from_compiled(compile_lexer(
+            lexer,
+            semantic_action_table,
+            false,
+        )?)?)
+    }
+
+    /// Create a builder for adding rules in code
+    pub fn builder() -> RuntimeLexerBuilder {
+        RuntimeLexerBuilder::new()
+    }
+
+    fn from_compiled(lexer: CompiledLexer) -> Result<RuntimeLexer, DefinitionError> {
+        if lexer.left_ctxs.has_preds() {
+            return Err(DefinitionError::invalid(
+                "Left context predicates (`{ ... } <`) cannot be used in runtime lexers, as they \
+                are Rust code"
+                    .to_owned(),
+            ));
+        }
+
+        let CompiledLexer {
+            semantic_action_table,
+            dfa,
+            rule_set_states,
+            left_ctxs,
+            right_ctx_dfas,
+            rule_sets: compiled_rule_sets,
+            ..
+        } = lexer;
+
+        let mut rule_sets = Vec::with_capacity(compiled_rule_sets.len());
+        let mut rules: Map<SemanticActionIdx, RuleInfo> = Default::default();
+
+        for rule_set in compiled_rule_sets {
+            for (rule_idx, action) in rule_set.actions.iter().enumerate() {
+                let skip = matches!(semantic_action_table.get(*action), RuleRhs::None);
+                rules.insert(*action, RuleInfo { rule_idx, skip });
+            }
+            let initial_state = rule_set_states[&rule_set.name];
+            rule_sets.push((rule_set.name, initial_state));
+        }
+
+        Ok(RuntimeLexer {
+            dfa,
+            left_ctxs,
+            right_ctx_dfas,
+            rule_sets,
+            rules,
+        })
+    }
+
+    /// Names of the rule sets, in the order they're defined
+    pub fn rule_set_names(&self) -> impl Iterator<Item = &str> {
+        self.rule_sets.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Lex `input` with the `Init` rule set
+    pub fn tokens<'lexer, 'input>(&'lexer self, input: &'input str) -> Tokens<'lexer, 'input> {
+        self.tokens_in("Init", input).unwrap()
+    }
+
+    /// Lex `input` with the given rule set. Returns `None` if the rule set doesn't exist.
+    pub fn tokens_in<'lexer, 'input>(
+        &'lexer self,
+        rule_set: &str,
+        input: &'input str,
+    ) -> Option<Tokens<'lexer, 'input>> {
+        let (_, initial_state) = self.rule_sets.iter().find(|(name, _)| name == rule_set)?;
+        Some(Tokens {
+            lexer: self,
+            initial_state: *initial_state,
+            input,
+            pos: 0,
+            done: false,
+        })
+    }
+}
+
+/// Iterator of tokens in an input, returned by [`RuntimeLexer::tokens`]. After an error the
+/// iterator yields `None`.
+pub struct Tokens<'lexer, 'input> {
+    lexer: &'lexer RuntimeLexer,
+    initial_state: DfaStateIdx,
+    input: &'input str,
+
+    /// Byte index of the rest of the input
+    pos: usize,
+
+    /// Whether end-of-input is handled, or an error is raised
+    done: bool,
+}
+
+impl<'lexer, 'input> Iterator for Tokens<'lexer, 'input> {
+    type Item = Result<Token, InvalidToken>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let lexer = self.lexer;
+
+        // The `Init` rule set drops input matched by skipping rules. In other rule sets skipped
+        // input becomes a part of the next token, as in generated lexers.
+        let in_init = self.initial_state == lexer.dfa.initial_state();
+
+        let mut token_start = self.pos;
+
+        loop {
+            if self.done {
+                return None;
+            }
+
+            if in_init {
+                token_start = self.pos;
+            }
+
+            let LongestMatch {
+                value,
+                end,
+                end_of_input,
+            } = match lexer.dfa.longest_match(
+                self.initial_state,
+                self.input,
+                self.pos,
+                &lexer.left_ctxs,
+                &lexer.right_ctx_dfas,
+            ) {
+                Some(match_) => match_,
+                None => {
+                    self.done = true;
+
+                    // `Init` stops at the end of the input, other rule sets should match
+                    // end-of-input with a `$` rule
+                    if in_init && self.pos == self.input.len() {
+                        return None;
+                    }
+
+                    return Some(Err(InvalidToken {
+                        location: token_start,
+                    }));
+                }
+            };
+
+            self.pos = end;
+            self.done = end_of_input;
+
+            let RuleInfo { rule_idx, skip } = lexer.rules[&value];
+
+            if !skip {
+                return Some(Ok(Token {
+                    rule: rule_idx,
+                    span: token_start..end,
+                }));
+            }
+        }
+    }
+}
+
+/// Builds a [`RuntimeLexer`] from rules added in code. Regexes are strings in `lexer!` syntax, or
+/// built with the types in [`builder`](crate::builder).
+///
+/// ```
+/// use lexgen_core::builder::Regex;
+///
+/// let lexer = lexgen_core::runtime::RuntimeLexer::builder()
+///     .binding("digit", "['0'-'9']")
+///     .skip("[' ' '\\n']+")
+///     .rule("$digit+")
+///     .rule(Regex::char('+'))
+///     .build()
+///     .unwrap();
+///
+/// let rules: Vec<usize> = lexer.tokens("1 + 2").map(|token| token.unwrap().rule).collect();
+/// assert_eq!(rules, vec![1, 2, 1]);
+/// ```
+#[derive(Debug, Default)]
+pub struct RuntimeLexerBuilder {
+    definition: Definition,
+}
+
+impl RuntimeLexerBuilder {
+    /// Create a builder. Rules added before a [`rule_set`](Self::rule_set) call are added to the
+    /// `Init` rule set.
+    pub fn new() -> RuntimeLexerBuilder {
+        Default::default()
+    }
+
+    /// Bind a regex to a variable, to be used in rules as `$name`. Bindings can be used in all
+    /// rule sets, and in bindings added after them.
+    pub fn binding<R: IntoRegexCtx>(mut self, name: &str, re: R) -> RuntimeLexerBuilder {
+        self.definition.binding(name, re.into_regex_ctx());
+        self
+    }
+
+    /// Start a new rule set. The first rule set should be `Init`.
+    pub fn rule_set(mut self, name: &str) -> RuntimeLexerBuilder {
+        self.definition.rule_set(name);
+        self
+    }
+
+    /// Add a rule that yields a token. The rule's index in its rule set is the index of the
+    /// tokens it yields.
+    pub fn rule<R: IntoRule>(mut self, rule: R) -> RuntimeLexerBuilder {
+        let rhs = RuleRhs::Rhs {
+            expr: syn::parse_quote!(()),
+            kind: RuleKind::Simple,
+        };
+        self.definition.rule(rule.into_rule(), Ok(rhs));
+        self
+    }
+
+    /// Add a rule that skips the matched input. Skipping rules have indices as well, to keep
+    /// rule indices the same as in a lexer definition with the same rules.
+    pub fn skip<R: IntoRule>(mut self, rule: R) -> RuntimeLexerBuilder {
+        self.definition.rule(rule.into_rule(), Ok(RuleRhs::None));
+        self
+    }
+
+    pub fn build(self) -> Result<RuntimeLexer, Error> {
+        let (lexer, semantic_action_table) = self.definition.into_lexer(
+            false,
+            syn::parse_quote!(Lexer),
+            None,
+            syn::parse_quote!(()),
+            None,
+        )?;
+
+        Ok(RuntimeLexer::from_compiled(compile_lexer(
+            lexer,
+            semantic_action_table,
+            false,
+        )?)?)
+    }
+}
@@ -18,6 +18,10 @@ impl SemanticActionTable {
         SemanticActionIdx(idx)
     }
 
+    pub fn get(&self, idx: SemanticActionIdx) -> &RuleRhs {
+        &self.table[idx.0]
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = (SemanticActionIdx, &RuleRhs)> {
         self.table
             .iter()
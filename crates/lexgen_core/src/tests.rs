@@ -0,0 +1,2035 @@
+use crate::ast::{Anchor, Binding, CharOrRange, CharSet, LeftCtx, Regex, RightCtx, Var};
+use crate::collections::Map;
+use crate::dfa::StateIdx as DfaStateIdx;
+use crate::left_ctx::LeftCtxs;
+use crate::lexer_file::locate_parse_error;
+use crate::nfa::simulate::{ErrorLoc, Matches};
+use crate::nfa::NFA;
+use crate::nfa_to_dfa::nfa_to_dfa;
+use crate::right_ctx::RightCtxDFAs;
+
+fn test_simulate<'input, A: Copy + std::fmt::Debug + Eq>(
+    nfa: &NFA<A>,
+    test_cases: Vec<(&'input str, Matches<'input, A>, Option<ErrorLoc>)>,
+) {
+    test_simulate_ctxs(nfa, &LeftCtxs::new(), &RightCtxDFAs::new(), test_cases)
+}
+
+fn test_simulate_right_ctx<'input, A: Copy + std::fmt::Debug + Eq>(
+    nfa: &NFA<A>,
+    right_ctx_dfas: &RightCtxDFAs<DfaStateIdx>,
+    test_cases: Vec<(&'input str, Matches<'input, A>, Option<ErrorLoc>)>,
+) {
+    test_simulate_ctxs(nfa, &LeftCtxs::new(), right_ctx_dfas, test_cases)
+}
+
+fn test_simulate_ctxs<'input, A: Copy + std::fmt::Debug + Eq>(
+    nfa: &NFA<A>,
+    left_ctxs: &LeftCtxs,
+    right_ctx_dfas: &RightCtxDFAs<DfaStateIdx>,
+    test_cases: Vec<(&'input str, Matches<'input, A>, Option<ErrorLoc>)>,
+) {
+    println!("NFA=\n{}", nfa);
+
+    let dfa = nfa_to_dfa(nfa);
+
+    println!("DFA=\n{}", dfa);
+
+    for (str, expected_matches, expected_error) in test_cases {
+        let expected = (expected_matches, expected_error);
+
+        assert_eq!(
+            &nfa.simulate(str, left_ctxs, right_ctx_dfas),
+            &expected,
+            "NFA simulation failed for string: {:?}",
+            str
+        );
+
+        assert_eq!(
+            dfa.simulate(str, left_ctxs, right_ctx_dfas),
+            expected,
+            "DFA simulation failed for string: {:?}",
+            str
+        );
+    }
+}
+
+#[test]
+fn simulate_backtracking() {
+    let mut nfa: NFA<usize> = NFA::new();
+
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::Concat(
+            Box::new(Regex::OneOrMore(Box::new(Regex::Char('a')))),
+            Box::new(Regex::Char('b')),
+        ),
+        None,
+        None,
+        None,
+        1,
+    )
+    .unwrap();
+
+    nfa.add_regex(&Default::default(), &Regex::Char('a'), None, None, None, 2)
+        .unwrap();
+
+    test_simulate(
+        &nfa,
+        vec![
+            ("a", vec![("a", 2)], None),
+            ("aa", vec![("a", 2), ("a", 2)], None),
+            ("aab", vec![("aab", 1)], None),
+        ],
+    );
+}
+
+#[test]
+fn issue_16() {
+    let mut nfa: NFA<usize> = NFA::new();
+
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::String("xyzxyz".to_owned()),
+        None,
+        None,
+        None,
+        1,
+    )
+    .unwrap();
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::String("xyz".to_owned()),
+        None,
+        None,
+        None,
+        2,
+    )
+    .unwrap();
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::String("xya".to_owned()),
+        None,
+        None,
+        None,
+        3,
+    )
+    .unwrap();
+
+    test_simulate(
+        &nfa,
+        vec![
+            ("xyzxya", vec![("xyz", 2), ("xya", 3)], None),
+            ("xyzxyz", vec![("xyzxyz", 1)], None),
+        ],
+    );
+}
+
+#[test]
+fn stuck_1() {
+    let nfa: NFA<usize> = NFA::new();
+    test_simulate(&nfa, vec![("a", vec![], Some(0))]);
+}
+
+#[test]
+fn stuck_2() {
+    let mut nfa: NFA<usize> = NFA::new();
+
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::String("ab".to_owned()),
+        None,
+        None,
+        None,
+        1,
+    )
+    .unwrap();
+
+    test_simulate(&nfa, vec![("aba", vec![("ab", 1)], Some(2))]);
+}
+
+#[test]
+fn stuck_3() {
+    let mut nfa: NFA<usize> = NFA::new();
+
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::String("aaab".to_owned()),
+        None,
+        None,
+        None,
+        1,
+    )
+    .unwrap();
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::String("a".to_owned()),
+        None,
+        None,
+        None,
+        2,
+    )
+    .unwrap();
+
+    test_simulate(&nfa, vec![("aaabb", vec![("aaab", 1)], Some(4))]);
+}
+
+#[test]
+fn simulate_char() {
+    let re = Regex::Char('a');
+    let mut nfa: NFA<usize> = NFA::new();
+    nfa.add_regex(&Default::default(), &re, None, None, None, 1)
+        .unwrap();
+
+    test_simulate(
+        &nfa,
+        vec![
+            ("aa", vec![("a", 1), ("a", 1)], None),
+            ("b", vec![], Some(0)),
+        ],
+    );
+}
+
+#[test]
+fn simulate_string() {
+    let re = Regex::String("ab".to_owned());
+    let mut nfa: NFA<usize> = NFA::new();
+    nfa.add_regex(&Default::default(), &re, None, None, None, 1)
+        .unwrap();
+
+    test_simulate(
+        &nfa,
+        vec![
+            ("a", vec![], Some(0)),
+            ("ab", vec![("ab", 1)], None),
+            ("abc", vec![("ab", 1)], Some(2)),
+        ],
+    );
+}
+
+#[test]
+fn simulate_char_set_char() {
+    let re = Regex::CharSet(CharSet(vec![
+        CharOrRange::Char('a'),
+        CharOrRange::Char('b'),
+    ]));
+    let mut nfa: NFA<usize> = NFA::new();
+    nfa.add_regex(&Default::default(), &re, None, None, None, 1)
+        .unwrap();
+
+    test_simulate(
+        &nfa,
+        vec![
+            ("a", vec![("a", 1)], None),
+            ("b", vec![("b", 1)], None),
+            ("ab", vec![("a", 1), ("b", 1)], None),
+            ("ba", vec![("b", 1), ("a", 1)], None),
+        ],
+    );
+}
+
+#[test]
+fn simulate_char_set_range() {
+    let re = Regex::CharSet(CharSet(vec![
+        CharOrRange::Char('a'),
+        CharOrRange::Char('b'),
+        CharOrRange::Range('0', '9'),
+    ]));
+    let mut nfa: NFA<usize> = NFA::new();
+    nfa.add_regex(&Default::default(), &re, None, None, None, 1)
+        .unwrap();
+
+    test_simulate(
+        &nfa,
+        vec![("ab09", vec![("a", 1), ("b", 1), ("0", 1), ("9", 1)], None)],
+    );
+}
+
+#[test]
+fn simulate_zero_or_more() {
+    let re = Regex::ZeroOrMore(Box::new(Regex::Char('a')));
+    let mut nfa: NFA<usize> = NFA::new();
+    nfa.add_regex(&Default::default(), &re, None, None, None, 1)
+        .unwrap();
+
+    test_simulate(
+        &nfa,
+        vec![
+            // TODO
+            // ("", vec![], None),
+            ("a", vec![("a", 1)], None),
+            ("aa", vec![("aa", 1)], None),
+            ("aab", vec![("aa", 1)], Some(2)),
+        ],
+    );
+}
+
+#[test]
+fn simulate_one_or_more() {
+    let re = Regex::OneOrMore(Box::new(Regex::Char('a')));
+    let mut nfa: NFA<usize> = NFA::new();
+    nfa.add_regex(&Default::default(), &re, None, None, None, 1)
+        .unwrap();
+
+    test_simulate(
+        &nfa,
+        vec![
+            ("", vec![], Some(0)),
+            ("a", vec![("a", 1)], None),
+            ("aa", vec![("aa", 1)], None),
+            ("aab", vec![("aa", 1)], Some(2)),
+        ],
+    );
+}
+
+#[test]
+fn simulate_zero_or_one() {
+    let re = Regex::ZeroOrOne(Box::new(Regex::Char('a')));
+    let mut nfa: NFA<usize> = NFA::new();
+    nfa.add_regex(&Default::default(), &re, None, None, None, 1)
+        .unwrap();
+
+    test_simulate(
+        &nfa,
+        vec![
+            ("", vec![], Some(0)),
+            ("a", vec![("a", 1)], None),
+            ("aa", vec![("a", 1), ("a", 1)], None),
+            ("aab", vec![("a", 1), ("a", 1)], Some(2)),
+        ],
+    );
+}
+
+#[test]
+fn simulate_concat() {
+    let re = Regex::Concat(Box::new(Regex::Char('a')), Box::new(Regex::Char('b')));
+    let mut nfa: NFA<usize> = NFA::new();
+    nfa.add_regex(&Default::default(), &re, None, None, None, 1)
+        .unwrap();
+
+    test_simulate(
+        &nfa,
+        vec![
+            ("a", vec![], Some(0)),
+            ("ab", vec![("ab", 1)], None),
+            ("aba", vec![("ab", 1)], Some(2)),
+        ],
+    );
+}
+
+#[test]
+fn simulate_or() {
+    let re = Regex::Or(Box::new(Regex::Char('a')), Box::new(Regex::Char('b')));
+    let mut nfa: NFA<usize> = NFA::new();
+    nfa.add_regex(&Default::default(), &re, None, None, None, 1)
+        .unwrap();
+
+    test_simulate(
+        &nfa,
+        vec![
+            ("a", vec![("a", 1)], None),
+            ("b", vec![("b", 1)], None),
+            ("ab", vec![("a", 1), ("b", 1)], None),
+        ],
+    );
+}
+
+#[test]
+fn simulate_or_one_or_more_char() {
+    let re = Regex::Or(
+        Box::new(Regex::OneOrMore(Box::new(Regex::Char('a')))),
+        Box::new(Regex::Char('b')),
+    );
+    let mut nfa: NFA<usize> = NFA::new();
+    nfa.add_regex(&Default::default(), &re, None, None, None, 1)
+        .unwrap();
+
+    test_simulate(
+        &nfa,
+        vec![
+            ("a", vec![("a", 1)], None),
+            ("b", vec![("b", 1)], None),
+            ("aa", vec![("aa", 1)], None),
+        ],
+    );
+}
+
+#[test]
+fn simulate_multiple_accepting_states_1() {
+    let re1 = Regex::String("aaaa".to_owned());
+    let re2 = Regex::String("aaab".to_owned());
+    let mut nfa: NFA<usize> = NFA::new();
+    nfa.add_regex(&Default::default(), &re1, None, None, None, 1)
+        .unwrap();
+    nfa.add_regex(&Default::default(), &re2, None, None, None, 2)
+        .unwrap();
+
+    test_simulate(
+        &nfa,
+        vec![
+            ("aaaa", vec![("aaaa", 1)], None),
+            ("aaab", vec![("aaab", 2)], None),
+            ("aaac", vec![], Some(0)),
+        ],
+    );
+}
+
+#[test]
+fn multiple_accepting_states_2() {
+    let re1 = Regex::Or(
+        Box::new(Regex::OneOrMore(Box::new(Regex::Char('a')))),
+        Box::new(Regex::Char('b')),
+    );
+    let re2 = Regex::CharSet(CharSet(vec![CharOrRange::Range('0', '9')]));
+    let mut nfa: NFA<usize> = NFA::new();
+    nfa.add_regex(&Default::default(), &re1, None, None, None, 1)
+        .unwrap();
+    nfa.add_regex(&Default::default(), &re2, None, None, None, 2)
+        .unwrap();
+
+    test_simulate(
+        &nfa,
+        vec![
+            ("b", vec![("b", 1)], None),
+            ("a", vec![("a", 1)], None),
+            ("aa", vec![("aa", 1)], None),
+            ("0", vec![("0", 2)], None),
+        ],
+    );
+}
+
+#[test]
+fn simulate_variables() {
+    let mut bindings: Map<Var, Binding> = Default::default();
+    bindings.insert(
+        Var("initial".to_owned()),
+        Binding {
+            params: vec![],
+            re: Regex::CharSet(CharSet(vec![CharOrRange::Range('a', 'z')])),
+        },
+    );
+    bindings.insert(
+        Var("subsequent".to_owned()),
+        Binding {
+            params: vec![],
+            re: Regex::CharSet(CharSet(vec![
+                CharOrRange::Range('a', 'z'),
+                CharOrRange::Range('A', 'Z'),
+                CharOrRange::Range('0', '9'),
+                CharOrRange::Char('-'),
+                CharOrRange::Char('_'),
+            ])),
+        },
+    );
+    let re = Regex::Concat(
+        Box::new(Regex::Var(Var("initial".to_owned()))),
+        Box::new(Regex::ZeroOrMore(Box::new(Regex::Var(Var(
+            "subsequent".to_owned()
+        ))))),
+    );
+    let mut nfa: NFA<usize> = NFA::new();
+    nfa.add_regex(&bindings, &re, None, None, None, 1).unwrap();
+
+    test_simulate(
+        &nfa,
+        vec![
+            ("a", vec![("a", 1)], None),
+            ("aA", vec![("aA", 1)], None),
+            ("aA123-a", vec![("aA123-a", 1)], None),
+        ],
+    );
+}
+
+#[test]
+fn zero_or_more_concat_confusion_1() {
+    let mut nfa: NFA<usize> = NFA::new();
+
+    let re = Regex::Concat(
+        Box::new(Regex::ZeroOrMore(Box::new(Regex::Char('a')))),
+        Box::new(Regex::Char('a')),
+    );
+
+    nfa.add_regex(&Default::default(), &re, None, None, None, 1)
+        .unwrap();
+
+    test_simulate(
+        &nfa,
+        vec![("a", vec![("a", 1)], None), ("aa", vec![("aa", 1)], None)],
+    );
+}
+
+#[test]
+fn zero_or_more_concat_confusion_2() {
+    let mut nfa: NFA<usize> = NFA::new();
+
+    let re = Regex::Concat(
+        Box::new(Regex::ZeroOrMore(Box::new(Regex::Char('a')))),
+        Box::new(Regex::String("ab".to_owned())),
+    );
+
+    nfa.add_regex(&Default::default(), &re, None, None, None, 1)
+        .unwrap();
+
+    test_simulate(
+        &nfa,
+        vec![
+            ("ab", vec![("ab", 1)], None),
+            ("aab", vec![("aab", 1)], None),
+        ],
+    );
+}
+
+#[test]
+fn zero_or_more_concat_confusion_3() {
+    let mut nfa: NFA<usize> = NFA::new();
+
+    let re = Regex::Concat(
+        Box::new(Regex::Concat(
+            Box::new(Regex::Char('a')),
+            Box::new(Regex::ZeroOrMore(Box::new(Regex::Char('a')))),
+        )),
+        Box::new(Regex::Char('a')),
+    );
+
+    nfa.add_regex(&Default::default(), &re, None, None, None, 1)
+        .unwrap();
+
+    test_simulate(
+        &nfa,
+        vec![
+            ("a", vec![], Some(0)),
+            ("aa", vec![("aa", 1)], None),
+            ("aaa", vec![("aaa", 1)], None),
+        ],
+    );
+}
+
+#[test]
+fn simulate_any_1() {
+    let mut nfa: NFA<usize> = NFA::new();
+
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::String("ab".to_owned()),
+        None,
+        None,
+        None,
+        1,
+    )
+    .unwrap();
+    nfa.add_regex(&Default::default(), &Regex::Any, None, None, None, 2)
+        .unwrap();
+
+    test_simulate(
+        &nfa,
+        vec![
+            ("a", vec![("a", 2)], None),
+            ("ab", vec![("ab", 1)], None),
+            ("abc", vec![("ab", 1), ("c", 2)], None),
+        ],
+    );
+}
+
+#[test]
+fn simulate_any_2() {
+    let mut nfa: NFA<usize> = NFA::new();
+
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::Concat(
+            Box::new(Regex::Char('\'')),
+            Box::new(Regex::Concat(
+                Box::new(Regex::Any),
+                Box::new(Regex::Char('\'')),
+            )),
+        ),
+        None,
+        None,
+        None,
+        1,
+    )
+    .unwrap();
+
+    test_simulate(&nfa, vec![("'a'", vec![("'a'", 1)], None)]);
+}
+
+#[test]
+fn simulate_end_of_input_1() {
+    let mut nfa: NFA<usize> = NFA::new();
+
+    // C-style single-line comment syntax: "//" _* ('\n' | $)
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::Concat(
+            Box::new(Regex::String("//".to_owned())),
+            Box::new(Regex::Concat(
+                Box::new(Regex::ZeroOrMore(Box::new(Regex::Any))),
+                Box::new(Regex::Or(
+                    Box::new(Regex::Char('\n')),
+                    Box::new(Regex::EndOfInput),
+                )),
+            )),
+        ),
+        None,
+        None,
+        None,
+        1,
+    )
+    .unwrap();
+
+    test_simulate(
+        &nfa,
+        vec![
+            ("//", vec![("//", 1)], None),
+            ("//  \n", vec![("//  \n", 1)], None),
+            ("//  ", vec![("//  ", 1)], None),
+        ],
+    );
+}
+
+#[test]
+fn simulate_end_of_input_2() {
+    let mut nfa: NFA<usize> = NFA::new();
+
+    nfa.add_regex(&Default::default(), &Regex::EndOfInput, None, None, None, 1)
+        .unwrap();
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::ZeroOrMore(Box::new(Regex::Any)),
+        None,
+        None,
+        None,
+        2,
+    )
+    .unwrap();
+
+    // TODO: EndOfInput never matches?
+    test_simulate(&nfa, vec![("a", vec![("a", 2)], None)]);
+}
+
+#[test]
+fn simulate_multiple_accepting_states_3() {
+    let mut nfa: NFA<usize> = NFA::new();
+
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::String("aaa".to_owned()),
+        None,
+        None,
+        None,
+        1,
+    )
+    .unwrap();
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::String("aaa".to_owned()),
+        None,
+        None,
+        None,
+        2,
+    )
+    .unwrap();
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::String("aa".to_owned()),
+        None,
+        None,
+        None,
+        3,
+    )
+    .unwrap();
+
+    test_simulate(
+        &nfa,
+        vec![
+            ("aaa", vec![("aaa", 1)], None),
+            ("aa", vec![("aa", 3)], None),
+        ],
+    );
+}
+
+#[test]
+fn range_and_char_confusion() {
+    let mut nfa: NFA<usize> = NFA::new();
+
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::String("ab".to_owned()),
+        None,
+        None,
+        None,
+        1,
+    )
+    .unwrap();
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::OneOrMore(Box::new(Regex::CharSet(CharSet(vec![CharOrRange::Range(
+            'a', 'z',
+        )])))),
+        None,
+        None,
+        None,
+        2,
+    )
+    .unwrap();
+
+    test_simulate(
+        &nfa,
+        vec![("ab", vec![("ab", 1)], None), ("ac", vec![("ac", 2)], None)],
+    );
+}
+
+#[test]
+fn overlapping_ranges() {
+    let mut nfa: NFA<usize> = NFA::new();
+
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::Concat(
+            Box::new(Regex::CharSet(CharSet(vec![CharOrRange::Range('a', 'b')]))),
+            Box::new(Regex::Char('1')),
+        ),
+        None,
+        None,
+        None,
+        1,
+    )
+    .unwrap();
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::Concat(
+            Box::new(Regex::CharSet(CharSet(vec![CharOrRange::Range('a', 'c')]))),
+            Box::new(Regex::Char('2')),
+        ),
+        None,
+        None,
+        None,
+        2,
+    )
+    .unwrap();
+
+    test_simulate(
+        &nfa,
+        vec![("a1", vec![("a1", 1)], None), ("a2", vec![("a2", 2)], None)],
+    );
+}
+
+#[test]
+fn right_context_1() {
+    let mut nfa: NFA<usize> = NFA::new();
+    let mut right_ctxs = RightCtxDFAs::new();
+
+    let right_ctx = right_ctxs
+        .new_right_ctx(
+            &Default::default(),
+            &RightCtx {
+                re: Regex::Char('a'),
+                negated: false,
+            },
+        )
+        .unwrap();
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::Char('a'),
+        None,
+        None,
+        Some(right_ctx),
+        1,
+    )
+    .unwrap();
+
+    test_simulate_right_ctx(&nfa, &right_ctxs, vec![("aa", vec![("a", 1)], Some(1))]);
+    test_simulate_right_ctx(&nfa, &right_ctxs, vec![("ab", vec![], Some(0))]);
+}
+
+#[test]
+fn right_context_2() {
+    let mut nfa: NFA<usize> = NFA::new();
+    let mut right_ctxs = RightCtxDFAs::new();
+
+    let right_ctx = right_ctxs
+        .new_right_ctx(
+            &Default::default(),
+            &RightCtx {
+                re: Regex::Any,
+                negated: false,
+            },
+        )
+        .unwrap();
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::Char('a'),
+        None,
+        None,
+        Some(right_ctx),
+        1,
+    )
+    .unwrap();
+
+    test_simulate_right_ctx(&nfa, &right_ctxs, vec![("aa", vec![("a", 1)], Some(1))]);
+    test_simulate_right_ctx(&nfa, &right_ctxs, vec![("ab", vec![("a", 1)], Some(1))]);
+    test_simulate_right_ctx(&nfa, &right_ctxs, vec![("a", vec![], Some(0))]);
+}
+
+#[test]
+fn right_context_3() {
+    let mut nfa: NFA<usize> = NFA::new();
+    let mut right_ctxs = RightCtxDFAs::new();
+
+    let right_ctx = right_ctxs
+        .new_right_ctx(
+            &Default::default(),
+            &RightCtx {
+                re: Regex::EndOfInput,
+                negated: false,
+            },
+        )
+        .unwrap();
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::Char('a'),
+        None,
+        None,
+        Some(right_ctx),
+        1,
+    )
+    .unwrap();
+
+    test_simulate_right_ctx(&nfa, &right_ctxs, vec![("a", vec![("a", 1)], None)]);
+    test_simulate_right_ctx(&nfa, &right_ctxs, vec![("ab", vec![], Some(0))]);
+}
+
+#[test]
+fn right_context_4() {
+    let mut nfa: NFA<usize> = NFA::new();
+    let mut right_ctxs = RightCtxDFAs::new();
+
+    let right_ctx = right_ctxs
+        .new_right_ctx(
+            &Default::default(),
+            &RightCtx {
+                re: Regex::Char('a'),
+                negated: false,
+            },
+        )
+        .unwrap();
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::Char('a'),
+        None,
+        None,
+        Some(right_ctx),
+        1,
+    )
+    .unwrap();
+
+    let right_ctx = right_ctxs
+        .new_right_ctx(
+            &Default::default(),
+            &RightCtx {
+                re: Regex::EndOfInput,
+                negated: false,
+            },
+        )
+        .unwrap();
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::Char('a'),
+        None,
+        None,
+        Some(right_ctx),
+        2,
+    )
+    .unwrap();
+
+    test_simulate_right_ctx(
+        &nfa,
+        &right_ctxs,
+        vec![("aa", vec![("a", 1), ("a", 2)], None)],
+    );
+}
+
+#[test]
+fn negated_right_context() {
+    let mut nfa: NFA<usize> = NFA::new();
+    let mut right_ctxs = RightCtxDFAs::new();
+
+    let right_ctx = right_ctxs
+        .new_right_ctx(
+            &Default::default(),
+            &RightCtx {
+                re: Regex::String("..".to_owned()),
+                negated: true,
+            },
+        )
+        .unwrap();
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::Char('1'),
+        None,
+        None,
+        Some(right_ctx),
+        1,
+    )
+    .unwrap();
+    nfa.add_regex(&Default::default(), &Regex::Char('.'), None, None, None, 2)
+        .unwrap();
+
+    test_simulate_right_ctx(&nfa, &right_ctxs, vec![("1", vec![("1", 1)], None)]);
+    test_simulate_right_ctx(
+        &nfa,
+        &right_ctxs,
+        vec![("1.", vec![("1", 1), (".", 2)], None)],
+    );
+    test_simulate_right_ctx(&nfa, &right_ctxs, vec![("1..", vec![], Some(0))]);
+}
+
+#[test]
+fn right_context_dedup() {
+    let mut right_ctxs: RightCtxDFAs<DfaStateIdx> = RightCtxDFAs::new();
+
+    let right_ctx_1 = right_ctxs
+        .new_right_ctx(
+            &Default::default(),
+            &RightCtx {
+                re: Regex::String("ab".to_owned()),
+                negated: false,
+            },
+        )
+        .unwrap();
+
+    // Right context DFAs stop at the first accepting state, so this is the same as "ab"
+    let right_ctx_2 = right_ctxs
+        .new_right_ctx(
+            &Default::default(),
+            &RightCtx {
+                re: Regex::Or(
+                    Box::new(Regex::String("abc".to_owned())),
+                    Box::new(Regex::String("ab".to_owned())),
+                ),
+                negated: false,
+            },
+        )
+        .unwrap();
+
+    let right_ctx_3 = right_ctxs
+        .new_right_ctx(
+            &Default::default(),
+            &RightCtx {
+                re: Regex::String("ab".to_owned()),
+                negated: true,
+            },
+        )
+        .unwrap();
+
+    // `('a' | 'b')+` and `['a' 'b'] ['a' 'b']*` should be minimized to the same DFA
+    let a_or_b = || {
+        Regex::CharSet(CharSet(vec![
+            CharOrRange::Char('a'),
+            CharOrRange::Char('b'),
+        ]))
+    };
+
+    let right_ctx_4 = right_ctxs
+        .new_right_ctx(
+            &Default::default(),
+            &RightCtx {
+                re: Regex::OneOrMore(Box::new(a_or_b())),
+                negated: false,
+            },
+        )
+        .unwrap();
+
+    let right_ctx_5 = right_ctxs
+        .new_right_ctx(
+            &Default::default(),
+            &RightCtx {
+                re: Regex::Concat(
+                    Box::new(a_or_b()),
+                    Box::new(Regex::ZeroOrMore(Box::new(a_or_b()))),
+                ),
+                negated: false,
+            },
+        )
+        .unwrap();
+
+    assert_eq!(right_ctx_1, right_ctx_2);
+    assert_ne!(right_ctx_1, right_ctx_3);
+    assert_ne!(right_ctx_1, right_ctx_4);
+    assert_eq!(right_ctx_4, right_ctx_5);
+    assert_eq!(right_ctxs.iter().count(), 3);
+}
+
+#[test]
+fn anchors() {
+    let mut nfa: NFA<usize> = NFA::new();
+
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::Char('a'),
+        Some(Anchor::LineStart),
+        None,
+        None,
+        1,
+    )
+    .unwrap();
+    nfa.add_regex(&Default::default(), &Regex::Char('a'), None, None, None, 2)
+        .unwrap();
+    nfa.add_regex(&Default::default(), &Regex::Char('\n'), None, None, None, 3)
+        .unwrap();
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::Char('b'),
+        Some(Anchor::InputStart),
+        None,
+        None,
+        4,
+    )
+    .unwrap();
+    nfa.add_regex(&Default::default(), &Regex::Char('b'), None, None, None, 5)
+        .unwrap();
+
+    test_simulate(
+        &nfa,
+        vec![
+            ("aa\na", vec![("a", 1), ("a", 2), ("\n", 3), ("a", 1)], None),
+            ("bb\nb", vec![("b", 4), ("b", 5), ("\n", 3), ("b", 5)], None),
+        ],
+    );
+}
+
+#[test]
+fn left_context() {
+    let mut nfa: NFA<usize> = NFA::new();
+    let mut left_ctxs = LeftCtxs::new();
+
+    let left_ctx = left_ctxs
+        .new_left_ctx(&Default::default(), &LeftCtx::Char(Regex::Char('a')))
+        .unwrap();
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::Char('b'),
+        None,
+        Some(left_ctx),
+        None,
+        1,
+    )
+    .unwrap();
+    nfa.add_regex(&Default::default(), &Regex::Char('b'), None, None, None, 2)
+        .unwrap();
+    nfa.add_regex(&Default::default(), &Regex::Char('a'), None, None, None, 3)
+        .unwrap();
+
+    test_simulate_ctxs(
+        &nfa,
+        &left_ctxs,
+        &RightCtxDFAs::new(),
+        vec![
+            ("abb", vec![("a", 3), ("b", 1), ("b", 2)], None),
+            ("b", vec![("b", 2)], None),
+        ],
+    );
+}
+
+#[test]
+fn lexer_file_error_location() {
+    // Error in tokenization: unterminated string
+    assert_eq!(locate_parse_error("Lexer -> Token;\n\n  \"abc"), (3, 3));
+
+    // Error in parsing: missing `;` after the first line
+    assert_eq!(
+        locate_parse_error("Lexer -> Token\n\nrule Init {\n    'a' = Token::A,\n}"),
+        (3, 1)
+    );
+
+    // No errors
+    assert_eq!(
+        locate_parse_error("Lexer -> Token; 'a' = Token::A,"),
+        (1, 1)
+    );
+
+    // Errors found after parsing are reported at the rule or binding with the error
+    let location = |definition: &str| match crate::generate(definition) {
+        Err(crate::Error::Invalid { location, .. }) => location,
+        other => panic!("Unexpected result: {:?}", other),
+    };
+
+    assert_eq!(
+        location("Lexer -> Token;\nlet x = 'a';\nlet x = 'b';"),
+        Some((3, 5))
+    );
+    assert_eq!(
+        location("Lexer -> Token;\nrule Init {\n    'a' = Token::A,\n    $x = Token::B,\n}"),
+        Some((4, 5))
+    );
+    assert_eq!(
+        location("Lexer -> Token;\nrule Init {}\nrule Init {}"),
+        Some((3, 6))
+    );
+    assert_eq!(
+        location("Lexer -> Token;\ntype Error = A;\ntype Error = B;"),
+        None
+    );
+
+    assert_eq!(
+        crate::generate("Lexer -> Token;\nlet x = 'a';\nlet x = 'b';")
+            .unwrap_err()
+            .to_string(),
+        "3:5: Variable \"x\" is defined multiple times"
+    );
+}
+
+#[test]
+fn build_script_generate() {
+    let code = crate::generate("Lexer -> Token; 'a' = Token::A,").unwrap();
+    assert!(code.contains("struct Lexer_"));
+
+    match crate::generate("Lexer -> Token\n'a' = Token::A,") {
+        Err(crate::Error::Syntax {
+            path: None,
+            line: 2,
+            col: 1,
+            message: _,
+        }) => {}
+        other => panic!("Unexpected result: {:?}", other),
+    }
+
+    match crate::generate("Lexer -> Token; $x = Token::A,") {
+        Err(crate::Error::Invalid {
+            path: None,
+            location: Some((1, 17)),
+            message,
+        }) => {
+            assert!(message.contains("x"), "{}", message)
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn inspect_lexer() {
+    use crate::inspect::{Inspection, Match};
+
+    let inspection = Inspection::new(
+        "
+        Lexer -> ();
+
+        rule Init {
+            [' ' '\\n'],
+            ['a'-'z']+ = (),
+            \"/*\" => |lexer| lexer.switch(LexerRule::Comment),
+        }
+
+        rule Comment {
+            \"*/\" => |lexer| lexer.switch(LexerRule::Init),
+            _,
+        }
+        ",
+    )
+    .unwrap();
+
+    assert_eq!(
+        inspection.rule_set_names().collect::<Vec<_>>(),
+        vec!["Init", "Comment"]
+    );
+
+    let stats = inspection.stats();
+    assert_eq!(stats.rule_sets.len(), 2);
+    assert_eq!(stats.rule_sets[0].n_rules, 3);
+    assert_eq!(stats.rule_sets[1].n_rules, 2);
+    assert!(stats.n_simplified_dfa_states <= stats.n_dfa_states);
+
+    assert!(inspection.nfa("Comment").is_some());
+    assert!(inspection.nfa("Strings").is_none());
+
+    let run = inspection.run("Init", "ab /*").unwrap();
+    assert_eq!(
+        run.matches,
+        vec![
+            Match {
+                text: "ab",
+                rule_idx: 1,
+                rule: "['a'-'z']+"
+            },
+            Match {
+                text: " ",
+                rule_idx: 0,
+                rule: "[' ' '\\n']"
+            },
+            Match {
+                text: "/*",
+                rule_idx: 2,
+                rule: "\"/*\""
+            },
+        ]
+    );
+    assert_eq!(run.error, None);
+
+    let run = inspection.run("Comment", "x*/!").unwrap();
+    assert_eq!(
+        run.matches.iter().map(|m| m.rule_idx).collect::<Vec<_>>(),
+        vec![1, 0, 1]
+    );
+
+    let run = inspection.run("Init", "ab1").unwrap();
+    assert_eq!(run.matches.len(), 1);
+    assert_eq!(run.error, Some(2));
+
+    assert!(matches!(
+        Inspection::new("Lexer -> (); $x,"),
+        Err(crate::Error::Invalid { .. })
+    ));
+}
+
+#[test]
+fn dot_output() {
+    let mut nfa: NFA<usize> = NFA::new();
+    nfa.add_regex(
+        &Default::default(),
+        &Regex::OneOrMore(Box::new(Regex::Char('a'))),
+        None,
+        None,
+        None,
+        1,
+    )
+    .unwrap();
+    nfa.add_regex(&Default::default(), &Regex::Char('"'), None, None, None, 2)
+        .unwrap();
+
+    let nfa_dot = nfa.to_dot();
+    assert!(nfa_dot.starts_with("digraph {\n"));
+    assert!(nfa_dot.contains("label=\"'a'\""), "{}", nfa_dot);
+    assert!(nfa_dot.contains("label=\"'\\\"'\""), "{}", nfa_dot);
+    assert!(nfa_dot.contains("label=\"ε\""), "{}", nfa_dot);
+
+    let dfa = nfa_to_dfa(&nfa);
+    let dfa_dot = dfa.to_dot();
+    assert!(dfa_dot.contains("s0_start -> s0"), "{}", dfa_dot);
+    assert!(
+        dfa_dot.contains("\\n1\", shape=doublecircle"),
+        "{}",
+        dfa_dot
+    );
+
+    let simplified_dfa = crate::dfa::simplify::simplify::<(), _>(dfa, &mut Default::default());
+    let simplified_dfa_dot = simplified_dfa.to_dot();
+    assert!(
+        simplified_dfa_dot.contains("[label=\"2\", shape=doubleoctagon]"),
+        "{}",
+        simplified_dfa_dot
+    );
+
+    let inspection = crate::inspect::Inspection::new(
+        "
+        Lexer -> ();
+
+        rule Init {
+            ['a'-'z']+ > ' ' = (),
+            ' ' => |lexer| lexer.switch(LexerRule::Space),
+        }
+
+        rule Space {
+            ' ',
+            'x' >! 'y' => |lexer| lexer.switch(LexerRule::Init),
+        }
+        ",
+    )
+    .unwrap();
+
+    let dfa_dot = inspection.dfa_dot();
+    assert!(dfa_dot.contains("[label=\"Init\"]"), "{}", dfa_dot);
+    assert!(dfa_dot.contains("[label=\"Space\"]"), "{}", dfa_dot);
+    assert!(
+        dfa_dot.contains("Init rule 0: ['a'-'z']+ > ' ' (right ctx 0)"),
+        "{}",
+        dfa_dot
+    );
+    assert!(
+        dfa_dot.contains("subgraph cluster_ctx0 {\n        label=\"right ctx 0\";"),
+        "{}",
+        dfa_dot
+    );
+    assert!(
+        dfa_dot.contains("subgraph cluster_ctx1 {\n        label=\"negated right ctx 1\";"),
+        "{}",
+        dfa_dot
+    );
+
+    let simplified_dfa_dot = inspection.simplified_dfa_dot();
+    assert!(
+        simplified_dfa_dot.contains("[label=\"Space rule 0: ' '\", shape=doubleoctagon]"),
+        "{}",
+        simplified_dfa_dot
+    );
+
+    assert!(inspection
+        .nfa_dot("Space")
+        .unwrap()
+        .contains("label=\"'x'\""));
+    assert!(inspection.nfa_dot("Comment").is_none());
+}
+
+#[test]
+fn runtime_lexer_builder() {
+    use crate::runtime::{InvalidToken, RuntimeLexer, Token};
+
+    let lexer = RuntimeLexer::builder()
+        .binding("digit", "['0'-'9']")
+        .skip("' '")
+        .rule("$digit+")
+        .rule("'\"'")
+        .rule_set("Str")
+        .rule("'\"'")
+        .skip("_")
+        .build()
+        .unwrap();
+
+    assert_eq!(
+        lexer.rule_set_names().collect::<Vec<_>>(),
+        vec!["Init", "Str"]
+    );
+
+    assert_eq!(
+        lexer.tokens("12 \"").collect::<Vec<_>>(),
+        vec![
+            Ok(Token {
+                rule: 1,
+                span: 0..2
+            }),
+            Ok(Token {
+                rule: 2,
+                span: 3..4
+            }),
+        ]
+    );
+
+    // Skipped input is a part of the next token in rule sets other than `Init`. Rule sets other
+    // than `Init` fail at the end of the input without a `$` rule.
+    assert_eq!(
+        lexer.tokens_in("Str", "ab\"").unwrap().collect::<Vec<_>>(),
+        vec![
+            Ok(Token {
+                rule: 0,
+                span: 0..3
+            }),
+            Err(InvalidToken { location: 3 })
+        ]
+    );
+
+    assert_eq!(
+        lexer.tokens_in("Str", "ab").unwrap().collect::<Vec<_>>(),
+        vec![Err(InvalidToken { location: 0 })]
+    );
+
+    assert!(lexer.tokens_in("Comment", "").is_none());
+
+    match RuntimeLexer::builder().rule("'a' |").build() {
+        Err(crate::Error::Syntax {
+            path: None,
+            line: 1,
+            col: _,
+            message,
+        }) => assert!(message.contains("(in regex \"'a' |\")"), "{}", message),
+        Err(other) => panic!("Unexpected error: {}", other),
+        Ok(_) => panic!("Syntax error not reported"),
+    }
+
+    match RuntimeLexer::builder().rule("$digit").build() {
+        Err(crate::Error::Invalid { path: None, .. }) => {}
+        Err(other) => panic!("Unexpected error: {}", other),
+        Ok(_) => panic!("Undefined variable not reported"),
+    }
+}
+
+#[test]
+fn runtime_lexer_definition_errors() {
+    use crate::runtime::RuntimeLexer;
+
+    let error = |definition: &str| match RuntimeLexer::new(definition) {
+        Err(crate::Error::Invalid {
+            path: None,
+            location: _,
+            message,
+        }) => message,
+        Err(other) => panic!("Unexpected error: {}", other),
+        Ok(_) => panic!("Error not reported"),
+    };
+
+    assert_eq!(
+        error("Lexer -> (); $$foo = (),"),
+        "Unknown builtin regex: foo"
+    );
+    assert_eq!(error("Lexer -> (); $x = (),"), "Unbound variable \"x\"");
+    assert_eq!(
+        error("Lexer -> (); rule Init { 'a' = (), } rule Init { 'b' = (), }"),
+        "Rule set \"Init\" is defined multiple times"
+    );
+    assert_eq!(
+        error("Lexer -> (); rule Foo { 'a' = (), }"),
+        "First rule set should be named \"Init\""
+    );
+    assert_eq!(
+        error("Lexer -> (); let x = 'a' > 'b'; $x > 'c' = (),"),
+        "Variable \"x\" has a right context, it cannot be used with another right context"
+    );
+    assert_eq!(
+        error("Lexer -> (); _ # ('a' 'b') = (),"),
+        "concatenation (`<re1> <re2>`) cannot be used in char sets (`#`)"
+    );
+    assert_eq!(
+        error("Lexer -> (); \"ab\" < 'x' = (),"),
+        "strings cannot be used in left contexts (`<re> < ...`), left contexts should be \
+        character sets"
+    );
+    assert_eq!(
+        error("Lexer -> (); let s = \"ab\"; [$s] = (),"),
+        "strings cannot be used in `$s` in `[...]`, variables in `[...]` should be character sets"
+    );
+    assert_eq!(
+        error("Lexer -> (); #[shortest] 'a'+ > 'b' = (),"),
+        "Shortest match rule `'a'+` has a right context. Right contexts cannot be used in \
+        `#[shortest]` rules."
+    );
+    assert_eq!(
+        error("Lexer -> (); { |lexer| lexer.state().0 } < 'a' = (),"),
+        "Left context predicates (`{ ... } <`) cannot be used in runtime lexers, as they are \
+        Rust code"
+    );
+
+    // Priorities can be any `i32`
+    assert!(RuntimeLexer::new("Lexer -> (); #[priority = -2147483648] 'a' = (),").is_ok());
+    assert!(RuntimeLexer::new("Lexer -> (); #[priority = 2147483647] 'a' = (),").is_ok());
+    match RuntimeLexer::new("Lexer -> (); #[priority = 2147483648] 'a' = (),") {
+        Err(crate::Error::Syntax { message, .. }) => {
+            assert_eq!(message, "Rule priority should fit in an `i32`")
+        }
+        Err(other) => panic!("Unexpected error: {}", other),
+        Ok(_) => panic!("Error not reported"),
+    }
+}
+
+#[test]
+fn lexer_builder() {
+    use crate::builder::{LexerBuilder, Regex, RegexCtx, Rule};
+
+    let ident = Regex::range('a', 'z')
+        .or(Regex::char('_'))
+        .then(Regex::var("ident_char").zero_or_more());
+    assert_eq!(ident.to_string(), "(['a'-'z'] | '_') $ident_char*");
+
+    let comment = RegexCtx::new(
+        Regex::string("#").then(Regex::any().except(Regex::char('\n')).zero_or_more()),
+    )
+    .at_line_start();
+    // `#` binds tighter than postfix operators
+    assert_eq!(comment.to_string(), "^\"#\" _ # '\\n'*");
+
+    let keywords = ["if", "else"];
+
+    let mut builder = LexerBuilder::new("Lexer", "Token")
+        .public()
+        .error_type("MyError")
+        .binding(
+            "ident_char",
+            Regex::alt([
+                Regex::range('a', 'z'),
+                Regex::range('0', '9'),
+                Regex::char('_'),
+            ]),
+        )
+        .skip(Regex::one_of([' ', '\n']).one_or_more())
+        .skip(comment);
+
+    for keyword in keywords {
+        builder = builder.rule(
+            Rule::new(Regex::string(keyword)).priority(1),
+            &format!("= Token::Keyword({:?})", keyword),
+        );
+    }
+
+    let code = builder
+        .rule(
+            ident,
+            "=> |lexer| lexer.return_(Token::Ident(lexer.match_()))",
+        )
+        .rule(
+            RegexCtx::new(Regex::builtin("ascii_digit").one_or_more())
+                .followed_by(Regex::char('.')),
+            "=? |lexer| lexer.return_(Err(MyError))",
+        )
+        .rule("'/' '*'", "=> |lexer| lexer.switch(LexerRule::Comment)")
+        .rule_set("Comment")
+        .rule(
+            Regex::string("*/"),
+            "=> |lexer| lexer.switch(LexerRule::Init)",
+        )
+        .skip(Rule::new(Regex::any()).shortest())
+        .generate()
+        .unwrap();
+
+    let expected = crate::generate(
+        r##"
+        pub Lexer -> Token;
+
+        type Error = MyError;
+
+        let ident_char = ['a'-'z'] | ['0'-'9'] | '_';
+
+        rule Init {
+            [' ' '\n']+,
+            ^ "#" (_ # '\n')*,
+            #[priority = 1] "if" = Token::Keyword("if"),
+            #[priority = 1] "else" = Token::Keyword("else"),
+            (['a'-'z'] | '_') $ident_char* => |lexer| lexer.return_(Token::Ident(lexer.match_())),
+            $$ascii_digit+ > '.' =? |lexer| lexer.return_(Err(MyError)),
+            '/' '*' => |lexer| lexer.switch(LexerRule::Comment),
+        }
+
+        rule Comment {
+            "*/" => |lexer| lexer.switch(LexerRule::Init),
+            #[shortest] _,
+        }
+        "##,
+    )
+    .unwrap();
+
+    assert_eq!(code, expected);
+
+    // Errors in rules are reported when generating code
+    match LexerBuilder::new("Lexer", "Token")
+        .rule(Regex::char('a'), "= Token::A")
+        .rule("'b' |", "= Token::B")
+        .generate()
+    {
+        Err(crate::Error::Syntax { message, .. }) => {
+            assert!(message.contains("(in regex \"'b' |\")"), "{}", message)
+        }
+        other => panic!("Unexpected result: {:?}", other.map(|_| ())),
+    }
+
+    let error = |builder: LexerBuilder| match builder.generate() {
+        Err(crate::Error::Invalid {
+            path: None,
+            location: None,
+            message,
+        }) => message,
+        other => panic!("Unexpected result: {:?}", other.map(|_| ())),
+    };
+
+    assert_eq!(
+        error(LexerBuilder::new("Lexer", "Token").rule(Regex::var("x"), "= Token::A")),
+        "Unbound variable \"x\" (in regex \"$x\")"
+    );
+    // Variables should be bound before they're used
+    assert_eq!(
+        error(
+            LexerBuilder::new("Lexer", "Token")
+                .rule("'a' > $x", "= Token::A")
+                .binding("x", Regex::char('b'))
+        ),
+        "Unbound variable \"x\" (in regex \"'a' > $x\")"
+    );
+    assert_eq!(
+        error(LexerBuilder::new("Lexer", "Token").skip(Regex::builtin("foo").one_or_more())),
+        "Unknown builtin regex: foo (in regex \"$$foo+\")"
+    );
+    assert_eq!(
+        error(LexerBuilder::new("Lexer", "Token").binding("x", "[$$foo 'a']")),
+        "Unknown builtin regex: foo (in regex \"[$$foo 'a']\")"
+    );
+
+    let inspection = LexerBuilder::new("Lexer", "Token")
+        .rule(Regex::char('a').one_or_more(), "= Token::A")
+        .inspect()
+        .unwrap();
+    assert_eq!(inspection.run("Init", "aa").unwrap().matches.len(), 1);
+}
+
+#[test]
+fn import_flex() {
+    let spec = r##"%{
+#include "tokens.h"
+%}
+%option noyywrap caseless
+DIGIT    [0-9]
+ID       [a-z_][a-z0-9_-]*
+%x COMMENT
+%%
+{DIGIT}{1,3}("."{DIGIT}*)?  { return NUM; }
+if|then                     |
+else                        { return KEYWORD; }
+^"#".*$                     ;
+{ID}/"("                    return CALL;
+[^ \t\n[:alpha:]]           { yyless(1);
+                              return OTHER; }
+"/*"                        BEGIN(COMMENT);
+<COMMENT>{
+    "*/"                    BEGIN INITIAL;
+    .|\n                    /* skip */
+}
+[ \t\n]+
+%%
+int main() {}
+"##;
+
+    let converted = crate::import::flex(spec).unwrap();
+
+    assert_eq!(
+        converted.definition,
+        r##"// Converted from flex. Actions marked with `todo!()` need to be translated by hand.
+Lexer -> Token;
+
+let DIGIT = ['0'-'9'];
+let ID = ['a'-'z' '_'] ['a'-'z' '0'-'9' '_' '-']*;
+
+rule Init {
+    $DIGIT $DIGIT? $DIGIT? ("." $DIGIT*)? => |lexer| {
+        // { return NUM; }
+        todo!()
+    },
+    "if" | "then" => |lexer| {
+        // { return KEYWORD; }
+        todo!()
+    },
+    "else" => |lexer| {
+        // { return KEYWORD; }
+        todo!()
+    },
+    ^"#" _ # '\n'* > '\n',
+    $ID > "(" => |lexer| {
+        // return CALL;
+        todo!()
+    },
+    _ # ([' ' '\t' '\n'] | $$ascii_alphabetic) => |lexer| {
+        // { yyless(1);
+        // return OTHER; }
+        todo!()
+    },
+    "/*" => |lexer| lexer.switch(LexerRule::Comment),
+    [' ' '\t' '\n']+,
+}
+
+rule Comment {
+    "*/" => |lexer| lexer.switch(LexerRule::Init),
+    _ # '\n' | '\n',
+}
+"##
+    );
+
+    let warnings: Vec<String> = converted.warnings.iter().map(|w| w.to_string()).collect();
+    assert_eq!(
+        warnings,
+        vec![
+            "1: Code in `%{ ... %}` is not converted",
+            "4: Option `caseless` is not supported",
+            "14: `yyless` in action has no lexgen equivalent",
+            "23: User code section is not converted",
+        ]
+    );
+
+    // The converted definition is a valid lexer definition that matches the same way
+    crate::generate(&converted.definition).unwrap();
+
+    let lexer = crate::runtime::RuntimeLexer::new(&converted.definition).unwrap();
+    let rules: Vec<usize> = lexer
+        .tokens("12.5 if else foo(-")
+        .map(|token| token.unwrap().rule)
+        .collect();
+    assert_eq!(rules, vec![0, 1, 2, 4, 5, 5]);
+
+    // Inclusive and exclusive start conditions, `<*>`, `<<EOF>>`
+    let spec = r#"
+%s EXPR
+%x STR
+%%
+"a"                 ;
+<STR>"b"            ;
+<*>"c"              ;
+<EXPR,STR><<EOF>>   ;
+"#;
+    let converted = crate::import::flex(spec).unwrap();
+    assert!(converted.definition.ends_with(
+        r#"rule Init {
+    "a",
+    "c",
+}
+
+rule Expr {
+    "a",
+    "c",
+    $,
+}
+
+rule Str {
+    "b",
+    "c",
+    $,
+}
+"#
+    ));
+
+    // Errors
+    let error = |spec: &str| crate::import::flex(spec).unwrap_err().to_string();
+    assert_eq!(error("A [a-z]\n"), "2:1: Expected `%%`");
+    assert_eq!(error("%%\n{A}+ ;\n"), "2:1: Undefined definition \"A\"");
+    assert_eq!(
+        error("%%\n<S>a ;\n"),
+        "2:2: Undeclared start condition \"S\""
+    );
+    assert_eq!(error("%%\n[a-z ;\n"), "2:1: Unterminated character class");
+    assert_eq!(
+        error("%%\na{3,1} ;\n"),
+        "2:7: Invalid repetition bounds {3,1}"
+    );
+    assert_eq!(
+        error("%%\na |\n"),
+        "2:1: The last rule's action cannot be `|`"
+    );
+    assert_eq!(
+        error("%%\n(?i:a) ;\n"),
+        "2:1: Pattern options `(?...)` are not supported"
+    );
+}
+
+#[test]
+fn import_ocamllex() {
+    let spec = r#"
+{ open Parser }
+let digit = ['0'-'9']
+let ident' = ['a'-'z' '_'] ['a'-'z' '0'-'9' "_'"]*
+rule token = parse
+  | [' ' '\t' '\n']  { token lexbuf }
+  | digit+ as n      { INT (int_of_string n) }
+  | "(*"             { comment 0 lexbuf }
+  | ident'           { IDENT (Lexing.lexeme lexbuf) }
+  | '"' [^ '"' '\\']* '"' { STRING }
+  | eof              { EOF }
+and comment depth = shortest
+  | "*)"  { if depth = 0 then token lexbuf else comment (depth - 1) lexbuf }
+  | _     { comment depth lexbuf (* '}' *) }
+"#;
+
+    let converted = crate::import::ocamllex(spec).unwrap();
+
+    assert_eq!(
+        converted.definition,
+        r#"// Converted from ocamllex. Actions marked with `todo!()` need to be translated by hand.
+Lexer -> Token;
+
+let digit = ['0'-'9'];
+let ident_ = ['a'-'z' '_'] ['a'-'z' '0'-'9' '_' '\'']*;
+
+rule Init {
+    [' ' '\t' '\n'],
+    $digit+ => |lexer| {
+        // { INT (int_of_string n) }
+        todo!()
+    },
+    "(*" => |lexer| {
+        // { comment 0 lexbuf }
+        todo!()
+    },
+    $ident_ => |lexer| {
+        // { IDENT (Lexing.lexeme lexbuf) }
+        todo!()
+    },
+    '"' _ # ['"' '\\']* '"' => |lexer| {
+        // { STRING }
+        todo!()
+    },
+    $ => |lexer| {
+        // { EOF }
+        todo!()
+    },
+}
+
+rule Comment {
+    #[shortest]
+    "*)" => |lexer| {
+        // { if depth = 0 then token lexbuf else comment (depth - 1) lexbuf }
+        todo!()
+    },
+    #[shortest]
+    _ => |lexer| {
+        // { comment depth lexbuf (* '}' *) }
+        todo!()
+    },
+}
+"#
+    );
+
+    let warnings: Vec<String> = converted.warnings.iter().map(|w| w.to_string()).collect();
+    assert_eq!(
+        warnings,
+        vec![
+            "2: Header is not converted",
+            "7: `as n` is not supported, the binding is dropped",
+            "12: Arguments of entry point `comment` are not supported: depth",
+        ]
+    );
+
+    crate::generate(&converted.definition).unwrap();
+
+    let lexer = crate::runtime::RuntimeLexer::new(&converted.definition).unwrap();
+    let rules: Vec<usize> = lexer
+        .tokens("x1 42 \"ab\" (*")
+        .map(|token| token.unwrap().rule)
+        .collect();
+    assert_eq!(rules, vec![3, 1, 4, 2, 5]);
+
+    // Errors
+    let error = |spec: &str| crate::import::ocamllex(spec).unwrap_err().to_string();
+    assert_eq!(error("let x = 'a"), "1:9: Invalid character literal");
+    assert_eq!(error("rule x = parse y { }"), "1:16: Undefined regex \"y\"");
+    assert_eq!(
+        error("rule x = parse 'a' { (* }"),
+        "1:22: Unterminated comment"
+    );
+    assert_eq!(
+        error("rule x = lex 'a' { }"),
+        "1:10: Expected `parse` or `shortest`"
+    );
+}
+
+#[test]
+fn raw_string_regexes() {
+    let show = |re: &str| {
+        crate::lexer_file::parse_regex_ctx(re)
+            .map_err(crate::Error::from)
+            .unwrap()
+            .to_string()
+    };
+
+    assert_eq!(
+        show(r#"regex!(r"[a-zA-Z_]\w*")"#),
+        "['a'-'z' 'A'-'Z' '_'] ['a'-'z' 'A'-'Z' '0'-'9' '_']*"
+    );
+    assert_eq!(show(r#"regex!(r"abc|a.c")"#), r#""abc" | 'a' _ # '\n' 'c'"#);
+    assert_eq!(show(r#"regex!(r"(?:ab)+c?")"#), r#""ab"+ 'c'?"#);
+    assert_eq!(show(r#"regex!(r"a{2,3}b{2,}")"#), r#""aa" 'a'? "bb" 'b'*"#);
+    assert_eq!(
+        show(r#"regex!(r"[^\d\s-]")"#),
+        r"_ # ['0'-'9' ' ' '\t' '\n' '\r' '\u{b}' '\u{c}' '-']"
+    );
+    assert_eq!(
+        show(r#"regex!(r"[[:alpha:]\W]")"#),
+        "$$ascii_alphabetic | _ # ['a'-'z' 'A'-'Z' '0'-'9' '_']"
+    );
+    assert_eq!(show(r#"regex!(r"\x41\u{3bb}\.\\")"#), r#""Aλ.\\""#);
+    assert_eq!(show(r#"regex!("\\d+")"#), "['0'-'9']+");
+    assert_eq!(show(r##"regex!(r#"(?<q>"|')"#)"##), r#"'"' | '\''"#);
+
+    // `regex!` combines with the rest of the syntax, strings (including raw strings) are still
+    // literals
+    assert_eq!(
+        show(r#"^ regex!(r"\d+") "[a]" r"\d" > $ws"#),
+        r#"^['0'-'9']+ "[a]" "\\d" > $ws"#
+    );
+
+    let error = |re: &str| match crate::lexer_file::parse_regex_ctx(re).map_err(crate::Error::from)
+    {
+        Err(crate::Error::Syntax { message, .. }) => message,
+        other => panic!("{:?}", other.map(|re| re.to_string())),
+    };
+
+    assert_eq!(
+        error(r#"regex!(r"(a)\1")"#),
+        "Backreferences are not supported (at character 4 of the regex)"
+    );
+    assert_eq!(
+        error(r#"regex!(r"(?<=a)b")"#),
+        "Lookbehind is not supported, use left context (`<`) (at character 1 of the regex)"
+    );
+    assert_eq!(
+        error(r#"regex!(r"a(?!b)")"#),
+        "Lookahead is not supported, use right context (`>` and `>!`) (at character 2 of the \
+         regex)"
+    );
+    assert_eq!(
+        error(r#"regex!(r"a*?")"#),
+        "Lazy quantifiers are not supported, use `#[shortest]` rules instead (at character 2 of \
+         the regex)"
+    );
+    assert_eq!(
+        error(r#"regex!(r"^a")"#),
+        "Anchors are not supported in regex strings, use `^` before the regex, or `$` for end of \
+         input (at character 1 of the regex)"
+    );
+    assert_eq!(
+        error(r#"regex!(r"[a-")"#),
+        "Unclosed `[` (at character 1 of the regex)"
+    );
+    assert_eq!(
+        error(r#"regex!(r"a)")"#),
+        "Unmatched `)` (at character 2 of the regex)"
+    );
+    assert_eq!(
+        error(r#"regex!(r"*")"#),
+        "Nothing to repeat with `*` (at character 1 of the regex)"
+    );
+    assert_eq!(
+        error(r#"regex!(r"\q")"#),
+        "Unknown escape sequence `\\q` (at character 1 of the regex)"
+    );
+    assert_eq!(
+        error(r#"re!(r"a")"#),
+        "Unknown macro `re!`, expected `regex!`"
+    );
+    assert_eq!(
+        error(r#"regex!(r"a", r"b")"#),
+        "Expected one string literal in `regex!(...)`"
+    );
+}
+
+#[test]
+fn char_set_code_points_and_references() {
+    let show = |re: &str| {
+        crate::lexer_file::parse_regex_ctx(re)
+            .map_err(crate::Error::from)
+            .unwrap()
+            .to_string()
+    };
+
+    assert_eq!(
+        show("[0x300-0x36F 'a'-'z' 95 $$ascii_digit $ws]"),
+        r"['\u{300}'-'\u{36f}' 'a'-'z' '_' $$ascii_digit $ws]"
+    );
+    assert_eq!(show("['a'-0x7A]"), "['a'-'z']");
+
+    let error = |re: &str| match crate::lexer_file::parse_regex_ctx(re).map_err(crate::Error::from)
+    {
+        Err(crate::Error::Syntax { message, .. }) => message,
+        other => panic!("{:?}", other.map(|re| re.to_string())),
+    };
+
+    assert_eq!(error("[0xD800]"), "Invalid code point");
+    assert_eq!(
+        error("[0x41u8]"),
+        "Code points in character sets cannot have a suffix"
+    );
+    assert_eq!(
+        error("['z'-'a']"),
+        "Invalid range 'z'-'a': end of the range is smaller than the start"
+    );
+    assert_eq!(
+        error(r#"["a"]"#),
+        "Expected a character, a code point (e.g. `0x300`), `$var`, or `$$builtin` in character \
+         set"
+    );
+
+    // Sets with references are merged into one range map
+    let mut bindings: Map<Var, Binding> = Default::default();
+    bindings.insert(
+        Var("hex_letter".to_owned()),
+        Binding {
+            params: vec![],
+            re: Regex::CharSet(CharSet(vec![
+                CharOrRange::Range('a', 'f'),
+                CharOrRange::Range('A', 'F'),
+            ])),
+        },
+    );
+    let set = crate::lexer_file::parse_regex_ctx("[$$ascii_digit $hex_letter 'c'-'h']")
+        .map_err(crate::Error::from)
+        .unwrap()
+        .re;
+    let map = crate::regex_to_nfa::regex_to_range_map(
+        &bindings,
+        &set,
+        crate::regex_to_nfa::CharSetUse::CharSet,
+    )
+    .unwrap();
+    let ranges: Vec<(u32, u32)> = map.iter().map(|range| (range.start, range.end)).collect();
+    assert_eq!(
+        ranges,
+        vec![
+            ('0' as u32, '9' as u32),
+            ('A' as u32, 'F' as u32),
+            ('a' as u32, 'b' as u32),
+            ('c' as u32, 'f' as u32),
+            ('g' as u32, 'h' as u32)
+        ]
+    );
+}
+
+#[test]
+fn parameterised_bindings() {
+    let show = |re: &str| {
+        crate::lexer_file::parse_regex_ctx(re)
+            .map_err(crate::Error::from)
+            .unwrap()
+            .to_string()
+    };
+
+    assert_eq!(
+        show(r#"$delimited!('"', '\\')+ $ws ('a' | 'b')"#),
+        r#"$delimited!('"', '\\')+ $ws ('a' | 'b')"#
+    );
+
+    let definition = r#"
+        Lexer -> ();
+
+        let a = 'a';
+        let b = 'b';
+        let delimited(q) = $q (_ # [$q '\\'] | '\\' _)* $q;
+        let pair(a, b) = $a $b;
+        let twice(x) = $pair!($x, $x);
+
+        ' ',
+        $delimited!('"') = (),
+        $delimited!('\'') = (),
+
+        // Arguments refer to the variables at the use site, not to the parameters
+        $pair!($b, $a) = (),
+
+        $twice!('c') = (),
+
+        // A variable followed by a regex in parens is a concatenation
+        $a ('x' | 'y') = (),
+    "#;
+
+    let lexer = crate::runtime::RuntimeLexer::new(definition).unwrap();
+    let rules: Vec<usize> = lexer
+        .tokens(r#""a\"b" 'c' ba cc ay"#)
+        .map(|token| token.unwrap().rule)
+        .collect();
+    assert_eq!(rules, vec![1, 2, 3, 4, 5]);
+
+    let error = |definition: &str| match crate::generate(definition) {
+        Err(crate::Error::Invalid { message, .. }) => message,
+        Err(other) => panic!("Unexpected error: {}", other),
+        Ok(_) => panic!("Error not reported"),
+    };
+
+    assert_eq!(
+        error("Lexer -> (); let a = $b; let b = 'x' $a; $a = (),"),
+        "Variable \"b\" is defined in terms of itself: $b -> $a -> $b"
+    );
+    assert_eq!(
+        error("Lexer -> (); let f(x) = $x $f!($x)?; $f!('a') = (),"),
+        "Variable \"f\" is defined in terms of itself: $f -> $f"
+    );
+    assert_eq!(
+        error("Lexer -> (); let f(x, y) = $x $y; $f!('a') = (),"),
+        "Variable \"f\" has 2 parameter(s), but it's used with 1 argument(s)"
+    );
+    assert_eq!(
+        error("Lexer -> (); let a = 'a'; $a!('b') = (),"),
+        "Variable \"a\" is not parameterised, it cannot be used with arguments"
+    );
+    assert_eq!(
+        error("Lexer -> (); let f(x) = $x; $f = (),"),
+        "Variable \"f\" is parameterised, it should be used with arguments: `$f!(...)`"
+    );
+    assert_eq!(
+        error(r#"Lexer -> (); let f(x) = [$x]; $f!("ab") = (),"#),
+        "Parameter \"x\" is used in a character set, but argument `\"ab\"` is not a character set"
+    );
+
+    match crate::generate("Lexer -> (); let f(x, x) = $x;") {
+        Err(crate::Error::Syntax { message, .. }) => {
+            assert_eq!(message, "Parameter \"x\" is defined multiple times")
+        }
+        other => panic!("Unexpected result: {:?}", other.map(|_| ())),
+    }
+}
@@ -0,0 +1,188 @@
+//! Generating token enums
+//!
+//! With `Lexer -> enum Token { ... };` syntax the token type is generated by the macro. Variants
+//! declared in the enum are used as they are. A variant for each `<regex> = Token::Variant` rule
+//! that isn't declared in the enum is added without any fields. We also generate a fieldless
+//! `TokenKind` enum with the same variants, `Token::kind`, and `describe` methods and a `Display`
+//! implementation for the kind enum, for use in error messages.
+
+use crate::ast::{Regex, RegexCtx, RuleKind, RuleRhs};
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+
+pub struct TokenEnum {
+    /// The enum as declared by the user
+    item: syn::ItemEnum,
+
+    /// Variants of the enum, in the order they're declared, followed by the variants used in
+    /// rules but not declared. Second component is the literal spelling of the token, when the
+    /// token is returned by a rule that matches a string or character literal.
+    variants: Vec<(syn::Ident, Option<String>)>,
+}
+
+impl TokenEnum {
+    pub fn new(item: syn::ItemEnum) -> TokenEnum {
+        let variants = item
+            .variants
+            .iter()
+            .map(|variant| (variant.ident.clone(), None))
+            .collect();
+
+        TokenEnum { item, variants }
+    }
+
+    /// Type of the tokens: the enum name applied to its generics
+    pub fn token_type(item: &syn::ItemEnum) -> syn::Type {
+        let ident = &item.ident;
+        let (_, ty_generics, _) = item.generics.split_for_impl();
+        syn::parse_quote!(#ident #ty_generics)
+    }
+
+    /// Collect variants returned by a rule
+    pub fn add_rule(&mut self, lhs: &RegexCtx, rhs: &RuleRhs) {
+        match rhs {
+            RuleRhs::None => {}
+
+            RuleRhs::Rhs { expr, kind } => match kind {
+                RuleKind::Simple => {
+                    let spelling = match &lhs.re {
+                        Regex::String(str) => Some(str.clone()),
+                        Regex::Char(char) => Some(char.to_string()),
+                        _ => None,
+                    };
+                    self.add_token(expr, spelling);
+                }
+                RuleKind::Fallible | RuleKind::Infallible => {}
+            },
+
+            RuleRhs::Keywords { keywords, rhs } => {
+                for (keyword, expr) in keywords {
+                    self.add_token(expr, Some(keyword.clone()));
+                }
+                self.add_rule(lhs, rhs);
+            }
+        }
+    }
+
+    /// Add a variant for `expr` if it's in `Token::Variant` form. `spelling` is the string matched
+    /// by the rule that returns the token, when it's a literal.
+    fn add_token(&mut self, expr: &syn::Expr, spelling: Option<String>) {
+        let path = match expr {
+            syn::Expr::Path(syn::ExprPath {
+                attrs: _,
+                qself: None,
+                path,
+            }) => path,
+            _ => return,
+        };
+
+        if path.leading_colon.is_some()
+            || path.segments.len() != 2
+            || path.segments[0].ident != self.item.ident
+            || !path.segments[1].arguments.is_empty()
+        {
+            return;
+        }
+
+        let variant = &path.segments[1].ident;
+
+        match self.variants.iter_mut().find(|(ident, _)| ident == variant) {
+            Some((_, old_spelling)) => {
+                // Use the spelling of the first rule that returns the token
+                if old_spelling.is_none() {
+                    *old_spelling = spelling;
+                }
+            }
+            None => {
+                self.variants.push((variant.clone(), spelling));
+            }
+        }
+    }
+
+    pub fn generate(self) -> TokenStream {
+        let TokenEnum { item, variants } = self;
+
+        let syn::ItemEnum {
+            attrs,
+            vis,
+            enum_token: _,
+            ident,
+            generics,
+            brace_token: _,
+            variants: declared_variants,
+        } = &item;
+
+        let kind_ident = format_ident!("{}Kind", ident);
+
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+        let declared_variants: Vec<&syn::Variant> = declared_variants.iter().collect();
+
+        let n_declared_variants = declared_variants.len();
+
+        let new_variants: Vec<&syn::Ident> = variants[n_declared_variants..]
+            .iter()
+            .map(|(variant, _)| variant)
+            .collect();
+
+        let variant_idents: Vec<&syn::Ident> =
+            variants.iter().map(|(variant, _)| variant).collect();
+
+        let descriptions: Vec<String> = variants
+            .iter()
+            .map(|(variant, spelling)| match spelling {
+                Some(spelling) => format!("`{}`", spelling),
+                None => variant.to_string(),
+            })
+            .collect();
+
+        quote!(
+            #(#attrs)*
+            #vis enum #ident #generics #where_clause {
+                #(#declared_variants,)*
+                #(#new_variants,)*
+            }
+
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            #[allow(dead_code)]
+            #vis enum #kind_ident {
+                #(#variant_idents,)*
+            }
+
+            #[allow(dead_code)]
+            impl #impl_generics #ident #ty_generics #where_clause {
+                /// Kind of the token, without the fields
+                #vis fn kind(&self) -> #kind_ident {
+                    match *self {
+                        #(#ident::#variant_idents { .. } => #kind_ident::#variant_idents,)*
+                    }
+                }
+
+                /// Description of the token for error messages. See `describe` of the kind
+                /// enum.
+                #vis fn describe(&self) -> &'static str {
+                    self.kind().describe()
+                }
+            }
+
+            #[allow(dead_code)]
+            impl #kind_ident {
+                /// Description of the token kind for error messages: the literal spelling of the
+                /// token in backticks when the token is returned by a rule that matches a
+                /// literal, the variant name otherwise
+                #vis fn describe(&self) -> &'static str {
+                    match *self {
+                        #(#kind_ident::#variant_idents => #descriptions,)*
+                    }
+                }
+            }
+
+            impl ::std::fmt::Display for #kind_ident {
+                fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                    f.write_str(self.describe())
+                }
+            }
+        )
+    }
+}
@@ -0,0 +1,123 @@
+//! Convert a flex or ocamllex specification to a lexgen lexer definition.
+//!
+//! Usage: `lexgen_import [options] <file>`. Run with `--help` to see the options.
+
+use lexgen_core::import;
+
+use std::process::exit;
+
+const USAGE: &str = "\
+Usage: lexgen_import [options] <file>
+
+Converts the flex or ocamllex specification in <file> to a lexer definition in
+`lexer!` syntax, and prints it. Parts of the specification that could not be
+converted are reported as warnings.
+
+Options:
+    --flex              Convert a flex specification (default, unless <file>
+                        ends with `.mll`)
+    --ocamllex          Convert an ocamllex specification
+    -o <file>           Write the lexer definition to <file>
+    -h, --help          Print this message";
+
+#[derive(Clone, Copy)]
+enum Format {
+    Flex,
+    Ocamllex,
+}
+
+struct Args {
+    file: String,
+    format: Format,
+    output: Option<String>,
+}
+
+fn main() {
+    let args = parse_args();
+
+    let spec = std::fs::read_to_string(&args.file).unwrap_or_else(|error| {
+        eprintln!("Unable to read {}: {}", args.file, error);
+        exit(1);
+    });
+
+    let result = match args.format {
+        Format::Flex => import::flex(&spec),
+        Format::Ocamllex => import::ocamllex(&spec),
+    };
+
+    let converted = match result {
+        Ok(converted) => converted,
+        Err(error) => {
+            eprintln!("{}:{}", args.file, error);
+            exit(1);
+        }
+    };
+
+    for warning in &converted.warnings {
+        eprintln!(
+            "{}:{}: warning: {}",
+            args.file, warning.line, warning.message
+        );
+    }
+
+    match &args.output {
+        Some(output) => {
+            if let Err(error) = std::fs::write(output, &converted.definition) {
+                eprintln!("Unable to write {}: {}", output, error);
+                exit(1);
+            }
+        }
+        None => print!("{}", converted.definition),
+    }
+}
+
+fn parse_args() -> Args {
+    let mut file: Option<String> = None;
+    let mut format: Option<Format> = None;
+    let mut output: Option<String> = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--flex" => format = Some(Format::Flex),
+            "--ocamllex" => format = Some(Format::Ocamllex),
+            "-o" => {
+                output = Some(
+                    args.next()
+                        .unwrap_or_else(|| usage_error("-o needs an argument")),
+                )
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                exit(0);
+            }
+            _ if arg.starts_with('-') => usage_error(&format!("Unknown option {}", arg)),
+            _ => {
+                if file.replace(arg).is_some() {
+                    usage_error("More than one specification file given");
+                }
+            }
+        }
+    }
+
+    let file = file.unwrap_or_else(|| usage_error("Specification file is not given"));
+
+    let format = format.unwrap_or(if file.ends_with(".mll") {
+        Format::Ocamllex
+    } else {
+        Format::Flex
+    });
+
+    Args {
+        file,
+        format,
+        output,
+    }
+}
+
+fn usage_error(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    eprintln!();
+    eprintln!("{}", USAGE);
+    exit(1);
+}
@@ -0,0 +1,178 @@
+//! Print statistics and state machines of a lexer definition, and run it on sample input.
+//!
+//! Usage: `lexgen_inspect [options] <file>`, where `<file>` has the same syntax as the body of
+//! `lexer! { ... }`. Run with `--help` to see the options.
+
+use lexgen_core::inspect::Inspection;
+
+use std::process::exit;
+
+const USAGE: &str = "\
+Usage: lexgen_inspect [options] <file>
+
+Prints statistics of the lexer defined in <file>.
+
+Options:
+    --nfa                 Print NFAs of the rule sets
+    --dfa                 Print the DFA of all rule sets
+    --dot <graph>         Only print <graph> in Graphviz DOT format. <graph> is one of
+                          `nfa` (NFA of the rule set given with --rule-set), `dfa`,
+                          or `simplified-dfa` (the DFA that code is generated for)
+    --rule-set <name>     Only print the NFA of, and run, rule set <name> (default: Init)
+    --input <string>      Run the lexer on <string> and print the matched rules
+    --input-file <file>   Run the lexer on the contents of <file>
+    -h, --help            Print this message";
+
+struct Args {
+    file: String,
+    nfa: bool,
+    dfa: bool,
+    dot: Option<Dot>,
+    rule_set: Option<String>,
+    input: Option<String>,
+}
+
+enum Dot {
+    Nfa,
+    Dfa,
+    SimplifiedDfa,
+}
+
+fn main() {
+    let args = parse_args();
+
+    let definition = read_file(&args.file);
+
+    let inspection = match Inspection::new(&definition) {
+        Ok(inspection) => inspection,
+        Err(error) => {
+            eprintln!("{}: {}", args.file, error);
+            exit(1);
+        }
+    };
+
+    if let Some(rule_set) = &args.rule_set {
+        if inspection.rules(rule_set).is_none() {
+            eprintln!("Rule set {:?} is not defined", rule_set);
+            exit(1);
+        }
+    }
+
+    if let Some(dot) = &args.dot {
+        match dot {
+            Dot::Nfa => {
+                let rule_set = args.rule_set.as_deref().unwrap_or("Init");
+                print!("{}", inspection.nfa_dot(rule_set).unwrap());
+            }
+            Dot::Dfa => print!("{}", inspection.dfa_dot()),
+            Dot::SimplifiedDfa => print!("{}", inspection.simplified_dfa_dot()),
+        }
+        return;
+    }
+
+    println!("{}", inspection.stats());
+
+    if args.nfa {
+        for rule_set in inspection.rule_set_names() {
+            if args.rule_set.is_none() || args.rule_set.as_deref() == Some(rule_set) {
+                println!();
+                println!("NFA of rule set {}:", rule_set);
+                print!("{}", inspection.nfa(rule_set).unwrap());
+            }
+        }
+    }
+
+    if args.dfa {
+        println!();
+        println!("DFA:");
+        print!("{}", inspection.dfa());
+    }
+
+    if let Some(input) = &args.input {
+        let rule_set = args.rule_set.as_deref().unwrap_or("Init");
+        let run = inspection.run(rule_set, input).unwrap();
+
+        println!();
+        for match_ in &run.matches {
+            println!(
+                "{:?}: rule {}: {}",
+                match_.text, match_.rule_idx, match_.rule
+            );
+        }
+
+        if let Some(error) = run.error {
+            println!(
+                "error at byte {}: no rule matches {:?}",
+                error,
+                &input[error..]
+            );
+        }
+    }
+}
+
+fn parse_args() -> Args {
+    let mut file: Option<String> = None;
+    let mut nfa = false;
+    let mut dfa = false;
+    let mut dot: Option<Dot> = None;
+    let mut rule_set: Option<String> = None;
+    let mut input: Option<String> = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--nfa" => nfa = true,
+            "--dfa" => dfa = true,
+            "--dot" => {
+                dot = Some(match option_value(&arg, args.next()).as_str() {
+                    "nfa" => Dot::Nfa,
+                    "dfa" => Dot::Dfa,
+                    "simplified-dfa" => Dot::SimplifiedDfa,
+                    other => usage_error(&format!("Unknown graph {}", other)),
+                })
+            }
+            "--rule-set" => rule_set = Some(option_value(&arg, args.next())),
+            "--input" => input = Some(option_value(&arg, args.next())),
+            "--input-file" => input = Some(read_file(&option_value(&arg, args.next()))),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                exit(0);
+            }
+            _ if arg.starts_with('-') => usage_error(&format!("Unknown option {}", arg)),
+            _ => {
+                if file.replace(arg).is_some() {
+                    usage_error("More than one lexer definition file given");
+                }
+            }
+        }
+    }
+
+    let file = file.unwrap_or_else(|| usage_error("Lexer definition file is not given"));
+
+    Args {
+        file,
+        nfa,
+        dfa,
+        dot,
+        rule_set,
+        input,
+    }
+}
+
+fn option_value(option: &str, value: Option<String>) -> String {
+    value.unwrap_or_else(|| usage_error(&format!("{} needs an argument", option)))
+}
+
+fn read_file(path: &str) -> String {
+    std::fs::read_to_string(path).unwrap_or_else(|error| {
+        eprintln!("Unable to read {}: {}", path, error);
+        exit(1);
+    })
+}
+
+fn usage_error(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    eprintln!();
+    eprintln!("{}", USAGE);
+    exit(1);
+}
@@ -1,14 +1,33 @@
 #![allow(clippy::should_implement_trait, clippy::type_complexity)]
 
+use std::borrow::Cow;
+use std::fmt;
 use std::iter::Peekable;
+use std::ops::Range;
 use std::str::Chars;
 
 use unicode_width::UnicodeWidthChar;
 
+/// An error raised by a lexer. `L` is `SourceLoc` when the lexer is wrapped with `with_source`.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct LexerError<E> {
-    pub location: Loc,
+pub struct LexerError<E, L = Loc> {
+    pub location: L,
     pub kind: LexerErrorKind<E>,
+
+    /// Details of `InvalidToken` errors raised by lexgen-generated code. `None` for custom
+    /// errors.
+    pub details: Option<InvalidTokenDetails>,
+}
+
+impl<E, L> LexerError<E, L> {
+    /// Create an error without details
+    pub fn new(location: L, kind: LexerErrorKind<E>) -> Self {
+        LexerError {
+            location,
+            kind,
+            details: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -20,14 +39,81 @@ pub enum LexerErrorKind<E> {
     Custom(E),
 }
 
+/// Details of an `InvalidToken` error, see `details` field of `LexerError`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidTokenDetails {
+    /// The character that could not be matched. `None` when the input ended unexpectedly.
+    pub char: Option<char>,
+
+    /// Description of the characters that the lexer could accept at the location of `char`, e.g.
+    /// `'0'-'9', '_', end of input`. Empty when the lexer can't accept any character.
+    pub expected: &'static str,
+
+    /// Name of the rule set that was active when the error was raised. `Init` for the initial,
+    /// unnamed rule set.
+    pub rule_set: &'static str,
+}
+
 /// A location, used in errors
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Loc {
     pub line: u32,
     pub col: u32,
     pub byte_idx: usize,
 }
 
+impl<E: fmt::Display, L: fmt::Display> fmt::Display for LexerError<E, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.kind)?;
+        if let Some(details) = &self.details {
+            write!(f, ": {}", details)?;
+        }
+        Ok(())
+    }
+}
+
+/// Shows the details as `unexpected <char>, expected <expected>`
+impl fmt::Display for InvalidTokenDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.char {
+            Some(char) => write!(f, "unexpected {:?}", char)?,
+            None => f.write_str("unexpected end of input")?,
+        }
+        if !self.expected.is_empty() {
+            write!(f, ", expected {}", self.expected)?;
+        }
+        Ok(())
+    }
+}
+
+impl<E: std::error::Error + 'static, L: fmt::Debug + fmt::Display> std::error::Error
+    for LexerError<E, L>
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            LexerErrorKind::InvalidToken => None,
+            LexerErrorKind::Custom(err) => Some(err),
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for LexerErrorKind<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexerErrorKind::InvalidToken => f.write_str("invalid token"),
+            LexerErrorKind::Custom(err) => err.fmt(f),
+        }
+    }
+}
+
+/// Shows the location as `line:col`. Lines and columns are 0-based in `Loc`, but shown 1-based,
+/// as in editors and compiler messages.
+impl fmt::Display for Loc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line + 1, self.col + 1)
+    }
+}
+
 impl Loc {
     const ZERO: Loc = Loc {
         line: 0,
@@ -36,6 +122,245 @@ impl Loc {
     };
 }
 
+/// A location with a source (e.g. file) identifier. Lexers wrapped with `with_source` method of
+/// generated lexers yield these instead of `Loc`s.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SourceLoc<Id> {
+    pub source: Id,
+    pub loc: Loc,
+}
+
+/// Shows the location as `source:line:col`
+impl<Id: fmt::Display> fmt::Display for SourceLoc<Id> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.source, self.loc)
+    }
+}
+
+/// Locations yielded by lexers: `Loc`, or `SourceLoc` when the lexer is wrapped with
+/// `with_source`. Used to build `Span`s.
+pub trait Location {
+    /// Type of the source identifier, `()` for `Loc`
+    type Source;
+
+    fn into_parts(self) -> (Self::Source, Loc);
+}
+
+impl Location for Loc {
+    type Source = ();
+
+    fn into_parts(self) -> ((), Loc) {
+        ((), self)
+    }
+}
+
+impl<Id> Location for SourceLoc<Id> {
+    type Source = Id;
+
+    fn into_parts(self) -> (Id, Loc) {
+        (self.source, self.loc)
+    }
+}
+
+/// A span in the input: start location (inclusive) and end location (exclusive), and the source
+/// identifier when the lexer is wrapped with `with_source`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span<Id = ()> {
+    pub start: Loc,
+    pub end: Loc,
+    pub source: Id,
+}
+
+impl Span {
+    pub fn new(start: Loc, end: Loc) -> Span {
+        Span {
+            start,
+            end,
+            source: (),
+        }
+    }
+}
+
+impl<Id> Span<Id> {
+    pub fn with_source(start: Loc, end: Loc, source: Id) -> Span<Id> {
+        Span { start, end, source }
+    }
+
+    /// Byte indices of the span, for use with e.g. `codespan-reporting`, `ariadne`, `miette`
+    pub fn byte_range(&self) -> Range<usize> {
+        self.start.byte_idx..self.end.byte_idx
+    }
+
+    /// Length of the span in bytes. Spans with `end` before `start` are empty.
+    pub fn len(&self) -> usize {
+        self.end.byte_idx.saturating_sub(self.start.byte_idx)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The smallest span that contains both spans. The spans should be in the same source, the
+    /// source of `self` is used in the result.
+    pub fn merge(self, other: Span<Id>) -> Span<Id> {
+        Span {
+            start: if other.start.byte_idx < self.start.byte_idx {
+                other.start
+            } else {
+                self.start
+            },
+            end: if other.end.byte_idx > self.end.byte_idx {
+                other.end
+            } else {
+                self.end
+            },
+            source: self.source,
+        }
+    }
+
+    /// Whether the byte at `byte_idx` is within this span
+    pub fn contains_byte(&self, byte_idx: usize) -> bool {
+        self.byte_range().contains(&byte_idx)
+    }
+}
+
+impl<Id: PartialEq> Span<Id> {
+    /// Whether `other` is within this span
+    pub fn contains(&self, other: &Span<Id>) -> bool {
+        self.source == other.source
+            && self.start.byte_idx <= other.start.byte_idx
+            && other.end.byte_idx <= self.end.byte_idx
+    }
+}
+
+impl From<(Loc, Loc)> for Span {
+    fn from((start, end): (Loc, Loc)) -> Span {
+        Span::new(start, end)
+    }
+}
+
+impl<Id> From<(SourceLoc<Id>, SourceLoc<Id>)> for Span<Id> {
+    fn from((start, end): (SourceLoc<Id>, SourceLoc<Id>)) -> Span<Id> {
+        Span {
+            start: start.loc,
+            end: end.loc,
+            source: start.source,
+        }
+    }
+}
+
+impl<Id> From<Span<Id>> for Range<usize> {
+    fn from(span: Span<Id>) -> Range<usize> {
+        span.byte_range()
+    }
+}
+
+/// Shows the span as `line:col-line:col`, see `Display` implementation of `Loc`. The source
+/// identifier is not shown.
+impl<Id> fmt::Display for Span<Id> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+/// A value with its span in the input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Spanned<T, Id = ()> {
+    pub value: T,
+    pub span: Span<Id>,
+}
+
+/// An iterator adapter that yields `Spanned` tokens instead of `(Loc, Token, Loc)` triples
+/// yielded by lexers. Returned by `spanned` method of generated lexers and `WithSource`.
+#[derive(Debug, Clone)]
+pub struct SpannedIter<I> {
+    iter: I,
+}
+
+impl<I> SpannedIter<I> {
+    pub fn new(iter: I) -> SpannedIter<I> {
+        SpannedIter { iter }
+    }
+
+    /// Returns the wrapped lexer
+    pub fn into_inner(self) -> I {
+        self.iter
+    }
+}
+
+impl<I: Iterator<Item = Result<(L, T, L), E>>, L: Location, T, E> Iterator for SpannedIter<I> {
+    type Item = Result<Spanned<T, L::Source>, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|result| {
+            result.map(|(start, value, end)| {
+                let (source, start) = start.into_parts();
+                let (_, end) = end.into_parts();
+                Spanned {
+                    value,
+                    span: Span { start, end, source },
+                }
+            })
+        })
+    }
+}
+
+/// An iterator adapter that attaches a source identifier to locations yielded by a lexer.
+/// Returned by `with_source` method of generated lexers.
+#[derive(Debug, Clone)]
+pub struct WithSource<I, Id> {
+    iter: I,
+    source: Id,
+}
+
+impl<I, Id> WithSource<I, Id> {
+    pub fn new(iter: I, source: Id) -> WithSource<I, Id> {
+        WithSource { iter, source }
+    }
+
+    pub fn source(&self) -> &Id {
+        &self.source
+    }
+
+    /// Returns the wrapped lexer
+    pub fn into_inner(self) -> I {
+        self.iter
+    }
+
+    /// Yield `Spanned` tokens instead of `(SourceLoc, Token, SourceLoc)` triples
+    pub fn spanned(self) -> SpannedIter<Self> {
+        SpannedIter::new(self)
+    }
+}
+
+impl<I, Id, T, E> Iterator for WithSource<I, Id>
+where
+    I: Iterator<Item = Result<(Loc, T, Loc), LexerError<E>>>,
+    Id: Clone,
+{
+    type Item = Result<(SourceLoc<Id>, T, SourceLoc<Id>), LexerError<E, SourceLoc<Id>>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let source = &self.source;
+        let source_loc = |loc: Loc| SourceLoc {
+            source: source.clone(),
+            loc,
+        };
+        self.iter.next().map(|result| match result {
+            Ok((start, token, end)) => Ok((source_loc(start), token, source_loc(end))),
+            Err(LexerError {
+                location,
+                kind,
+                details,
+            }) => Err(LexerError {
+                location: source_loc(location),
+                kind,
+                details,
+            }),
+        })
+    }
+}
+
 /// **Do not use**
 // Possible outcomes of a semantic action
 pub enum SemanticActionResult<T> {
@@ -75,6 +400,15 @@ pub struct Lexer<'input, Iter: Iterator<Item = char> + Clone, Token, State, Erro
     // User-provided input string. Does not change after initialization.
     input: &'input str,
 
+    // Whether the lexer was initialized with a string. When not, `input` is empty and the input
+    // is only available via `__iter`.
+    input_is_str: bool,
+
+    // Byte index of the start of `input` in the enclosing document, when the lexer is started at
+    // a location other than `Loc::ZERO` with `start_at`. Locations are relative to the enclosing
+    // document, subtract this from byte indices before indexing `input`.
+    input_byte_offset: usize,
+
     // Start location of `iter`. We update this as we backtrack and update `iter`.
     iter_loc: Loc,
 
@@ -89,16 +423,31 @@ pub struct Lexer<'input, Iter: Iterator<Item = char> + Clone, Token, State, Erro
     // End of the current match
     current_match_end: Loc,
 
-    // If we skipped an accepting state, this holds the triple:
+    // Character iterator at the start of the current match, when the lexer is not initialized
+    // with a string. Used to get the current match as a string in `match_str`.
+    match_start_iter: Option<Peekable<Iter>>,
+
+    // The character before `current_match_end`. `None` when we haven't consumed any characters
+    // yet.
+    last_char: Option<char>,
+
+    // The character before the location where the current rule set started matching. Used to
+    // check `^` (beginning of line) and `^^` (beginning of input) anchors.
+    rule_start_last_char: Option<char>,
+
+    // If we skipped an accepting state, this holds:
     //
     // - Skipped match start (byte index in `input`)
+    // - Character iterator at the end of the skipped match
     // - Semantic action (a function name)
     // - Skipped match end (exclusive, byte index in `input`)
+    // - Last character of the skipped match
     last_match: Option<(
         Loc,
         Peekable<Iter>,
         for<'lexer> fn(&'lexer mut Wrapper) -> SemanticActionResult<Result<Token, Error>>,
         Loc,
+        Option<char>,
     )>,
 }
 
@@ -116,10 +465,15 @@ impl<I: Iterator<Item = char> + Clone, T, S, E, W> Lexer<'static, I, T, S, E, W>
             __initial_state: 0,
             user_state: state,
             input: "",
+            input_is_str: false,
+            input_byte_offset: 0,
             iter_loc: Loc::ZERO,
-            __iter: iter.peekable(),
+            __iter: iter.clone().peekable(),
             current_match_start: Loc::ZERO,
             current_match_end: Loc::ZERO,
+            match_start_iter: Some(iter.peekable()),
+            last_char: None,
+            rule_start_last_char: None,
             last_match: None,
         }
     }
@@ -139,21 +493,40 @@ impl<'input, T, S, E, W> Lexer<'input, Chars<'input>, T, S, E, W> {
             __initial_state: 0,
             user_state: state,
             input,
+            input_is_str: true,
+            input_byte_offset: 0,
             iter_loc: Loc::ZERO,
             __iter: input.chars().peekable(),
             current_match_start: Loc::ZERO,
             current_match_end: Loc::ZERO,
+            match_start_iter: None,
+            last_char: None,
+            rule_start_last_char: None,
             last_match: None,
         }
     }
 }
 
 impl<'input, I: Iterator<Item = char> + Clone, T, S, E, W> Lexer<'input, I, T, S, E, W> {
+    // Set the location of the beginning of the input, and the character before it in the
+    // document (`None` at the beginning of the document). Used when the input is a part of a
+    // larger document, to report locations in the document, and to check anchors and left
+    // contexts at the beginning of the input. Should be called before lexing.
+    pub fn start_at(&mut self, loc: Loc, prev_char: Option<char>) {
+        self.input_byte_offset = loc.byte_idx;
+        self.iter_loc = loc;
+        self.current_match_start = loc;
+        self.current_match_end = loc;
+        self.last_char = prev_char;
+        self.rule_start_last_char = prev_char;
+    }
+
     // Read the next chracter
     pub fn next(&mut self) -> Option<char> {
         match self.__iter.next() {
             None => None,
             Some(char) => {
+                self.last_char = Some(char);
                 self.current_match_end.byte_idx += char.len_utf8();
                 if char == '\n' {
                     self.current_match_end.line += 1;
@@ -172,10 +545,89 @@ impl<'input, I: Iterator<Item = char> + Clone, T, S, E, W> Lexer<'input, I, T, S
         self.__iter.peek().copied()
     }
 
+    // Look ahead `n` characters, without consuming. `peek_nth(0)` is the same as `peek()`.
+    pub fn peek_nth(&self, n: usize) -> Option<char> {
+        self.__iter.clone().nth(n)
+    }
+
+    // Look ahead at most `len` characters, without consuming. Returns less than `len` characters
+    // when the input ends before that. Borrows from the input when the lexer is initialized with a
+    // string, allocates otherwise.
+    pub fn peek_str(&self, len: usize) -> Cow<'input, str> {
+        if self.input_is_str {
+            let rest = &self.input[self.current_match_end.byte_idx - self.input_byte_offset..];
+            let end = rest
+                .char_indices()
+                .nth(len)
+                .map_or(rest.len(), |(byte_idx, _)| byte_idx);
+            Cow::Borrowed(&rest[..end])
+        } else {
+            Cow::Owned(self.__iter.clone().take(len).collect())
+        }
+    }
+
+    // Check if the remaining input starts with the given string, without consuming
+    pub fn starts_with(&self, prefix: &str) -> bool {
+        let mut iter = self.__iter.clone();
+        prefix.chars().all(|char| iter.next() == Some(char))
+    }
+
+    // Consume characters while the predicate holds. Consumed characters become a part of the
+    // current match.
+    pub fn skip_while<F: FnMut(char) -> bool>(&mut self, mut pred: F) {
+        while let Some(char) = self.peek() {
+            if !pred(char) {
+                break;
+            }
+            self.next();
+        }
+    }
+
+    // Consume characters until `str` is seen, including `str`. Consumed characters become a part
+    // of the current match. Returns `false` if the input ends before `str` is seen, in which case
+    // the rest of the input is consumed.
+    pub fn skip_until(&mut self, str: &str) -> bool {
+        loop {
+            if self.starts_with(str) {
+                for _ in str.chars() {
+                    self.next();
+                }
+                return true;
+            }
+            if self.next().is_none() {
+                return false;
+            }
+        }
+    }
+
     // On success returns semantic action function for the last match
     pub fn backtrack(
         &mut self,
     ) -> Result<for<'lexer> fn(&'lexer mut W) -> SemanticActionResult<Result<T, E>>, LexerError<E>>
+    {
+        self.backtrack_(None)
+    }
+
+    // Same as `backtrack`, but adds the given details to the error when there isn't a match to
+    // backtrack to
+    pub fn backtrack_with_details(
+        &mut self,
+        char: Option<char>,
+        expected: &'static str,
+        rule_set: &'static str,
+    ) -> Result<for<'lexer> fn(&'lexer mut W) -> SemanticActionResult<Result<T, E>>, LexerError<E>>
+    {
+        self.backtrack_(Some(InvalidTokenDetails {
+            char,
+            expected,
+            rule_set,
+        }))
+    }
+
+    fn backtrack_(
+        &mut self,
+        details: Option<InvalidTokenDetails>,
+    ) -> Result<for<'lexer> fn(&'lexer mut W) -> SemanticActionResult<Result<T, E>>, LexerError<E>>
     {
         match self.last_match.take() {
             None => {
@@ -183,12 +635,14 @@ impl<'input, I: Iterator<Item = char> + Clone, T, S, E, W> Lexer<'input, I, T, S
                 Err(LexerError {
                     location: self.current_match_start,
                     kind: LexerErrorKind::InvalidToken,
+                    details,
                 })
             }
-            Some((match_start, iter, semantic_action, match_end)) => {
+            Some((match_start, iter, semantic_action, match_end, last_char)) => {
                 self.__done = false;
                 self.current_match_start = match_start;
                 self.current_match_end = match_end;
+                self.last_char = last_char;
                 self.__iter = iter;
                 self.iter_loc = match_end;
                 Ok(semantic_action)
@@ -209,15 +663,60 @@ impl<'input, I: Iterator<Item = char> + Clone, T, S, E, W> Lexer<'input, I, T, S
             self.__iter.clone(),
             semantic_action_fn,
             self.current_match_end,
+            self.last_char,
         ));
     }
 
+    // Called in initial states of rule sets, to be able to check anchors in accepting states
+    pub fn mark_rule_start(&mut self) {
+        self.rule_start_last_char = self.last_char;
+    }
+
+    // Whether the current rule set started matching at the beginning of a line
+    pub fn at_line_start(&self) -> bool {
+        matches!(self.rule_start_last_char, None | Some('\n'))
+    }
+
+    // Whether the current rule set started matching at the beginning of the input
+    pub fn at_input_start(&self) -> bool {
+        self.rule_start_last_char.is_none()
+    }
+
+    // The character before the location where the current rule set started matching. Used to
+    // check left contexts.
+    pub fn rule_start_last_char(&self) -> Option<char> {
+        self.rule_start_last_char
+    }
+
     pub fn reset_match(&mut self) {
         self.current_match_start = self.current_match_end;
+        if !self.input_is_str {
+            self.match_start_iter = Some(self.__iter.clone());
+        }
     }
 
     pub fn match_(&self) -> &'input str {
-        &self.input[self.current_match_start.byte_idx..self.current_match_end.byte_idx]
+        &self.input[self.current_match_start.byte_idx - self.input_byte_offset
+            ..self.current_match_end.byte_idx - self.input_byte_offset]
+    }
+
+    // Same as `match_`, but also works when the lexer is not initialized with a string, in which
+    // case it allocates
+    pub fn match_str(&self) -> Cow<'input, str> {
+        match &self.match_start_iter {
+            None => Cow::Borrowed(self.match_()),
+            Some(iter) => {
+                let len = self.current_match_end.byte_idx - self.current_match_start.byte_idx;
+                let mut str = String::with_capacity(len);
+                for char in iter.clone() {
+                    if str.len() >= len {
+                        break;
+                    }
+                    str.push(char);
+                }
+                Cow::Owned(str)
+            }
+        }
     }
 
     pub fn match_loc(&self) -> (Loc, Loc) {